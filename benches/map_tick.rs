@@ -0,0 +1,289 @@
+//! Rough timing of the map work done every tick (gas flow plus mob path lookups) and of the
+//! tile-change checks the UI makes, on a 200x200 maze.
+//!
+//! The gas and path timings are also taken on a copy of the hash map storage the map used before
+//! the chunked grid, for comparison.
+//!
+//! Run with `cargo bench --bench map_tick`.
+
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use radishes::resources::{Map, MovementKind, PathTarget, Tile, WorldBounds};
+
+const SIZE: i32 = 200;
+const TICKS: u32 = 100;
+const GAS_TRAP_SPACING: i32 = 20;
+const MOB_SPACING: i32 = 4;
+const CORE: (i32, i32) = (SIZE - 1, SIZE - 1);
+
+/// Serpentine maze: a wall every fourth column, with the gap alternating between top and bottom,
+/// a spawn in one corner and the core in the opposite one.
+fn is_wall(x: i32, y: i32) -> bool {
+    // the core's corner would otherwise be part of the last wall
+    if (x, y) == CORE {
+        return false;
+    }
+
+    x % 4 == 3 && (if (x / 4) % 2 == 0 { y != SIZE - 1 } else { y != 0 })
+}
+
+fn build_map() -> Map {
+    let mut map = Map::new(WorldBounds {
+        x_min: 0,
+        y_min: 0,
+        x_max: SIZE,
+        y_max: SIZE,
+    });
+
+    for x in 0..SIZE {
+        for y in 0..SIZE {
+            map.set_tile(x, y, if is_wall(x, y) { Tile::Wall } else { Tile::Open });
+        }
+    }
+
+    map.set_spawns(vec![(0, 0)].into_iter().collect());
+    map.set_cores(vec![CORE].into_iter().collect());
+
+    map
+}
+
+fn build_before_map() -> before::Map {
+    let mut tiles = HashMap::new();
+
+    for x in 0..SIZE {
+        for y in 0..SIZE {
+            tiles.insert((x, y), if is_wall(x, y) { Tile::Wall } else { Tile::Open });
+        }
+    }
+
+    before::Map::new(tiles, CORE)
+}
+
+fn open_tiles(spacing: i32) -> Vec<(i32, i32)> {
+    let mut out = Vec::new();
+    for x in (0..SIZE).step_by(spacing as usize) {
+        for y in (0..SIZE).step_by(spacing as usize) {
+            if !is_wall(x, y) {
+                out.push((x, y));
+            }
+        }
+    }
+    out
+}
+
+fn per_iter(total: Duration, iters: u32) -> String {
+    format!("{:>10.1} us", total.as_secs_f64() * 1_000_000. / iters as f64)
+}
+
+/// Time spent on gas and on path lookups, over all the ticks
+fn time_ticks<M>(
+    map: &mut M,
+    add_gas: impl Fn(&mut M, i32, i32),
+    tick_gas: impl Fn(&mut M),
+    move_mob: impl Fn(&M, i32, i32) -> (i32, i32),
+) -> (Duration, Duration) {
+    let gas_traps = open_tiles(GAS_TRAP_SPACING);
+    let mobs = open_tiles(MOB_SPACING);
+
+    let mut gas_time = Duration::default();
+    let mut path_time = Duration::default();
+
+    for _ in 0..TICKS {
+        let start = Instant::now();
+        for (x, y) in gas_traps.iter().copied() {
+            add_gas(map, x, y);
+        }
+        tick_gas(map);
+        gas_time += start.elapsed();
+
+        let start = Instant::now();
+        for (x, y) in mobs.iter().copied() {
+            std::hint::black_box(move_mob(map, x, y));
+        }
+        path_time += start.elapsed();
+    }
+
+    (gas_time, path_time)
+}
+
+fn main() {
+    println!(
+        "{} gas traps, {} mobs, {} ticks\n",
+        open_tiles(GAS_TRAP_SPACING).len(),
+        open_tiles(MOB_SPACING).len(),
+        TICKS
+    );
+    println!("                                 before          after");
+
+    let start = Instant::now();
+    let mut before = build_before_map();
+    let before_build = start.elapsed();
+
+    let start = Instant::now();
+    let mut map = build_map();
+    println!(
+        "build map:                  {}  {}",
+        per_iter(before_build, 1),
+        per_iter(start.elapsed(), 1)
+    );
+
+    let (before_gas, before_paths) = time_ticks(
+        &mut before,
+        |map, x, y| map.add_gas_to_tile(x, y, 10),
+        |map| map.tick_gas_map(),
+        |map, x, y| map.move_toward_spawn(x, y),
+    );
+    let (gas_time, path_time) = time_ticks(
+        &mut map,
+        |map, x, y| map.add_gas_to_tile(x, y, 10),
+        |map| map.tick_gas_map(),
+        |map, x, y| map.move_toward_spawn(MovementKind::Walker, PathTarget::NearestCore, x, y),
+    );
+
+    println!(
+        "gas tick:                   {}  {}",
+        per_iter(before_gas, TICKS),
+        per_iter(gas_time, TICKS)
+    );
+    println!(
+        "path lookups:               {}  {}",
+        per_iter(before_paths, TICKS),
+        per_iter(path_time, TICKS)
+    );
+    println!(
+        "full tick:                  {}  {}",
+        per_iter(before_gas + before_paths, TICKS),
+        per_iter(gas_time + path_time, TICKS)
+    );
+
+    // Blocking the maze near the spawn forces a detour check across most of the map
+    let (x, y) = (1, SIZE / 2);
+
+    let start = Instant::now();
+    for _ in 0..TICKS {
+        std::hint::black_box(map.can_set_tile(x, y, Tile::Wall));
+    }
+    println!("can_set_tile:                            -  {}", per_iter(start.elapsed(), TICKS));
+
+    let start = Instant::now();
+    for _ in 0..TICKS {
+        map.set_tile(x, y, Tile::Wall);
+        map.set_tile(x, y, Tile::Open);
+    }
+    println!("set_tile (wall, then open):              -  {}", per_iter(start.elapsed(), TICKS));
+}
+
+/// The map as it was stored before the chunked grid: tiles, path costs and gas all in hash maps
+/// keyed by position, with gas flowing from every tile in sorted order. Kept here so the
+/// "before" numbers can be reproduced alongside the current ones.
+mod before {
+    use std::collections::{HashMap, VecDeque};
+
+    use radishes::resources::Tile;
+
+    const FLUIDITY: i32 = 6;
+    const DISPERSAL: i32 = 1;
+
+    pub struct Map {
+        tiles: HashMap<(i32, i32), Tile>,
+        costs: HashMap<(i32, i32), i32>,
+        gas: HashMap<(i32, i32), i32>,
+    }
+
+    fn neighbors(x: i32, y: i32) -> [(i32, i32); 4] {
+        [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)]
+    }
+
+    impl Map {
+        pub fn new(tiles: HashMap<(i32, i32), Tile>, core: (i32, i32)) -> Self {
+            let mut map = Map {
+                tiles,
+                costs: HashMap::new(),
+                gas: HashMap::new(),
+            };
+
+            // every step costs the same, so a breadth-first search gives the same costs as Dijkstra
+            let mut to_process = VecDeque::new();
+            map.costs.insert(core, 0);
+            to_process.push_back(core);
+
+            while let Some(pos) = to_process.pop_front() {
+                let cost = map.costs[&pos];
+                for next in neighbors(pos.0, pos.1).iter().copied() {
+                    if map.is_passable(next) && !map.costs.contains_key(&next) {
+                        map.costs.insert(next, cost + 1);
+                        to_process.push_back(next);
+                    }
+                }
+            }
+
+            map
+        }
+
+        fn is_passable(&self, pos: (i32, i32)) -> bool {
+            self.tiles.get(&pos).copied().unwrap_or(Tile::Wall) != Tile::Wall
+        }
+
+        pub fn add_gas_to_tile(&mut self, x: i32, y: i32, amount: i32) {
+            *self.gas.entry((x, y)).or_insert(0) += amount;
+        }
+
+        pub fn tick_gas_map(&mut self) {
+            self.gas.values_mut().for_each(|amt| *amt = (*amt - DISPERSAL).max(0));
+            self.gas.retain(|_, amt| *amt != 0);
+
+            let mut flow_from: Vec<(i32, i32)> = self.tiles.keys().copied().collect();
+            flow_from.sort();
+
+            for (tile_x, tile_y) in flow_from {
+                let mut neighbors: Vec<(i32, i32)> = neighbors(tile_x, tile_y)
+                    .iter()
+                    .copied()
+                    .filter(|pos| self.is_passable(*pos))
+                    .collect();
+
+                let mut remaining_fluidity = FLUIDITY;
+                let mut neighbor_index = 0;
+                let mut self_amount = self.gas.get(&(tile_x, tile_y)).copied().unwrap_or(0);
+
+                while remaining_fluidity > 0 && !neighbors.is_empty() && self_amount > 1 {
+                    neighbor_index %= neighbors.len();
+
+                    let neighbor = self.gas.entry(neighbors[neighbor_index]).or_insert(0);
+
+                    if *neighbor + 1 < self_amount {
+                        self_amount -= 1;
+                        remaining_fluidity -= 1;
+                        *neighbor += 1;
+                        neighbor_index += 1;
+                    } else {
+                        neighbors.remove(neighbor_index);
+                    }
+                }
+
+                self.gas.insert((tile_x, tile_y), self_amount);
+            }
+
+            self.gas.retain(|_, amt| *amt != 0);
+        }
+
+        pub fn move_toward_spawn(&self, start_x: i32, start_y: i32) -> (i32, i32) {
+            let mut least_cost = self.costs.get(&(start_x, start_y)).copied().unwrap_or(i32::MAX);
+            let mut winning_coords = (start_x, start_y);
+
+            for pos in neighbors(start_x, start_y).iter().copied().filter(|pos| self.is_passable(*pos)) {
+                let cost = self.costs.get(&pos).copied().unwrap_or(i32::MAX);
+
+                if cost < least_cost {
+                    least_cost = cost;
+                    winning_coords = pos;
+                }
+            }
+
+            winning_coords
+        }
+    }
+}
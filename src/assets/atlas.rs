@@ -0,0 +1,151 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+
+use wasm_bindgen::JsValue;
+
+use web_sys::{CanvasRenderingContext2d, ImageBitmap};
+
+use crate::{components::SpriteId, resources::Tile};
+
+use super::ImageBitmapExt;
+
+/// Where one frame sits in the atlas image, in pixels
+#[derive(Deserialize, Copy, Clone, Eq, PartialEq, Debug)]
+pub struct Frame {
+    pub x: i32,
+    pub y: i32,
+    pub w: i32,
+    pub h: i32,
+}
+
+/// Frames shown one after another, each for the same length of time, looping forever
+#[derive(Deserialize, Clone, Eq, PartialEq, Debug)]
+pub struct Animation {
+    pub frames: Vec<String>,
+    pub frame_ms: u32,
+}
+
+/// Describes the contents of the atlas image. Sprites are looked up by name, and a name can be
+/// either a single frame or an animation (animations win if both exist).
+#[derive(Deserialize, Clone, Eq, PartialEq, Debug)]
+pub struct AtlasManifest {
+    pub frames: HashMap<String, Frame>,
+    #[serde(default)]
+    pub animations: HashMap<String, Animation>,
+    /// Sprite to draw for each kind of tile; tiles without one are drawn as flat colors
+    #[serde(default)]
+    pub tiles: HashMap<Tile, String>,
+}
+
+impl AtlasManifest {
+    /// See `SpriteAtlas::frame`; the lookup doesn't need the image
+    pub fn frame(&self, name: &str, time_ms: f64) -> Option<Frame> {
+        match self.animations.get(name) {
+            Some(animation) if !animation.frames.is_empty() => {
+                let ticks = (time_ms / animation.frame_ms.max(1) as f64) as u64;
+                let index = (ticks % animation.frames.len() as u64) as usize;
+                self.frames.get(&animation.frames[index]).copied()
+            }
+            _ => self.frames.get(name).copied(),
+        }
+    }
+
+    pub fn tile_frame(&self, tile: Tile, time_ms: f64) -> Option<Frame> {
+        let name = self.tiles.get(&tile)?;
+        self.frame(name, time_ms)
+    }
+}
+
+/// One image holding every sprite, plus the manifest saying where they are
+pub struct SpriteAtlas {
+    image: ImageBitmap,
+    manifest: AtlasManifest,
+}
+
+impl SpriteAtlas {
+    pub fn new(image: ImageBitmap, manifest: AtlasManifest) -> Self {
+        SpriteAtlas { image, manifest }
+    }
+
+    /// The frame to show for the named sprite at the given time (in milliseconds, from any fixed
+    /// starting point), or None if the manifest doesn't know the name
+    pub fn frame(&self, name: &str, time_ms: f64) -> Option<Frame> {
+        self.manifest.frame(name, time_ms)
+    }
+
+    pub fn sprite_frame(&self, sprite: SpriteId, time_ms: f64) -> Option<Frame> {
+        self.frame(sprite.0, time_ms)
+    }
+
+    pub fn tile_frame(&self, tile: Tile, time_ms: f64) -> Option<Frame> {
+        self.manifest.tile_frame(tile, time_ms)
+    }
+
+    /// Draw the frame, unscaled, with its top left corner at the given canvas coordinates
+    pub fn draw(&self, ctx: &CanvasRenderingContext2d, frame: Frame, dx: i32, dy: i32) -> Result<(), JsValue> {
+        self.image.render_to_canvas(ctx, frame.x, frame.y, dx, dy, frame.w, frame.h)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::resources::ALL_TILES;
+
+    use super::*;
+
+    fn shipped_manifest() -> AtlasManifest {
+        serde_json::from_str(include_str!("../../static/assets/atlas.json")).expect("atlas.json should parse")
+    }
+
+    /// Every time an animation could show a different frame, over one full loop
+    fn times_through(manifest: &AtlasManifest, name: &str) -> Vec<f64> {
+        match manifest.animations.get(name) {
+            Some(animation) => (0..animation.frames.len())
+                .map(|i| (i as u32 * animation.frame_ms) as f64)
+                .collect(),
+            None => vec![0.],
+        }
+    }
+
+    #[test]
+    fn every_sprite_has_a_frame() {
+        let manifest = shipped_manifest();
+
+        for sprite in SpriteId::ALL.iter() {
+            for time_ms in times_through(&manifest, sprite.0) {
+                assert!(
+                    manifest.frame(sprite.0, time_ms).is_some(),
+                    "no frame for {:?} at {}ms",
+                    sprite,
+                    time_ms
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn every_tile_has_a_frame() {
+        let manifest = shipped_manifest();
+
+        for tile in ALL_TILES.iter().copied() {
+            // fails to compile when a tile is added, as a reminder to add it to ALL_TILES
+            match tile {
+                Tile::Open | Tile::Wall | Tile::Mud | Tile::Road | Tile::Water => {}
+            }
+
+            let name = manifest
+                .tiles
+                .get(&tile)
+                .unwrap_or_else(|| panic!("no sprite named for {:?}", tile));
+            for time_ms in times_through(&manifest, name) {
+                assert!(
+                    manifest.tile_frame(tile, time_ms).is_some(),
+                    "no frame for {:?} at {}ms",
+                    tile,
+                    time_ms
+                );
+            }
+        }
+    }
+}
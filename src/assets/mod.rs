@@ -4,26 +4,17 @@ use wasm_bindgen_futures::JsFuture;
 
 use web_sys::{Blob, CanvasRenderingContext2d, ImageBitmap, Request, RequestInit, RequestMode, Response, Window};
 
+mod atlas;
+
+pub use atlas::{AtlasManifest, SpriteAtlas};
+
 pub struct Assets {
-    pub gas_trap: ImageBitmap,
-    pub gas_image: ImageBitmap,
+    pub atlas: SpriteAtlas,
 }
 
 pub trait ImageBitmapExt {
     fn render_to_canvas(&self, ctx: &CanvasRenderingContext2d, sx: i32, sy: i32, dx: i32, dy: i32, sw: i32, sh: i32)
         -> Result<(), JsValue>;
-
-    fn render_to_canvas_tile(&self, ctx: &CanvasRenderingContext2d, sx: i32, sy: i32, dx: i32, dy: i32) -> Result<(), JsValue> {
-        self.render_to_canvas(
-            ctx,
-            sx,
-            sy,
-            dx,
-            dy,
-            crate::tile_helpers::TILE_WIDTH_PIXELS,
-            crate::tile_helpers::TILE_HEIGHT_PIXELS,
-        )
-    }
 }
 
 impl ImageBitmapExt for ImageBitmap {
@@ -46,15 +37,17 @@ impl ImageBitmapExt for ImageBitmap {
 pub async fn load_assets() -> Result<Assets, JsValue> {
     let window = web_sys::window().expect("Should have a window");
 
+    let manifest: AtlasManifest = serde_json::from_str(&load_text(&window, "/assets/atlas.json").await?)
+        .map_err(|e| JsValue::from(format!("Atlas manifest should parse: {}", e)))?;
+
     let assets = Assets {
-        gas_image: load_image(&window, "/assets/images/gas-frame.png").await?,
-        gas_trap: load_image(&window, "/assets/images/gas-trap.png").await?,
+        atlas: SpriteAtlas::new(load_image(&window, "/assets/images/atlas.png").await?, manifest),
     };
 
     Ok(assets)
 }
 
-async fn load_image(window: &Window, url: &str) -> Result<ImageBitmap, JsValue> {
+async fn fetch(window: &Window, url: &str) -> Result<Response, JsValue> {
     let mut opts = RequestInit::new();
     opts.method("GET");
     opts.mode(RequestMode::Cors);
@@ -66,6 +59,20 @@ async fn load_image(window: &Window, url: &str) -> Result<ImageBitmap, JsValue>
     assert!(resp_value.is_instance_of::<Response>());
     let resp: Response = resp_value.dyn_into().unwrap();
 
+    Ok(resp)
+}
+
+async fn load_text(window: &Window, url: &str) -> Result<String, JsValue> {
+    let resp = fetch(window, url).await?;
+
+    let text: JsValue = JsFuture::from(resp.text()?).await?;
+
+    text.as_string().ok_or_else(|| JsValue::from(format!("{} should be text", url)))
+}
+
+async fn load_image(window: &Window, url: &str) -> Result<ImageBitmap, JsValue> {
+    let resp = fetch(window, url).await?;
+
     let text: JsValue = JsFuture::from(resp.blob()?).await?;
 
     assert!(text.is_instance_of::<Blob>());
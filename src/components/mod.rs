@@ -6,26 +6,64 @@ mod user_input;
 
 pub use user_input::*;
 
-/// Indicates the entity has touched the core.
+/// Indicates the entity has touched the core at the given tile.
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
-pub struct TouchedCore;
+pub struct TouchedCore {
+    pub tile_x: i32,
+    pub tile_y: i32,
+}
+
+/// Component for one of the player's cores, a structure which mobs path toward. Mobs that reach
+/// it damage it; once its health runs out it is destroyed (with its OnDeath events, if any), and
+/// the game is lost if it was the main core or the last one standing.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Core {
+    pub health: i32,
+    pub max_health: i32,
+    pub main: bool,
+}
 
-/// Component indicating the entity has a world position in pixels
+/// Component for a spawn, a structure which sends out mobs when a wave launches
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Spawn {
+    /// The first wave this spawn takes part in (waves are counted from zero)
+    pub first_wave: usize,
+    /// After the first, it takes part in every this-many-th wave (1 for every wave)
+    pub wave_interval: usize,
+    pub mobs_per_wave: usize,
+    /// Ticks between one mob and the next, within a wave
+    pub ticks_per_mob: usize,
+}
+
+impl Spawn {
+    pub fn serves_wave(&self, wave: usize) -> bool {
+        wave >= self.first_wave && (wave - self.first_wave).is_multiple_of(self.wave_interval.max(1))
+    }
+}
+
+/// Component indicating the entity has a world position in pixels; fractional, so things can
+/// move smoothly at any speed
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct Position {
     // Note -- these are "world pixel" coordinates, not tile coordinates or etc.
-    pub x: i32,
-    pub y: i32,
+    pub x: f64,
+    pub y: f64,
 }
 
 impl Position {
     pub fn at_tile_center(tile_x: i32, tile_y: i32) -> Position {
         let (x, y) = crate::tile_helpers::tile_to_pixel_coords(tile_x, tile_y);
-        Position { x, y }
+        Position { x: x as f64, y: y as f64 }
+    }
+
+    /// The whole pixel this position falls in
+    pub fn to_pixel_coords(self) -> (i32, i32) {
+        (self.x.floor() as i32, self.y.floor() as i32)
     }
 
     pub fn to_tile_coords(&self) -> (i32, i32) {
-        crate::tile_helpers::coords_to_tile(self.x, self.y)
+        let (x, y) = self.to_pixel_coords();
+        crate::tile_helpers::coords_to_tile(x, y)
     }
 }
 
@@ -33,6 +71,39 @@ impl Position {
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub struct TdMob;
 
+/// Component indicating how the entity gets around the map, and where it's going
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Movement {
+    pub kind: MovementKind,
+    pub target: PathTarget,
+    /// Pixels per tick across open ground
+    pub speed: f64,
+}
+
+/// How much room the mob takes up, in pixels; other mobs are pushed out of the circle this wide
+/// around its position
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct MobRadius(pub i32);
+
+/// How much health the mob takes off a core when it reaches one
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct CoreDamage {
+    pub amount: i32,
+}
+
+/// Component indicating the mob shrugs off some of every hit
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Armor {
+    /// Taken off each hit, as a percentage of it (rounded down)
+    pub percent: i32,
+}
+
+/// Component indicating the mob heals a little every tick, up to its max health
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Regenerates {
+    pub per_tick: i32,
+}
+
 /// Component indicating the entity has health. Probably they can take damage and if the health
 /// goes to zero, they'll die.
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
@@ -54,29 +125,46 @@ pub struct OnDeath {
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub enum DeathEvent {
     GetResources(OwnedResource, i64),
+    /// Poison gas bursts out of the tile the entity died on
+    ReleaseGas(i32),
 }
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum Renderable {
-    #[allow(unused)] // we are going to use this at some point, the code is tested
+    /// A sprite from the atlas, drawn with its top left corner (dx, dy) from the position
     Bitmap {
         dx: i32,
         dy: i32,
-        bitmap: RenderBitmap,
+        sprite: SpriteId,
     },
     Geometry(RenderGeometry),
 }
 
-/// Options for rendering an object using a bitmap in the Assets folder
-#[derive(Copy, Clone, Debug, Eq, PartialEq)]
-pub enum RenderBitmap {
-    GasTrap,
+/// Name of a frame or an animation in the sprite atlas manifest
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct SpriteId(pub &'static str);
+
+impl SpriteId {
+    pub const GAS_TRAP: SpriteId = SpriteId("gas-trap");
+    pub const GAS: SpriteId = SpriteId("gas");
+    pub const WALKER: SpriteId = SpriteId("walker");
+    pub const SWIMMER: SpriteId = SpriteId("swimmer");
+
+    /// Every sprite above, which the atlas must have
+    pub const ALL: &'static [SpriteId] = &[SpriteId::GAS_TRAP, SpriteId::GAS, SpriteId::WALKER, SpriteId::SWIMMER];
 }
 
+/// Fill colors for the structures drawn as plain squares
+pub const CORE_COLOR: &str = "#1584ff";
+pub const SPAWN_COLOR: &str = "#ff1587";
+
 /// Options for rendering an object using geometry
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum RenderGeometry {
+    #[allow(unused)] // mobs were drawn this way before they had sprites, and it still works
     Circle { radius: i32 },
+    /// A filled square, centered on the position
+    Square { half_width: i32, color: &'static str },
 }
 
 /// Indication of the state of a wave associated to the given entity.
@@ -115,10 +203,44 @@ pub struct PoisonGasTrap {
     pub amount: i32,
 }
 
+/// How much gas a gas trap the player builds produces each tick
+pub const BUILT_GAS_TRAP_AMOUNT: i32 = 10;
+/// Cores the player adds themselves are sturdy, but not as sturdy as the one they start with
+pub const BUILT_CORE_HEALTH: i32 = 10;
+/// How much gas a core the player builds releases when it falls
+pub const BUILT_CORE_GAS_RELEASE: i32 = 200;
+
 /// Indicates the target should take a certain amount of damage. Can be expanded for damage type,
-/// source, etc. so we can do all resistances, callbacks, particles, and so on in one place.
+/// etc. so we can do all resistances, callbacks, particles, and so on in one place.
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub struct TakeDamage {
     pub target: Entity,
     pub amount: i32,
+    /// What dealt the damage, if anything in particular did
+    pub source: Option<Entity>,
+}
+
+/// What a structure has done to the mobs over the whole game; added the first time it hurts one
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Default)]
+pub struct DamageDealt {
+    pub damage: i64,
+    pub kills: usize,
+}
+
+/// What hurt a mob most on the last tick it was hurt, so whatever it was can be credited if the mob
+/// dies. The kind and tile are kept apart from the entity, since the structure may be gone by then.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct LastHitBy {
+    pub source: Option<Entity>,
+    pub kind: DamageSource,
+    pub tile: Option<(i32, i32)>,
+}
+
+/// Indicates the wall at the given tile should take a certain amount of damage (if walls can be
+/// broken at all)
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct DamageWall {
+    pub tile_x: i32,
+    pub tile_y: i32,
+    pub amount: i32,
 }
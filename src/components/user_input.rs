@@ -34,28 +34,46 @@ pub struct TryLaunchWave;
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub struct ToggleAutoLaunchWave;
 
-/// Message component; the user has done a key thing regarding the canvas
+/// Message component; the user has attempted to undo the last thing they did
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct TryUndo;
+
+/// Message component; the user pressed or let go of a key. The key map has already been used to
+/// work out what the key means, but the code is kept so held keys can be matched up on release.
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub enum UserKeyEvent {
-    KeyDown(UserKey),
-    KeyUp(UserKey),
-    AllKeysUp,
+    Pressed { code: String, action: InputAction },
+    Released { code: String },
+    AllReleased,
 }
 
-/// What the canvas has determined the JS key event meant (e.g. w and arrow-up both become "up")
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
-pub enum UserKey {
-    Up,
-    Down,
-    Left,
-    Right,
+pub struct UserClickTile {
+    pub tile_x: i32,
+    pub tile_y: i32,
 }
 
+/// Message component; add the tile to the selection, or take it out if it's already in
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
-pub struct UserClickTile {
+pub struct UserToggleTile {
     pub tile_x: i32,
     pub tile_y: i32,
 }
 
+/// Message component; add every tile in the rectangle to the selection
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct UserSelectRect(pub TileRect);
+
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub struct UserUnselectTile;
+
+/// Message component; the user has attempted the same thing on many tiles or structures at once.
+/// It goes ahead on all of them, or on none, if any can't take it or the whole lot can't be paid
+/// for together.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum TryBulkAction {
+    /// The same change or build on every tile
+    Paint { tool: PaintTool, tiles: Vec<(i32, i32)> },
+    /// Sell every structure; none are sold if any of them is already gone
+    Sell { to_sell: Vec<Entity> },
+}
@@ -0,0 +1,68 @@
+//! Keys work wherever the focus is, so they're heard on the document rather than the canvas, and
+//! turned into actions through the key map. The key map is kept in local storage, so rebinding a
+//! key sticks between sessions.
+
+use wasm_bindgen::{closure::Closure, JsCast};
+use yew::{
+    format::{Json, Text},
+    services::{storage::Area, StorageService},
+    Callback,
+};
+
+use crate::resources::KeyMap;
+
+const KEY_MAP_STORAGE_KEY: &str = "radishes.keymap";
+
+/// The saved key map, or the defaults if there isn't one (or it can't be read)
+pub(super) fn load_key_map() -> KeyMap {
+    let saved = StorageService::new(Area::Local)
+        .ok()
+        .and_then(|storage| storage.restore::<Text>(KEY_MAP_STORAGE_KEY).ok())
+        .and_then(|text| serde_json::from_str::<KeyMap>(&text).ok());
+
+    match saved {
+        Some(mut key_map) => {
+            key_map.fill_defaults();
+            key_map
+        }
+        None => KeyMap::default(),
+    }
+}
+
+pub(super) fn save_key_map(key_map: &KeyMap) {
+    if let Ok(mut storage) = StorageService::new(Area::Local) {
+        storage.store(KEY_MAP_STORAGE_KEY, Json(key_map));
+    }
+}
+
+/// How a key code reads in the controls list; "KeyB" is just "B", "Digit3" just "3"
+pub(super) fn key_label(code: &str) -> &str {
+    code.strip_prefix("Key").or_else(|| code.strip_prefix("Digit")).unwrap_or(code)
+}
+
+/// Calls back when the window loses focus. Keys let go of after that are never heard about, so
+/// anything held has to be let go of then. Stops listening when dropped.
+pub(super) struct WindowBlurListener {
+    closure: Closure<dyn FnMut()>,
+}
+
+impl WindowBlurListener {
+    pub(super) fn new(callback: Callback<()>) -> Self {
+        let closure = Closure::wrap(Box::new(move || callback.emit(())) as Box<dyn FnMut()>);
+
+        web_sys::window()
+            .unwrap()
+            .add_event_listener_with_callback("blur", closure.as_ref().unchecked_ref())
+            .unwrap();
+
+        WindowBlurListener { closure }
+    }
+}
+
+impl Drop for WindowBlurListener {
+    fn drop(&mut self) {
+        if let Some(window) = web_sys::window() {
+            let _ = window.remove_event_listener_with_callback("blur", self.closure.as_ref().unchecked_ref());
+        }
+    }
+}
@@ -1,3 +1,5 @@
+use std::collections::HashSet;
+
 use yew::prelude::*;
 
 use web_sys::MouseEvent;
@@ -35,6 +37,24 @@ pub(crate) enum DetailViewMsg {
     SellExistingStructureButtonClicked {
         to_sell: Entity,
     },
+    BulkSellButtonClicked {
+        to_sell: Vec<Entity>,
+    },
+    BulkActionButtonClicked {
+        action: PaintTool,
+        tiles: Vec<(i32, i32)>,
+    },
+    BuildStructureButtonHovered {
+        x: i32,
+        y: i32,
+        kind: StructureKind,
+    },
+    ChangeTileButtonHovered {
+        x: i32,
+        y: i32,
+        tile: Tile,
+    },
+    OptionButtonUnhovered,
     Nothing,
 }
 
@@ -44,18 +64,25 @@ enum DetailState {
         x: i32,
         y: i32,
         tile: Tile,
+        wall_health: Option<i32>,
         structures: Vec<StructureState>,
     },
+    /// Several tiles at once, in order, with what's on each
+    Many {
+        tiles: Vec<(i32, i32, Tile)>,
+        structures: Vec<SelectedStructure>,
+    },
 }
 
 struct StructureState {
     entity: Entity,
     kind: StructureKind,
     sell_value: Option<OwnedResources>,
+    damage_dealt: Option<DamageDealt>,
 }
 
 fn from_ecs(ecs: &ECS) -> DetailState {
-    ecs.with(|_, r| {
+    ecs.with(|world, r| {
         let mouseover = r.get_or_default::<TdTileSelect>().clone();
 
         match mouseover {
@@ -65,16 +92,37 @@ fn from_ecs(ecs: &ECS) -> DetailState {
                 y,
                 structures: selected,
             } => {
-                let tile = r.get::<Map>().unwrap().get_tile(x, y);
+                let map = r.get::<Map>().unwrap();
+                let tile = map.get_tile(x, y);
+                let wall_health = map.wall_health(x, y);
                 let structures: Vec<StructureState> = selected
                     .iter()
                     .map(|s| StructureState {
                         entity: s.entity,
                         kind: s.kind,
                         sell_value: s.sell_value.clone(),
+                        damage_dealt: world
+                            .entry_ref(s.entity)
+                            .ok()
+                            .and_then(|e| e.get_component::<DamageDealt>().ok().copied()),
                     })
                     .collect();
-                DetailState::Tile { x, y, tile, structures }
+                DetailState::Tile {
+                    x,
+                    y,
+                    tile,
+                    wall_health,
+                    structures,
+                }
+            }
+            TdTileSelect::Many { tiles, structures } => {
+                let map = r.get::<Map>().unwrap();
+                let tiles = tiles
+                    .into_iter()
+                    .filter(|&(x, y)| map.in_bounds(x, y))
+                    .map(|(x, y)| (x, y, map.get_tile(x, y)))
+                    .collect();
+                DetailState::Many { tiles, structures }
             }
         }
     })
@@ -107,6 +155,8 @@ impl DetailView {
 
         let trap_name = match structure.kind {
             StructureKind::GasTrap => "Gas Trap",
+            StructureKind::Core => "Core",
+            StructureKind::Spawn => "Spawn",
         };
 
         let button_text = format!("Sell existing {}", trap_name);
@@ -126,12 +176,22 @@ impl DetailView {
             html! { <p> { "You cannot sell this." } </p> }
         };
 
+        // only gas traps hurt anything, so for the rest this would always say nothing
+        let record = match (structure.kind, structure.damage_dealt) {
+            (_, Some(dealt)) => html! { <p>{ format!("Damage dealt: {}, kills: {}", dealt.damage, dealt.kills) }</p> },
+            (StructureKind::GasTrap, None) => html! { <p>{ "Damage dealt: 0, kills: 0" }</p> },
+            (_, None) => html! {},
+        };
+
         html! {
-            <div onclick=click_cb class=style_class>
-                <p> { &button_text } </p>
-                { cost_display }
-                { sell_err }
-            </div>
+            <>
+                { record }
+                <div onclick=click_cb class=style_class>
+                    <p> { &button_text } </p>
+                    { cost_display }
+                    { sell_err }
+                </div>
+            </>
         }
     }
 
@@ -191,8 +251,13 @@ impl DetailView {
             html! {}
         };
 
+        let enter_cb = self
+            .link
+            .callback(move |_: MouseEvent| DetailViewMsg::ChangeTileButtonHovered { x, y, tile });
+        let leave_cb = self.link.callback(|_: MouseEvent| DetailViewMsg::OptionButtonUnhovered);
+
         html! {
-            <div onclick=click_cb class=style_class>
+            <div onclick=click_cb onmouseenter=enter_cb onmouseleave=leave_cb class=style_class>
                 <p> { &button_text } </p>
                 { cost_display }
                 { structures_err }
@@ -251,8 +316,13 @@ impl DetailView {
             html! {}
         };
 
+        let enter_cb = self
+            .link
+            .callback(move |_: MouseEvent| DetailViewMsg::BuildStructureButtonHovered { x, y, kind });
+        let leave_cb = self.link.callback(|_: MouseEvent| DetailViewMsg::OptionButtonUnhovered);
+
         html! {
-            <div onclick=click_cb class=style_class>
+            <div onclick=click_cb onmouseenter=enter_cb onmouseleave=leave_cb class=style_class>
                 <p> { &button_text } </p>
                 { cost_display }
                 { blocked_err }
@@ -271,11 +341,16 @@ impl DetailView {
             .collect()
     }
 
-    fn tile_details(&self, x: i32, y: i32, tile: Tile, structures: &[StructureState]) -> Html {
+    fn tile_details(&self, x: i32, y: i32, tile: Tile, wall_health: Option<i32>, structures: &[StructureState]) -> Html {
         use super::collapsible_div::*;
 
         let tile_str = format!("Selected tile at ({}, {}): {:?}", x, y, tile);
 
+        let wall_health_view = match wall_health {
+            Some(health) => html! { <p>{ format!("Wall health: {}/{}", health, WALL_HEALTH) }</p> },
+            None => html! {},
+        };
+
         let mut sell_structures: Vec<Html> = Vec::new();
         let mut build_structures: Vec<Html> = Vec::new();
         let mut changes: Vec<Html> = Vec::new();
@@ -284,6 +359,10 @@ impl DetailView {
             for ss in structures {
                 sell_structures.push(self.make_structure_view(ss));
             }
+            // Nothing can be done with tiles past the edge of the world
+            if !r.get::<Map>().unwrap().in_bounds(x, y) {
+                return;
+            }
             for (target, cost) in r.get::<TileTransforms>().unwrap().list_all_for(tile).into_iter() {
                 changes.push(self.make_change_button(r, x, y, target, cost, !structures.is_empty()));
             }
@@ -325,6 +404,7 @@ impl DetailView {
         html! {
             <div>
                 <p>{ tile_str }</p>
+                { wall_health_view }
                 { structures_view }
                 { build_view }
                 { change_tile_view }
@@ -333,6 +413,235 @@ impl DetailView {
     }
 }
 
+/// Bulk actions, for when more than one tile is selected
+impl DetailView {
+    fn many_details(&self, tiles: &[(i32, i32, Tile)], structures: &[SelectedStructure]) -> Html {
+        use super::collapsible_div::*;
+
+        let mut tile_counts: Vec<(Tile, usize)> = Vec::new();
+        for (_, _, tile) in tiles {
+            match tile_counts.iter_mut().find(|(t, _)| t == tile) {
+                Some((_, count)) => *count += 1,
+                None => tile_counts.push((*tile, 1)),
+            }
+        }
+        sort_by_name(&mut tile_counts, |(tile, _)| *tile);
+
+        let tile_count_view: Html = tile_counts
+            .iter()
+            .map(|(tile, count)| html! { <p>{ format!("{:?}: {}", tile, count) }</p> })
+            .collect();
+
+        let occupied: HashSet<(i32, i32)> = structures.iter().map(|s| s.tile).collect();
+
+        let mut build_structures: Vec<Html> = Vec::new();
+        let mut changes: Vec<Html> = Vec::new();
+
+        self.ecs.with(|_, r| {
+            let transforms = r.get::<TileTransforms>().unwrap();
+            let builds = r.get::<StructureBuilds>().unwrap();
+
+            let mut targets: Vec<Tile> = Vec::new();
+            let mut kinds: Vec<StructureKind> = Vec::new();
+            for (_, _, tile) in tiles {
+                targets.extend(transforms.list_all_for(*tile).into_keys());
+                kinds.extend(builds.list_all_for(*tile).into_keys());
+            }
+            sort_by_name(&mut targets, |target| *target);
+            targets.dedup();
+            kinds.sort();
+            kinds.dedup();
+
+            // each action only goes to the tiles it makes sense for; the rest are left out
+            let free_tiles = || tiles.iter().filter(|(x, y, _)| !occupied.contains(&(*x, *y)));
+
+            for target in targets {
+                let applicable = free_tiles()
+                    .filter(|(_, _, tile)| transforms.list_all_for(*tile).contains_key(&target))
+                    .map(|(x, y, _)| (*x, *y))
+                    .collect();
+                changes.push(self.make_bulk_button(r, PaintTool::ChangeTile(target), applicable, tiles.len(), &occupied));
+            }
+
+            for kind in kinds {
+                let applicable = free_tiles()
+                    .filter(|(_, _, tile)| builds.list_all_for(*tile).contains_key(&kind))
+                    .map(|(x, y, _)| (*x, *y))
+                    .collect();
+                build_structures.push(self.make_bulk_button(r, PaintTool::Build(kind), applicable, tiles.len(), &occupied));
+            }
+        });
+
+        html! {
+            <div>
+                <p>{ format!("{} tiles selected", tiles.len()) }</p>
+                { tile_count_view }
+                { self.make_bulk_sell_view(structures) }
+                <Collapsible collapse_name="BuildStructures" title="Build Structures".to_string() ecs=self.ecs.clone()>
+                    { build_structures }
+                </Collapsible>
+                <Collapsible collapse_name="TileChanges" title="Tile Changes".to_string() ecs=self.ecs.clone()>
+                    { changes }
+                </Collapsible>
+            </div>
+        }
+    }
+
+    fn make_bulk_sell_view(&self, structures: &[SelectedStructure]) -> Html {
+        use super::collapsible_div::*;
+
+        if structures.is_empty() {
+            return html! {};
+        }
+
+        let mut kind_counts: Vec<(StructureKind, usize)> = Vec::new();
+        for structure in structures {
+            match kind_counts.iter_mut().find(|(kind, _)| *kind == structure.kind) {
+                Some((_, count)) => *count += 1,
+                None => kind_counts.push((structure.kind, 1)),
+            }
+        }
+        kind_counts.sort();
+
+        let kind_count_view: Html = kind_counts
+            .iter()
+            .map(|(kind, count)| html! { <p>{ format!("{:?}: {}", kind, count) }</p> })
+            .collect();
+
+        let mut total_value = OwnedResources::new();
+        let mut to_sell: Vec<Entity> = Vec::new();
+        for structure in structures {
+            if let Some(value) = structure.sell_value.as_ref() {
+                total_value.receive_all(value);
+                to_sell.push(structure.entity);
+            }
+        }
+
+        let would_work = !to_sell.is_empty();
+        let unsellable = structures.len() - to_sell.len();
+        let button_text = format!("Sell {} structure(s)", to_sell.len());
+
+        let click_cb = if would_work {
+            self.link
+                .callback(move |_: MouseEvent| DetailViewMsg::BulkSellButtonClicked { to_sell: to_sell.clone() })
+        } else {
+            self.link.callback(|_: MouseEvent| DetailViewMsg::Nothing)
+        };
+
+        let style_class = format!(
+            "build-button {}",
+            if would_work {
+                "build-button-enabled"
+            } else {
+                "build-button-disabled"
+            }
+        );
+
+        let unsellable_note = if unsellable > 0 {
+            html! { <p> { format!("{} cannot be sold.", unsellable) } </p> }
+        } else {
+            html! {}
+        };
+
+        html! {
+            <Collapsible collapse_name="SellStructures" title="Existing Structures".to_string() ecs=self.ecs.clone()>
+                { kind_count_view }
+                <div onclick=click_cb class=style_class>
+                    <p> { &button_text } </p>
+                    { self.make_cost_display(&total_value) }
+                    { unsellable_note }
+                </div>
+            </Collapsible>
+        }
+    }
+
+    /// A button doing the action to every applicable tile at once. It's only enabled if every one
+    /// of them can take it, and the whole lot can be paid for together.
+    fn make_bulk_button(
+        &self,
+        resources: &Resources,
+        action: PaintTool,
+        tiles: Vec<(i32, i32)>,
+        num_selected: usize,
+        occupied: &HashSet<(i32, i32)>,
+    ) -> Html {
+        let plan = PaintPlan::new(
+            action,
+            &tiles,
+            &resources.get::<Map>().unwrap(),
+            &resources.get::<TileTransforms>().unwrap(),
+            &resources.get::<StructureBuilds>().unwrap(),
+            occupied,
+            None,
+        );
+
+        let can_pay = resources.get::<OwnedResources>().unwrap().can_pay(&plan.total);
+        let can_path = plan.rejected_for(PaintRejection::BlocksPath) == 0;
+
+        let would_work = can_pay && plan.all_accepted() && !tiles.is_empty();
+
+        let button_text = match action {
+            PaintTool::ChangeTile(tile) => format!("Change {} tile(s) to {:?}", tiles.len(), tile),
+            PaintTool::Build(kind) => format!("Build a {:?} on {} tile(s)", kind, tiles.len()),
+        };
+
+        let left_out = num_selected - tiles.len();
+        let cost_display = self.make_cost_display(&plan.total);
+
+        let click_cb = if would_work {
+            self.link.callback(move |_: MouseEvent| DetailViewMsg::BulkActionButtonClicked {
+                action,
+                tiles: tiles.clone(),
+            })
+        } else {
+            self.link.callback(|_: MouseEvent| DetailViewMsg::Nothing)
+        };
+
+        let style_class = format!(
+            "build-button {}",
+            if would_work {
+                "build-button-enabled"
+            } else {
+                "build-button-disabled"
+            }
+        );
+
+        let pay_err = if can_pay {
+            html! {}
+        } else {
+            html! { <p> { "You cannot afford this." } </p> }
+        };
+
+        let path_err: Html = if can_path {
+            html! {}
+        } else {
+            html! { <p> { "This would block the exit." } </p> }
+        };
+
+        let left_out_note: Html = if left_out > 0 {
+            html! { <p> { format!("{} tile(s) left out, which can't take this or have structures.", left_out) } </p> }
+        } else {
+            html! {}
+        };
+
+        html! {
+            <div onclick=click_cb class=style_class>
+                <p> { &button_text } </p>
+                { cost_display }
+                { left_out_note }
+                { pay_err }
+                { path_err }
+            </div>
+        }
+    }
+}
+
+/// Tiles don't have an order of their own, so they're listed by name, so the list doesn't shuffle
+/// about from one frame to the next
+fn sort_by_name<T, F: Fn(&T) -> Tile>(items: &mut [T], tile_of: F) {
+    items.sort_by_key(|item| format!("{:?}", tile_of(item)));
+}
+
 impl Component for DetailView {
     type Message = DetailViewMsg;
     type Properties = DetailViewProps;
@@ -349,13 +658,16 @@ impl Component for DetailView {
         match msg {
             DetailViewMsg::Nothing => {}
             DetailViewMsg::ChangeTileButtonClicked { x, y, desired, costs } => {
-                self.ecs.with(|world, _| {
+                // the button goes away once the change is made, without the mouse ever leaving it
+                self.ecs.with(|world, r| {
                     world.push((TryChangeTileType { x, y, desired, costs },));
+                    *r.get_mut::<TdBuildHover>().unwrap() = TdBuildHover::None;
                 });
             }
             DetailViewMsg::BuildStructureButtonClicked { x, y, desired, costs } => {
-                self.ecs.with(|world, _| {
+                self.ecs.with(|world, r| {
                     world.push((TryBuildStructure { x, y, desired, costs },));
+                    *r.get_mut::<TdBuildHover>().unwrap() = TdBuildHover::None;
                 });
             }
             DetailViewMsg::SellExistingStructureButtonClicked { to_sell } => {
@@ -363,6 +675,31 @@ impl Component for DetailView {
                     world.push((TrySellStructure { to_sell },));
                 });
             }
+            DetailViewMsg::BulkSellButtonClicked { to_sell } => {
+                self.ecs.with(|world, _| {
+                    world.push((TryBulkAction::Sell { to_sell },));
+                });
+            }
+            DetailViewMsg::BulkActionButtonClicked { action, tiles } => {
+                self.ecs.with(|world, _| {
+                    world.push((TryBulkAction::Paint { tool: action, tiles },));
+                });
+            }
+            DetailViewMsg::BuildStructureButtonHovered { x, y, kind } => {
+                self.ecs.with(|_, r| {
+                    *r.get_mut::<TdBuildHover>().unwrap() = TdBuildHover::Build { x, y, kind };
+                });
+            }
+            DetailViewMsg::ChangeTileButtonHovered { x, y, tile } => {
+                self.ecs.with(|_, r| {
+                    *r.get_mut::<TdBuildHover>().unwrap() = TdBuildHover::ChangeTile { x, y, tile };
+                });
+            }
+            DetailViewMsg::OptionButtonUnhovered => {
+                self.ecs.with(|_, r| {
+                    *r.get_mut::<TdBuildHover>().unwrap() = TdBuildHover::None;
+                });
+            }
         }
 
         false
@@ -375,7 +712,14 @@ impl Component for DetailView {
     fn view(&self) -> Html {
         match &self.detail_state {
             DetailState::Nothing => empty_pane(),
-            DetailState::Tile { x, y, tile, structures } => self.tile_details(*x, *y, *tile, structures),
+            DetailState::Tile {
+                x,
+                y,
+                tile,
+                wall_health,
+                structures,
+            } => self.tile_details(*x, *y, *tile, *wall_health, structures),
+            DetailState::Many { tiles, structures } => self.many_details(tiles, structures),
         }
     }
 }
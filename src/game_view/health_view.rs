@@ -1,6 +1,8 @@
 use yew::prelude::*;
 
-use crate::{resources::*, ECS};
+use legion::*;
+
+use crate::{components::*, ECS};
 
 pub(crate) struct HealthView {
     model: ECS,
@@ -33,13 +35,28 @@ impl Component for HealthView {
     }
 
     fn view(&self) -> Html {
-        let player_health = self.model.with(|_, r| *r.get_or_default::<PlayerHealth>());
-
-        let text = format!("Health: {}/{}", player_health.health, player_health.max);
+        let mut cores: Vec<((i32, i32), Core)> = self.model.with(|w, _| {
+            <(Read<Core>, Read<Position>)>::query()
+                .iter(w)
+                .map(|(core, pos)| (pos.to_tile_coords(), *core))
+                .collect()
+        });
+
+        // main core first, then the rest in a stable order
+        cores.sort_by_key(|(tile, core)| (!core.main, *tile));
+
+        let lines: Vec<Html> = cores
+            .into_iter()
+            .map(|((x, y), core)| {
+                let name = if core.main { "Main core" } else { "Core" };
+                let text = format!("{} at ({}, {}): {}/{}", name, x, y, core.health, core.max_health);
+                html! { <p>{ text }</p> }
+            })
+            .collect();
 
         html! {
             <div class="health-view">
-                { text }
+                { lines }
             </div>
         }
     }
@@ -49,9 +49,17 @@ impl Component for LaunchWaveView {
     }
 
     fn view(&self) -> Html {
-        let next = self.model.with(|_, r| *r.get_or_default::<NextWaveState>());
+        let (next, mode) = self.model.with(|_, r| {
+            let next = *r.get_or_default::<NextWaveState>();
+            (next, *r.get_or_default::<GameMode>())
+        });
+
+        let waves_left = match mode {
+            GameMode::Campaign { level } => LEVELS[level].wave(next.next_wave).is_some(),
+            GameMode::Classic | GameMode::Endless { .. } => true,
+        };
 
-        let style_class = if next.delay_ticks > 0 {
+        let style_class = if next.delay_ticks > 0 || !waves_left {
             "launch-wave-div-disabled "
         } else {
             "launch-wave-div-enabled"
@@ -62,7 +70,9 @@ impl Component for LaunchWaveView {
 
         let mut text = format!("Launch wave {}", next.next_wave);
 
-        if next.delay_ticks > 0 {
+        if !waves_left {
+            text = "No more waves".to_string();
+        } else if next.delay_ticks > 0 {
             text = format!("{} (wait {})", text, next.delay_ticks);
         }
 
@@ -1,104 +1,298 @@
 use std::sync::Arc;
 
-use yew::prelude::*;
+use yew::{
+    prelude::*,
+    services::keyboard::{KeyListenerHandle, KeyboardService},
+};
 
 use legion::*;
 
-use crate::{assets::Assets, resources::*, ECS};
+use crate::{
+    assets::Assets,
+    components::{self, *},
+    resources::*,
+    tile_helpers::TILE_WIDTH_PIXELS,
+    ECS,
+};
 
 mod collapsible_div;
 
+mod controls;
 mod detail_view;
 mod health_view;
 mod launch_wave_view;
+mod objective_view;
 mod resource_view;
 mod td_view;
+mod toolbar_view;
+mod wave_preview_view;
 
 pub struct GameView {
-    _link: ComponentLink<Self>,
+    link: ComponentLink<Self>,
     ecs: ECS,
     assets: Arc<Assets>,
+    // the next key pressed gets bound to this, instead of doing anything
+    rebinding: Option<Rebinding>,
+    // the listeners stop when these are dropped
+    _key_down_listener: KeyListenerHandle,
+    _key_up_listener: KeyListenerHandle,
+    _blur_listener: controls::WindowBlurListener,
 }
 
-pub enum ModelMsg {}
+/// A key binding the player is in the middle of changing
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Rebinding {
+    action: InputAction,
+    // the key the new one is standing in for; none if it's being added alongside the others
+    replacing: Option<String>,
+}
+
+pub enum ModelMsg {
+    KeyDown(KeyboardEvent),
+    KeyUp(KeyboardEvent),
+    WindowBlurred,
+    RebindClicked(Rebinding),
+    ResetBindingsClicked,
+}
 
 // TODO this probably shouldn't live here
-pub fn init_ecs(ecs: &ECS) {
+/// Start a fresh game. `blocking` is for classic and endless games; campaign levels set their own.
+pub fn init_ecs(ecs: &ECS, mode: GameMode, blocking: BlockingRule) {
     ecs.with(|world, r| {
         *r = Resources::default();
         world.clear();
 
-        r.insert(KeysPressed::default());
+        r.insert(HeldActions::default());
         r.insert(NextWaveState::default());
-        r.insert(PlayerHealth::default());
         r.insert(MenuCollapseStates::default());
         r.insert(TdTileSelect::None);
-
-        r.insert(OwnedResources::new().with(OwnedResource::Money, 50).with(OwnedResource::Wood, 20));
-
-        let mut map = Map::new();
-
-        map.set_tile(8, 0, Tile::Spawn);
-        map.set_tile(8, 1, Tile::Open);
-        map.set_tile(8, 2, Tile::Open);
-        map.set_tile(8, 3, Tile::Open);
-        map.set_tile(7, 3, Tile::Open);
-        map.set_tile(6, 3, Tile::Open);
-        map.set_tile(5, 3, Tile::Open);
-        map.set_tile(4, 3, Tile::Open);
-        map.set_tile(0, 0, Tile::Spawn);
-        map.set_tile(0, 1, Tile::Open);
-        map.set_tile(0, 2, Tile::Open);
-        map.set_tile(1, 2, Tile::Open);
-        map.set_tile(2, 2, Tile::Open);
-        map.set_tile(3, 2, Tile::Open);
-        map.set_tile(4, 2, Tile::Open);
-        map.set_tile(4, 1, Tile::Open);
-        map.set_tile(4, 0, Tile::Open);
-        map.set_tile(4, -1, Tile::Open);
-        map.set_tile(4, -2, Tile::Core);
-
-        r.insert(map);
+        r.insert(TdSelectDrag::default());
+        r.insert(TdBuildHover::None);
+        r.insert(Particles::default());
+        r.insert(FloatingLabels::default());
+        r.insert(GameSpeed::default());
+        r.insert(LastBuilt::default());
+        r.insert(PaintState::default());
+        r.insert(GasPreview::default());
+        r.insert(RoutePreview::default());
+        r.insert(TickCount::default());
+        r.insert(UndoStack::default());
+        r.insert(RunStats::default());
+        r.insert(mode);
+        r.insert(controls::load_key_map());
 
         let mut camera = TdCamera::default();
         camera.top = -100;
         camera.left = -100;
         r.insert(camera);
 
-        // TODO: probably put these in "raws" somewhere
-        let mut transforms = TileTransforms::new();
-        transforms.add(TileTransformDesc {
+        match mode {
+            GameMode::Campaign { level } => set_up_level(world, r, &LEVELS[level]),
+            GameMode::Classic | GameMode::Endless { .. } => set_up_classic(world, r, blocking),
+        }
+    })
+}
+
+fn set_up_classic(world: &mut World, r: &mut Resources, blocking: BlockingRule) {
+    r.insert(OwnedResources::new().with(OwnedResource::Money, 50).with(OwnedResource::Wood, 20));
+
+    // with BlockingRule::MobsBreakWalls, the player may wall the cores off entirely
+    let mut map = Map::with_rules(
+        WorldBounds {
+            x_min: -8,
+            y_min: -8,
+            x_max: 24,
+            y_max: 24,
+        },
+        MapRules {
+            blocking,
+            ..MapRules::default()
+        },
+    );
+
+    map.set_tile(8, 0, Tile::Open);
+    map.set_tile(8, 1, Tile::Open);
+    map.set_tile(8, 2, Tile::Mud);
+    map.set_tile(8, 3, Tile::Open);
+    map.set_tile(7, 3, Tile::Open);
+    map.set_tile(6, 3, Tile::Open);
+    map.set_tile(5, 3, Tile::Open);
+    map.set_tile(4, 3, Tile::Open);
+    map.set_tile(0, 0, Tile::Open);
+    map.set_tile(0, 1, Tile::Open);
+    map.set_tile(0, 2, Tile::Open);
+    map.set_tile(1, 2, Tile::Road);
+    map.set_tile(2, 2, Tile::Road);
+    map.set_tile(3, 2, Tile::Road);
+    map.set_tile(4, 2, Tile::Open);
+    map.set_tile(4, 1, Tile::Open);
+    map.set_tile(4, 0, Tile::Open);
+    map.set_tile(4, -1, Tile::Open);
+    map.set_tile(4, -2, Tile::Open);
+
+    // the map picks these up on the first tick
+    for (x, y) in [(8, 0), (0, 0)].iter().copied() {
+        push_spawn(
+            world,
+            x,
+            y,
+            Spawn {
+                first_wave: 0,
+                wave_interval: 1,
+                mobs_per_wave: 5,
+                ticks_per_mob: 40,
+            },
+        );
+    }
+    push_main_core(world, 4, -2, 20);
+    // a shortcut, but only for swimmers
+    map.set_tile(1, 0, Tile::Water);
+    map.set_tile(2, 0, Tile::Water);
+    map.set_tile(3, 0, Tile::Water);
+
+    r.insert(map);
+
+    let mut transforms = TileTransforms::new();
+    for transform in standard_transforms() {
+        transforms.add(transform);
+    }
+    r.insert(transforms);
+
+    let mut builds = StructureBuilds::new();
+    for build in standard_builds() {
+        builds.add(build);
+    }
+    r.insert(builds);
+}
+
+/// The level's layout, with wall all around it; only the level's own structures and tile changes
+/// are on offer, at the usual prices
+fn set_up_level(world: &mut World, r: &mut Resources, level: &Level) {
+    r.insert(level.starting_resources());
+
+    let (width, height) = level.size();
+    let mut map = Map::with_rules(
+        WorldBounds {
+            x_min: -LEVEL_MARGIN,
+            y_min: -LEVEL_MARGIN,
+            x_max: width + LEVEL_MARGIN,
+            y_max: height + LEVEL_MARGIN,
+        },
+        level.map_rules(),
+    );
+
+    // as in a classic game, the map picks the spawns and cores up on the first tick
+    for ((x, y), cell) in level.cells() {
+        map.set_tile(x, y, cell.tile());
+
+        match cell {
+            LayoutCell::Spawn => push_spawn(world, x, y, level.spawn),
+            LayoutCell::MainCore => push_main_core(world, x, y, level.core_health),
+            LayoutCell::Tile(_) => {}
+        }
+    }
+
+    r.insert(map);
+
+    let mut transforms = TileTransforms::new();
+    for transform in standard_transforms() {
+        if level.transforms.contains(&(transform.source, transform.target)) {
+            transforms.add(transform);
+        }
+    }
+    r.insert(transforms);
+
+    let mut builds = StructureBuilds::new();
+    for build in standard_builds() {
+        if level.structures.contains(&build.kind) {
+            builds.add(build);
+        }
+    }
+    r.insert(builds);
+}
+
+fn push_spawn(world: &mut World, x: i32, y: i32, spawn: Spawn) {
+    world.push((
+        Position::at_tile_center(x, y),
+        Structure(StructureKind::Spawn),
+        spawn,
+        components::Renderable::Geometry(RenderGeometry::Square {
+            half_width: TILE_WIDTH_PIXELS / 2 - 1,
+            color: SPAWN_COLOR,
+        }),
+    ));
+}
+
+fn push_main_core(world: &mut World, x: i32, y: i32, health: i32) {
+    world.push((
+        Position::at_tile_center(x, y),
+        Structure(StructureKind::Core),
+        Core {
+            health,
+            max_health: health,
+            main: true,
+        },
+        components::Renderable::Geometry(RenderGeometry::Square {
+            half_width: TILE_WIDTH_PIXELS / 2 - 1,
+            color: CORE_COLOR,
+        }),
+    ));
+}
+
+// TODO: probably put these in "raws" somewhere
+fn standard_transforms() -> Vec<TileTransformDesc> {
+    vec![
+        TileTransformDesc {
             source: Tile::Open,
             target: Tile::Wall,
             cost: OwnedResources::new().with(OwnedResource::Money, 5).with(OwnedResource::Wood, 5),
-        });
-        transforms.add(TileTransformDesc {
+        },
+        TileTransformDesc {
             source: Tile::Wall,
             target: Tile::Open,
             cost: OwnedResources::new().with(OwnedResource::Money, 3),
-        });
-        transforms.add(TileTransformDesc {
+        },
+        TileTransformDesc {
             source: Tile::Open,
-            target: Tile::Spawn,
-            cost: OwnedResources::new().with(OwnedResource::Metal, 15).with(OwnedResource::Wood, 25),
-        });
-        transforms.add(TileTransformDesc {
+            target: Tile::Mud,
+            cost: OwnedResources::new().with(OwnedResource::Money, 4).with(OwnedResource::Wood, 2),
+        },
+        TileTransformDesc {
+            source: Tile::Mud,
+            target: Tile::Open,
+            cost: OwnedResources::new().with(OwnedResource::Money, 2),
+        },
+        TileTransformDesc {
             source: Tile::Open,
-            target: Tile::Core,
-            cost: OwnedResources::new().with(OwnedResource::Metal, 15).with(OwnedResource::Wood, 25),
-        });
-
-        r.insert(transforms);
+            target: Tile::Road,
+            cost: OwnedResources::new().with(OwnedResource::Money, 3).with(OwnedResource::Wood, 3),
+        },
+        TileTransformDesc {
+            source: Tile::Road,
+            target: Tile::Open,
+            cost: OwnedResources::new().with(OwnedResource::Money, 2),
+        },
+    ]
+}
 
-        let mut builds = StructureBuilds::new();
-        builds.add(StructureBuildDesc {
+fn standard_builds() -> Vec<StructureBuildDesc> {
+    vec![
+        StructureBuildDesc {
             tile: Tile::Open,
             kind: StructureKind::GasTrap,
             cost: OwnedResources::new().with(OwnedResource::Money, 10).with(OwnedResource::Wood, 5),
-        });
-
-        r.insert(builds);
-    })
+        },
+        StructureBuildDesc {
+            tile: Tile::Open,
+            kind: StructureKind::Spawn,
+            cost: OwnedResources::new().with(OwnedResource::Metal, 15).with(OwnedResource::Wood, 25),
+        },
+        StructureBuildDesc {
+            tile: Tile::Open,
+            kind: StructureKind::Core,
+            cost: OwnedResources::new().with(OwnedResource::Metal, 15).with(OwnedResource::Wood, 25),
+        },
+    ]
 }
 
 #[derive(Properties, Clone)]
@@ -112,15 +306,75 @@ impl Component for GameView {
     type Properties = GameProps;
 
     fn create(props: Self::Properties, link: ComponentLink<Self>) -> Self {
+        let document = web_sys::window().unwrap().document().unwrap();
+        let key_down_listener = KeyboardService::register_key_down(&document, link.callback(ModelMsg::KeyDown));
+        let key_up_listener = KeyboardService::register_key_up(&document, link.callback(ModelMsg::KeyUp));
+        let blur_listener = controls::WindowBlurListener::new(link.callback(|_| ModelMsg::WindowBlurred));
+
         Self {
             ecs: props.ecs,
             assets: props.assets,
-            _link: link,
+            link,
+            rebinding: None,
+            _key_down_listener: key_down_listener,
+            _key_up_listener: key_up_listener,
+            _blur_listener: blur_listener,
         }
     }
 
     fn update(&mut self, msg: Self::Message) -> ShouldRender {
-        match msg {}
+        match msg {
+            ModelMsg::KeyDown(event) => {
+                if event.repeat() || event.ctrl_key() || event.alt_key() || event.meta_key() {
+                    return false;
+                }
+
+                let code = event.code();
+
+                if let Some(rebinding) = self.rebinding.take() {
+                    event.prevent_default();
+                    self.finish_rebinding(rebinding, code);
+                    return true;
+                }
+
+                let action = self.ecs.with(|_, r| r.get::<KeyMap>().unwrap().action_for(&code));
+
+                if let Some(action) = action {
+                    // otherwise space scrolls the page and tab moves the focus
+                    event.prevent_default();
+                    self.ecs.with(|w, _| {
+                        w.push((UserKeyEvent::Pressed { code, action },));
+                    });
+                }
+
+                false
+            }
+            ModelMsg::KeyUp(event) => {
+                self.ecs.with(|w, _| {
+                    w.push((UserKeyEvent::Released { code: event.code() },));
+                });
+                false
+            }
+            ModelMsg::WindowBlurred => {
+                self.ecs.with(|w, _| {
+                    w.push((UserKeyEvent::AllReleased,));
+                });
+                false
+            }
+            ModelMsg::RebindClicked(rebinding) => {
+                self.rebinding = Some(rebinding);
+                true
+            }
+            ModelMsg::ResetBindingsClicked => {
+                self.rebinding = None;
+                self.ecs.with(|_, r| {
+                    let key_map = KeyMap::default();
+                    controls::save_key_map(&key_map);
+                    r.insert(key_map);
+                });
+                true
+            }
+        }
     }
 
     fn change(&mut self, props: Self::Properties) -> ShouldRender {
@@ -135,10 +389,14 @@ impl Component for GameView {
                     <td_view::TowerDefenseComponent assets={self.assets.clone()} ecs={self.ecs.clone()} />
                 </div>
                 <div class="info-pane-main-div">
+                    { self.objective_view() }
                     <health_view::HealthView ecs={self.ecs.clone()} />
                     <launch_wave_view::LaunchWaveView ecs={self.ecs.clone()} />
+                    { self.wave_preview_view() }
+                    { self.toolbar_view() }
                     { self.resource_view() }
                     { self.detail_view() }
+                    { self.controls_view() }
                 </div>
             </div>
         }
@@ -160,6 +418,147 @@ impl GameView {
         }
     }
 
+    fn objective_view(&self) -> Html {
+        use collapsible_div::*;
+
+        let in_campaign = self
+            .ecs
+            .with(|_, r| matches!(*r.get_or_default::<GameMode>(), GameMode::Campaign { .. }));
+
+        if in_campaign {
+            html! {
+                <Collapsible
+                    ecs=self.ecs.clone(),
+                    collapse_name="Objective",
+                    title="Objective".to_string(),
+                >
+                    <objective_view::ObjectiveView ecs={self.ecs.clone()} />
+                </Collapsible>
+            }
+        } else {
+            html! {}
+        }
+    }
+
+    fn wave_preview_view(&self) -> Html {
+        use collapsible_div::*;
+
+        html! {
+            <Collapsible
+                ecs=self.ecs.clone(),
+                collapse_name="UpcomingWaves",
+                title="Upcoming Waves".to_string(),
+            >
+                <wave_preview_view::WavePreviewView ecs={self.ecs.clone()} />
+            </Collapsible>
+        }
+    }
+
+    fn toolbar_view(&self) -> Html {
+        use collapsible_div::*;
+
+        html! {
+            <Collapsible
+                ecs=self.ecs.clone(),
+                collapse_name="Tools",
+                title="Tools".to_string(),
+            >
+                <toolbar_view::ToolbarView ecs={self.ecs.clone()} />
+            </Collapsible>
+        }
+    }
+
+    fn finish_rebinding(&mut self, rebinding: Rebinding, code: String) {
+        // escape just cancels, and backspace clears the key being replaced
+        if code == "Escape" {
+            return;
+        }
+
+        self.ecs.with(|_, r| {
+            let key_map = &mut *r.get_mut::<KeyMap>().unwrap();
+
+            if let Some(replacing) = rebinding.replacing.as_deref() {
+                key_map.unbind(replacing);
+            }
+            if code != "Backspace" {
+                key_map.bind(code, rebinding.action);
+            }
+
+            controls::save_key_map(key_map);
+        });
+    }
+
+    fn controls_view(&self) -> Html {
+        use collapsible_div::*;
+
+        let (key_map, speed) = self
+            .ecs
+            .with(|_, r| (r.get::<KeyMap>().unwrap().clone(), *r.get::<GameSpeed>().unwrap()));
+
+        let speed_text = if speed.paused {
+            "Paused".to_string()
+        } else {
+            format!("Speed: {}x", speed.ticks_per_step)
+        };
+
+        let rows: Vec<Html> = ALL_INPUT_ACTIONS
+            .iter()
+            .copied()
+            .map(|action| {
+                let keys = key_map
+                    .keys_for(action)
+                    .map(|code| Some(code.to_string()))
+                    .chain(std::iter::once(None))
+                    .map(|replacing| self.key_button(action, replacing))
+                    .collect::<Html>();
+
+                html! {
+                    <div class="control-row">
+                        <span>{ action.description() }</span>
+                        <span>{ keys }</span>
+                    </div>
+                }
+            })
+            .collect();
+
+        let reset_cb = self.link.callback(|_| ModelMsg::ResetBindingsClicked);
+
+        html! {
+            <Collapsible
+                ecs=self.ecs.clone(),
+                collapse_name="Controls",
+                title="Controls".to_string(),
+            >
+                <div class="info-pane">
+                    <div>{ speed_text }</div>
+                    { rows }
+                    <button class="control-reset-button" onclick=reset_cb>{ "Reset to defaults" }</button>
+                </div>
+            </Collapsible>
+        }
+    }
+
+    /// One of the keys bound to the action, or if `replacing` is none, the button to add another
+    fn key_button(&self, action: InputAction, replacing: Option<String>) -> Html {
+        let rebinding = Rebinding { action, replacing };
+
+        let (text, title) = if self.rebinding.as_ref() == Some(&rebinding) {
+            ("press a key...", "Escape to cancel, backspace to clear")
+        } else {
+            match rebinding.replacing.as_deref() {
+                Some(code) => (controls::key_label(code), "Click, then press the new key"),
+                None => ("+", "Click, then press a key to add"),
+            }
+        };
+        let text = text.to_string();
+
+        let cb = self.link.callback(move |_| ModelMsg::RebindClicked(rebinding.clone()));
+
+        html! {
+            <button class="control-key-button" onclick=cb title=title>{ text }</button>
+        }
+    }
+
     fn detail_view(&self) -> Html {
         use collapsible_div::*;
 
@@ -0,0 +1,58 @@
+use yew::prelude::*;
+
+use crate::{resources::*, ECS};
+
+pub(crate) struct ObjectiveView {
+    model: ECS,
+}
+
+#[derive(Clone, Properties)]
+pub(crate) struct ObjectiveProps {
+    pub(crate) ecs: ECS,
+}
+
+#[derive(Clone)]
+pub(crate) enum ObjectiveMsg {}
+
+impl Component for ObjectiveView {
+    type Message = ObjectiveMsg;
+    type Properties = ObjectiveProps;
+
+    fn create(props: Self::Properties, _link: ComponentLink<Self>) -> Self {
+        // link is not used; no callbacks are needed
+        ObjectiveView { model: props.ecs }
+    }
+
+    fn update(&mut self, msg: Self::Message) -> bool {
+        match msg {}
+    }
+
+    fn change(&mut self, props: Self::Properties) -> bool {
+        self.model = props.ecs;
+        true
+    }
+
+    fn view(&self) -> Html {
+        let (level, progress) = self.model.with(|_, r| {
+            let level = match *r.get_or_default::<GameMode>() {
+                GameMode::Campaign { level } => &LEVELS[level],
+                GameMode::Classic | GameMode::Endless { .. } => return (None, Vec::new()),
+            };
+            (Some(level), level.progress(&r.get_or_default::<RunStats>()))
+        });
+
+        let level = match level {
+            Some(level) => level,
+            None => return html! {},
+        };
+
+        html! {
+            <div class="info-pane">
+                <b>{ level.name }</b>
+                <div>{ level.briefing }</div>
+                <div>{ level.objective() }</div>
+                { progress.into_iter().map(|line| html! { <div>{ line }</div> }).collect::<Html>() }
+            </div>
+        }
+    }
+}
@@ -1,43 +1,163 @@
 //! General module for executing and viewing the "tower defense" component (which is basically
 //! an entire game, but the "view" is just a canvas, so that works well)
 
-use std::sync::Arc;
+use std::{collections::HashSet, sync::Arc};
+
+use legion::*;
 
 use yew::prelude::*;
 
 use wasm_bindgen::JsValue;
-use web_sys::{KeyboardEvent, MouseEvent};
+use web_sys::{MouseEvent, WheelEvent};
+
+use crate::{
+    assets::Assets,
+    canvas_util::with_canvas,
+    components::*,
+    resources::*,
+    tile_helpers::{coords_to_tile, coords_to_tile_buffered},
+    ECS,
+};
 
-use crate::{assets::Assets, canvas_util::with_canvas, components::*, resources::*, tile_helpers::coords_to_tile_buffered, ECS};
+/// How far (in screen pixels) the mouse has to move with the button held before it counts as
+/// dragging the map rather than clicking on it
+const DRAG_THRESHOLD: i32 = 4;
+/// How much one notch of the mouse wheel zooms in or out
+const WHEEL_ZOOM_FACTOR: f64 = 1.1;
 
 pub(crate) struct TowerDefenseComponent {
     link: ComponentLink<Self>,
     ecs: ECS,
-    // TODO: at some point use images for tiles
-    #[allow(unused)]
     assets: Arc<Assets>,
+    drag: Option<Drag>,
+    // a drag ends with the button coming up over the canvas, which also counts as a click
+    swallow_click: bool,
+    // the button is down with a tool picked, so moving the mouse paints tiles
+    painting: bool,
+    // the tile a shift-drag started on, which is one corner of the rectangle being selected
+    select_start: Option<(i32, i32)>,
 }
 
+/// The mouse button is down over the canvas; this may turn out to be a click or a drag
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
-pub(crate) enum TDMessage {
-    ClickedPixel { x: i32, y: i32 },
-    Cancel,
-    KeyUp(ArrowKey),
-    KeyDown(ArrowKey),
-    FocusLost,
-    // callbacks can't conditionally return things, so we need an "actually nevermind"
-    Nothing,
+struct Drag {
+    start_x: i32,
+    start_y: i32,
+    start_camera_left: i32,
+    start_camera_top: i32,
+    dragging: bool,
 }
 
-#[derive(Copy, Clone, Eq, PartialEq, Debug)]
-pub(crate) enum ArrowKey {
-    Down,
-    Left,
-    Right,
-    Up,
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub(crate) enum TDMessage {
+    ClickedPixel {
+        x: i32,
+        y: i32,
+    },
+    /// `primary` is whether it was the left button; with a tool picked, the others still drag the
+    /// map. With shift held and no tool, the left button adds tiles to the selection instead.
+    MouseDown {
+        x: i32,
+        y: i32,
+        primary: bool,
+        shift: bool,
+    },
+    MouseMoved {
+        x: i32,
+        y: i32,
+    },
+    MouseUp,
+    MouseLeft,
+    Wheel {
+        x: i32,
+        y: i32,
+        delta_y: f64,
+    },
 }
 
 impl TowerDefenseComponent {
+    /// The tile under the given point on the canvas
+    fn tile_at(&self, x: i32, y: i32) -> (i32, i32) {
+        let (world_x, world_y) = self
+            .ecs
+            .with(|_, r| r.get_or_default::<TdCamera>().screen_to_world(x as f64, y as f64));
+
+        coords_to_tile(world_x, world_y)
+    }
+
+    fn end_drag(&mut self) {
+        if let Some(drag) = self.drag.take() {
+            self.swallow_click = drag.dragging;
+        }
+
+        if let Some(start) = self.select_start.take() {
+            self.swallow_click = true;
+            self.ecs.with(|w, r| {
+                if let Some(rect) = r.get_mut::<TdSelectDrag>().unwrap().0.take() {
+                    // a shift-click, rather than a drag, picks the one tile up or puts it down
+                    if rect.x_min == rect.x_max && rect.y_min == rect.y_max {
+                        w.push((UserToggleTile {
+                            tile_x: start.0,
+                            tile_y: start.1,
+                        },));
+                    } else {
+                        w.push((UserSelectRect(rect),));
+                    }
+                }
+            });
+        }
+
+        if std::mem::take(&mut self.painting) {
+            self.swallow_click = true;
+            self.finish_stroke();
+        }
+    }
+
+    /// Send off a change or build for every tile in the stroke which passed, in the order they were
+    /// painted, so they're carried out just as they were planned
+    fn finish_stroke(&self) {
+        self.ecs.with(|world, r| {
+            let occupied: HashSet<(i32, i32)> = <(Read<Structure>, Read<Position>)>::query()
+                .iter(world)
+                .map(|(_, pos)| pos.to_tile_coords())
+                .collect();
+
+            let plan = r.get::<PaintState>().unwrap().plan(
+                &r.get::<Map>().unwrap(),
+                &r.get::<TileTransforms>().unwrap(),
+                &r.get::<StructureBuilds>().unwrap(),
+                &occupied,
+                &r.get::<OwnedResources>().unwrap(),
+            );
+
+            let paint = &mut *r.get_mut::<PaintState>().unwrap();
+
+            for (x, y, costs) in plan.accepted() {
+                match paint.tool {
+                    Some(PaintTool::ChangeTile(desired)) => {
+                        world.push((TryChangeTileType {
+                            x,
+                            y,
+                            desired,
+                            costs: costs.clone(),
+                        },));
+                    }
+                    Some(PaintTool::Build(desired)) => {
+                        world.push((TryBuildStructure {
+                            x,
+                            y,
+                            desired,
+                            costs: costs.clone(),
+                        },));
+                    }
+                    None => {}
+                }
+            }
+
+            paint.stroke.clear();
+        });
+    }
+
     fn draw_canvas(&self) {
         with_canvas(|canvas_state| {
             canvas_state
@@ -55,6 +175,16 @@ impl TowerDefenseComponent {
             );
 
             self.ecs.with(|world, resources| {
+                // everything is drawn in world pixels (less the camera offset), and scaled here
+                let zoom = {
+                    let mut camera = resources.get_mut_or_insert_with(TdCamera::default);
+                    camera.screen_width = canvas_state.bounding_rect.width();
+                    camera.screen_height = canvas_state.bounding_rect.height();
+                    camera.zoom
+                };
+                canvas_state.context.scale(zoom, zoom).expect("Scaling should work");
+                canvas_state.context.set_image_smoothing_enabled(false);
+
                 crate::systems::canvas_render_schedule(&canvas_state, &self.assets).execute(world, resources);
             });
         });
@@ -76,48 +206,106 @@ impl Component for TowerDefenseComponent {
             link,
             ecs: props.ecs,
             assets: props.assets,
+            drag: None,
+            swallow_click: false,
+            painting: false,
+            select_start: None,
         }
     }
 
     fn update(&mut self, msg: Self::Message) -> bool {
         match msg {
-            TDMessage::Nothing => {}
             TDMessage::ClickedPixel { x, y } => {
-                let (left, top) = self.ecs.with(|_, r| {
-                    let camera = r.get_or_default::<TdCamera>();
-                    (camera.left, camera.top)
-                });
+                if std::mem::take(&mut self.swallow_click) {
+                    return false;
+                }
 
-                if let Some((tile_x, tile_y)) = coords_to_tile_buffered(x + left, y + top, 2) {
+                let (world_x, world_y) = self
+                    .ecs
+                    .with(|_, r| r.get_or_default::<TdCamera>().screen_to_world(x as f64, y as f64));
+
+                if let Some((tile_x, tile_y)) = coords_to_tile_buffered(world_x, world_y, 2) {
                     self.ecs.with(|w, _| {
                         w.push((UserClickTile { tile_x, tile_y },));
                     });
                 }
             }
-            TDMessage::Cancel => {
-                self.ecs.with(|w, _| {
-                    w.push((UserUnselectTile,));
+            TDMessage::MouseDown { x, y, primary, shift } => {
+                let has_tool = self.ecs.with(|_, r| r.get::<PaintState>().unwrap().tool.is_some());
+
+                if primary && shift && !has_tool {
+                    let start = self.tile_at(x, y);
+                    self.select_start = Some(start);
+                    self.ecs.with(|_, r| {
+                        r.get_mut::<TdSelectDrag>().unwrap().0 = Some(TileRect::from_corners(start, start));
+                    });
+                    return false;
+                }
+
+                if primary && has_tool {
+                    let (tile_x, tile_y) = self.tile_at(x, y);
+                    self.painting = true;
+                    self.ecs.with(|_, r| {
+                        r.get_mut::<PaintState>().unwrap().add_to_stroke(tile_x, tile_y);
+                    });
+                    return false;
+                }
+
+                let camera = self.ecs.with(|_, r| *r.get_or_default::<TdCamera>());
+                self.drag = Some(Drag {
+                    start_x: x,
+                    start_y: y,
+                    start_camera_left: camera.left,
+                    start_camera_top: camera.top,
+                    dragging: false,
                 });
             }
-            TDMessage::KeyDown(arrow_key) => {
-                self.ecs.with(|w, _| match arrow_key {
-                    ArrowKey::Down => w.push((UserKeyEvent::KeyDown(UserKey::Down),)),
-                    ArrowKey::Left => w.push((UserKeyEvent::KeyDown(UserKey::Left),)),
-                    ArrowKey::Right => w.push((UserKeyEvent::KeyDown(UserKey::Right),)),
-                    ArrowKey::Up => w.push((UserKeyEvent::KeyDown(UserKey::Up),)),
+            TDMessage::MouseMoved { x, y } => {
+                let (tile_x, tile_y) = self.tile_at(x, y);
+                let painting = self.painting;
+                let select_start = self.select_start;
+
+                self.ecs.with(|_, r| {
+                    let paint = &mut *r.get_mut::<PaintState>().unwrap();
+                    paint.hover = Some((tile_x, tile_y));
+
+                    if painting {
+                        paint_line_to(paint, tile_x, tile_y);
+                    }
+
+                    if let Some(start) = select_start {
+                        r.get_mut::<TdSelectDrag>().unwrap().0 = Some(TileRect::from_corners(start, (tile_x, tile_y)));
+                    }
                 });
+
+                if let Some(drag) = self.drag.as_mut() {
+                    let (dx, dy) = (x - drag.start_x, y - drag.start_y);
+
+                    if dx.abs() > DRAG_THRESHOLD || dy.abs() > DRAG_THRESHOLD {
+                        drag.dragging = true;
+                    }
+
+                    if drag.dragging {
+                        let drag = *drag;
+                        self.ecs.with(|_, r| {
+                            let mut camera = r.get_mut_or_insert_with(TdCamera::default);
+                            // the map follows the mouse, so the camera goes the other way
+                            camera.left = drag.start_camera_left - (dx as f64 / camera.zoom).round() as i32;
+                            camera.top = drag.start_camera_top - (dy as f64 / camera.zoom).round() as i32;
+                        });
+                    }
+                }
             }
-            TDMessage::KeyUp(arrow_key) => {
-                self.ecs.with(|w, _| match arrow_key {
-                    ArrowKey::Down => w.push((UserKeyEvent::KeyUp(UserKey::Down),)),
-                    ArrowKey::Left => w.push((UserKeyEvent::KeyUp(UserKey::Left),)),
-                    ArrowKey::Right => w.push((UserKeyEvent::KeyUp(UserKey::Right),)),
-                    ArrowKey::Up => w.push((UserKeyEvent::KeyUp(UserKey::Up),)),
-                });
+            TDMessage::MouseUp => self.end_drag(),
+            TDMessage::MouseLeft => {
+                self.end_drag();
+                self.ecs.with(|_, r| r.get_mut::<PaintState>().unwrap().hover = None);
             }
-            TDMessage::FocusLost => {
-                self.ecs.with(|w, _| {
-                    w.push((UserKeyEvent::AllKeysUp,));
+            TDMessage::Wheel { x, y, delta_y } => {
+                let factor = if delta_y < 0. { WHEEL_ZOOM_FACTOR } else { 1. / WHEEL_ZOOM_FACTOR };
+
+                self.ecs.with(|_, r| {
+                    r.get_mut_or_insert_with(TdCamera::default).zoom_around(x as f64, y as f64, factor);
                 });
             }
         }
@@ -133,49 +321,44 @@ impl Component for TowerDefenseComponent {
     }
 
     fn view(&self) -> Html {
-        let hover_cb = self.link.callback(|_: MouseEvent| {
-            with_canvas(|cs| {
-                cs.canvas.focus().unwrap();
-            });
-
-            TDMessage::Nothing
+        let hover_cb = self.link.callback(|mouse_event: MouseEvent| {
+            let (x, y) = canvas_coords(&mouse_event);
+            TDMessage::MouseMoved { x, y }
         });
 
         let click_cb = self.link.callback(|mouse_event: MouseEvent| {
-            let (x, y) = with_canvas(|cs| {
-                cs.canvas.focus().unwrap();
-
-                let x = mouse_event.client_x() - cs.bounding_rect.left() as i32;
-                let y = mouse_event.client_y() - cs.bounding_rect.top() as i32;
-
-                (x, y)
-            });
-
+            let (x, y) = canvas_coords(&mouse_event);
             TDMessage::ClickedPixel { x, y }
         });
 
-        let kd_cb = self.link.callback(|e: KeyboardEvent| match e.code().as_str() {
-            "ArrowDown" | "KeyS" => TDMessage::KeyDown(ArrowKey::Down),
-            "ArrowUp" | "KeyW" => TDMessage::KeyDown(ArrowKey::Up),
-            "ArrowLeft" | "KeyA" => TDMessage::KeyDown(ArrowKey::Left),
-            "ArrowRight" | "KeyD" => TDMessage::KeyDown(ArrowKey::Right),
-            "Escape" => TDMessage::Cancel,
-            _ => TDMessage::Nothing,
+        let down_cb = self.link.callback(|mouse_event: MouseEvent| {
+            let (x, y) = canvas_coords(&mouse_event);
+            TDMessage::MouseDown {
+                x,
+                y,
+                primary: mouse_event.button() == 0,
+                shift: mouse_event.shift_key(),
+            }
         });
 
-        let ku_cb = self.link.callback(|e: KeyboardEvent| match e.code().as_str() {
-            "ArrowDown" | "KeyS" => TDMessage::KeyUp(ArrowKey::Down),
-            "ArrowUp" | "KeyW" => TDMessage::KeyUp(ArrowKey::Up),
-            "ArrowLeft" | "KeyA" => TDMessage::KeyUp(ArrowKey::Left),
-            "ArrowRight" | "KeyD" => TDMessage::KeyUp(ArrowKey::Right),
-            "Escape" => TDMessage::Cancel,
-            _ => TDMessage::Nothing,
-        });
+        let up_cb = self.link.callback(|_: MouseEvent| TDMessage::MouseUp);
 
-        let focus_lost_cb = self.link.callback(|_| TDMessage::FocusLost);
+        // if the button comes up off the canvas we never hear about it, so stop dragging on the way out
+        let leave_cb = self.link.callback(|_: MouseEvent| TDMessage::MouseLeft);
+
+        let wheel_cb = self.link.callback(|wheel_event: WheelEvent| {
+            // keep the page from scrolling along with the zoom
+            wheel_event.prevent_default();
+            let (x, y) = canvas_coords(&wheel_event);
+            TDMessage::Wheel {
+                x,
+                y,
+                delta_y: wheel_event.delta_y(),
+            }
+        });
 
         html! {
-            <canvas id="td-canvas" tabIndex=1 onclick=click_cb onmousemove=hover_cb onkeydown=kd_cb onkeyup=ku_cb onblur=focus_lost_cb />
+            <canvas id="td-canvas" tabIndex=1 onclick=click_cb onmousedown=down_cb onmouseup=up_cb onmouseleave=leave_cb onmousemove=hover_cb onwheel=wheel_cb />
         }
     }
 
@@ -193,6 +376,40 @@ impl Component for TowerDefenseComponent {
             if style_h != actual_h {
                 cs.canvas.set_height(style_h);
             }
+
+            self.ecs.with(|_, r| {
+                let mut camera = r.get_mut_or_insert_with(TdCamera::default);
+                camera.screen_width = cs.bounding_rect.width();
+                camera.screen_height = cs.bounding_rect.height();
+            });
         })
     }
 }
+
+/// Paint every tile on a straight line from the end of the stroke to the given tile, so moving the
+/// mouse quickly doesn't leave gaps
+fn paint_line_to(paint: &mut PaintState, tile_x: i32, tile_y: i32) {
+    let (from_x, from_y) = match paint.stroke.last() {
+        Some(&last) => last,
+        None => (tile_x, tile_y),
+    };
+
+    let (dx, dy) = (tile_x - from_x, tile_y - from_y);
+    let steps = dx.abs().max(dy.abs()).max(1);
+
+    for step in 1..=steps {
+        let x = from_x + (dx as f64 * step as f64 / steps as f64).round() as i32;
+        let y = from_y + (dy as f64 * step as f64 / steps as f64).round() as i32;
+        paint.add_to_stroke(x, y);
+    }
+}
+
+/// Where the mouse is, in pixels from the top left of the canvas
+fn canvas_coords(mouse_event: &MouseEvent) -> (i32, i32) {
+    with_canvas(|cs| {
+        let x = mouse_event.client_x() - cs.bounding_rect.left() as i32;
+        let y = mouse_event.client_y() - cs.bounding_rect.top() as i32;
+
+        (x, y)
+    })
+}
@@ -0,0 +1,117 @@
+use legion::*;
+use yew::prelude::*;
+
+use crate::{components::*, resources::*, ECS};
+
+/// The tools on offer, and what their buttons say; "Select" is no tool at all, so clicking a tile
+/// just selects it
+const TOOLS: &[(&str, Option<PaintTool>)] = &[
+    ("Select", None),
+    ("Wall", Some(PaintTool::ChangeTile(Tile::Wall))),
+    ("Open", Some(PaintTool::ChangeTile(Tile::Open))),
+    ("Gas trap", Some(PaintTool::Build(StructureKind::GasTrap))),
+];
+
+pub(crate) struct ToolbarView {
+    link: ComponentLink<Self>,
+    ecs: ECS,
+}
+
+#[derive(Clone, Properties)]
+pub(crate) struct ToolbarProps {
+    pub(crate) ecs: ECS,
+}
+
+#[derive(Clone)]
+pub(crate) enum ToolbarMessage {
+    ToolPicked(Option<PaintTool>),
+    UndoClicked,
+}
+
+impl Component for ToolbarView {
+    type Message = ToolbarMessage;
+    type Properties = ToolbarProps;
+
+    fn create(props: Self::Properties, link: ComponentLink<Self>) -> Self {
+        ToolbarView { link, ecs: props.ecs }
+    }
+
+    fn update(&mut self, msg: Self::Message) -> bool {
+        match msg {
+            ToolbarMessage::ToolPicked(tool) => {
+                self.ecs.with(|_, r| {
+                    *r.get_mut::<PaintState>().unwrap() = PaintState {
+                        tool,
+                        ..PaintState::default()
+                    };
+                });
+            }
+            ToolbarMessage::UndoClicked => {
+                self.ecs.with(|world, _| {
+                    world.push((TryUndo,));
+                });
+            }
+        }
+
+        true
+    }
+
+    fn change(&mut self, props: Self::Properties) -> bool {
+        self.ecs = props.ecs;
+        true
+    }
+
+    fn view(&self) -> Html {
+        let current = self.ecs.with(|_, r| r.get::<PaintState>().unwrap().tool);
+
+        let buttons: Vec<Html> = TOOLS
+            .iter()
+            .copied()
+            .map(|(name, tool)| {
+                let style_class = if tool == current {
+                    "tool-button tool-button-picked"
+                } else {
+                    "tool-button"
+                };
+                let cb = self.link.callback(move |_| ToolbarMessage::ToolPicked(tool));
+
+                html! {
+                    <button class=style_class onclick=cb>{ name }</button>
+                }
+            })
+            .collect();
+
+        html! {
+            <div class="toolbar-div">
+                { buttons }
+                { self.undo_button() }
+            </div>
+        }
+    }
+}
+
+impl ToolbarView {
+    fn undo_button(&self) -> Html {
+        let (can_undo, remembered) = self.ecs.with(|world, r| {
+            let undo_stack = r.get::<UndoStack>().unwrap();
+            let tick = r.get::<TickCount>().unwrap();
+            let wave_active = <Read<WaveState>>::query().iter(world).next().is_some();
+
+            (undo_stack.can_undo(tick.0, wave_active), undo_stack.len())
+        });
+
+        let title = if can_undo {
+            format!("Undo the last change ({} remembered)", remembered)
+        } else if remembered > 0 {
+            "Only just-made changes can be undone while a wave is on".to_string()
+        } else {
+            "Nothing to undo".to_string()
+        };
+
+        html! {
+            <button class="tool-button" title=title disabled=!can_undo onclick=self.link.callback(|_| ToolbarMessage::UndoClicked)>
+                { "Undo" }
+            </button>
+        }
+    }
+}
@@ -0,0 +1,122 @@
+use legion::*;
+use yew::prelude::*;
+
+use crate::{components::*, resources::*, ECS};
+
+/// How many waves ahead are shown, counting the next one
+const PREVIEW_WAVES: usize = 3;
+
+pub(crate) struct WavePreviewView {
+    model: ECS,
+}
+
+#[derive(Clone, Properties)]
+pub(crate) struct WavePreviewProps {
+    pub(crate) ecs: ECS,
+}
+
+#[derive(Clone)]
+pub(crate) enum WavePreviewMessage {}
+
+impl Component for WavePreviewView {
+    type Message = WavePreviewMessage;
+    type Properties = WavePreviewProps;
+
+    fn create(props: Self::Properties, _link: ComponentLink<Self>) -> Self {
+        // link is not used; no callbacks are needed
+        WavePreviewView { model: props.ecs }
+    }
+
+    fn update(&mut self, msg: Self::Message) -> bool {
+        match msg {}
+    }
+
+    fn change(&mut self, props: Self::Properties) -> bool {
+        self.model = props.ecs;
+        true
+    }
+
+    fn view(&self) -> Html {
+        // planned from the spawns there are now; building or selling one changes what's coming
+        let plans: Vec<WavePlan> = self.model.with(|world, r| {
+            let next_wave = r.get::<NextWaveState>().unwrap().next_wave;
+            let mode = *r.get_or_default::<GameMode>();
+
+            let spawns: Vec<(Spawn, (i32, i32))> = <(Read<Spawn>, Read<Position>)>::query()
+                .iter(world)
+                .map(|(spawn, pos)| (*spawn, pos.to_tile_coords()))
+                .collect();
+
+            (next_wave..next_wave + PREVIEW_WAVES)
+                .map(|wave_num| WavePlan::new(mode, wave_num, &spawns))
+                .collect()
+        });
+
+        html! {
+            <div class="info-pane">
+                { plans.iter().map(wave_view).collect::<Html>() }
+            </div>
+        }
+    }
+}
+
+fn wave_view(plan: &WavePlan) -> Html {
+    let title = if plan.is_boss_wave() {
+        format!("Wave {} (boss wave)", plan.wave_num)
+    } else {
+        format!("Wave {}", plan.wave_num)
+    };
+
+    if plan.mobs.is_empty() {
+        return html! {
+            <div class="wave-preview">
+                <b>{ title }</b>
+                <div>{ "No spawn sends anything" }</div>
+            </div>
+        };
+    }
+
+    let mobs: Vec<String> = plan
+        .counts()
+        .into_iter()
+        .map(|(kind, count)| {
+            let desc = kind.desc();
+            let name = if count == 1 { desc.name } else { desc.plural };
+            format!("{} {}", count, name)
+        })
+        .collect();
+
+    let spawns: Vec<String> = plan.spawn_tiles().into_iter().map(|(x, y)| format!("({}, {})", x, y)).collect();
+
+    let traits: Vec<&str> = plan.traits().into_iter().map(MobTrait::description).collect();
+    let traits = if traits.is_empty() {
+        html! {}
+    } else {
+        html! { <div>{ format!("Watch for: {}", traits.join(", ")) }</div> }
+    };
+
+    let affixes: Vec<&str> = plan.affixes.iter().copied().map(Affix::description).collect();
+    let affixes = if affixes.is_empty() {
+        html! {}
+    } else {
+        html! { <div>{ format!("Every mob is {}", affixes.join(", ")) }</div> }
+    };
+
+    let health = if plan.health_percent == 100 {
+        html! {}
+    } else {
+        html! { <div>{ format!("Health: {}%", plan.health_percent) }</div> }
+    };
+
+    html! {
+        <div class="wave-preview">
+            <b>{ title }</b>
+            <div>{ mobs.join(", ") }</div>
+            <div>{ format!("From: {}", spawns.join(", ")) }</div>
+            { traits }
+            { affixes }
+            { health }
+            <div>{ format!("Reward: {}", plan.reward()) }</div>
+        </div>
+    }
+}
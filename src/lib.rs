@@ -17,7 +17,7 @@ pub use ecs_wrapper::ECS;
 mod tile_helpers;
 
 mod components;
-mod resources;
+pub mod resources;
 mod systems;
 
 mod canvas_util;
@@ -50,7 +50,8 @@ mod ecs_wrapper {
 
 struct View {
     ecs: ECS,
-    schedule: Schedule,
+    input_schedule: Schedule,
+    sim_schedule: Schedule,
     assets: Arc<assets::Assets>,
 
     // We have to keep a reference to this; it keeps triggering until it's dropped
@@ -73,7 +74,7 @@ impl Component for View {
     fn create(props: Self::Properties, link: ComponentLink<Self>) -> Self {
         let ecs = ECS::new();
 
-        game_view::init_ecs(&ecs);
+        game_view::init_ecs(&ecs, resources::GameMode::Classic, resources::BlockingRule::NeverBlock);
 
         ecs.with(|_, r| {
             r.insert(resources::GameState::Opening);
@@ -83,13 +84,12 @@ impl Component for View {
 
         let tick_handle = IntervalService::spawn(std::time::Duration::from_millis(50), tick_cb);
 
-        let schedule = crate::systems::make_tick_schedule();
-
         Self {
             ecs,
             assets: props.assets,
             _tick_handle: Box::new(tick_handle),
-            schedule,
+            input_schedule: crate::systems::make_input_schedule(),
+            sim_schedule: crate::systems::make_sim_schedule(),
         }
     }
 
@@ -98,15 +98,29 @@ impl Component for View {
 
         match msg {
             ViewMsg::Tick => {
-                let schedule = &mut self.schedule;
+                let input_schedule = &mut self.input_schedule;
+                let sim_schedule = &mut self.sim_schedule;
                 self.ecs.with(|world, resources| {
                     let should_run_tick = match *resources.get::<GameState>().unwrap() {
                         GameState::MainGame => true,
-                        GameState::Opening | GameState::Died => false,
+                        GameState::Opening | GameState::Died | GameState::Won => false,
                     };
 
+                    let speed = *resources.get_or_default::<resources::GameSpeed>();
+
                     if should_run_tick {
-                        schedule.execute(world, resources);
+                        input_schedule.execute(world, resources);
+
+                        if !speed.paused {
+                            for _ in 0..speed.ticks_per_step {
+                                sim_schedule.execute(world, resources);
+                            }
+                        }
+
+                        // nothing runs once the game is over, so this is the tick it ended on
+                        if *resources.get::<GameState>().unwrap() == GameState::Died {
+                            new_game_view::record_game_over(resources);
+                        }
                     }
                 });
 
@@ -127,6 +141,7 @@ impl Component for View {
         match game_state {
             GameState::Opening => self.render_opening(),
             GameState::Died => self.render_died(),
+            GameState::Won => self.render_won(),
             GameState::MainGame => self.render_main_game(),
         }
     }
@@ -145,6 +160,12 @@ impl View {
         }
     }
 
+    fn render_won(&self) -> Html {
+        html! {
+            <new_game_view::WonView ecs=self.ecs.clone() />
+        }
+    }
+
     fn render_main_game(&self) -> Html {
         html! {
             <game_view::GameView assets=self.assets.clone() ecs=self.ecs.clone() />
@@ -1,14 +1,49 @@
+mod records;
+mod summary_view;
+
+use legion::Resources;
 use web_sys::MouseEvent;
 use yew::prelude::*;
 
 use crate::{resources::*, ECS};
 
+/// How an endless game went, worked out and recorded once, as it ended
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+struct EndlessResult {
+    survived: usize,
+    new_best: bool,
+}
+
+/// Keep whatever bests the game which just ended set. Called once, when it ends, so the new game
+/// screen already knows, and so showing the end of it again doesn't record anything again.
+pub(crate) fn record_game_over(r: &mut Resources) {
+    let mode = *r.get_or_default::<GameMode>();
+    if let GameMode::Endless { .. } = mode {
+        let survived = r.get_or_default::<RunStats>().waves_survived();
+        let new_best = records::record_endless_waves(survived);
+        r.insert(EndlessResult { survived, new_best });
+    }
+}
+
 pub(crate) struct NewGameView {
+    link: ComponentLink<Self>,
     model: ECS,
+    endless_best: Option<usize>,
+    levels_unlocked: usize,
+    mobs_break_walls: bool,
 }
 
 pub(crate) struct DiedView {
     model: ECS,
+    /// How many waves were survived, if it was an endless game, and whether that's a new best
+    endless_result: Option<(usize, bool)>,
+    /// The level, if it was a campaign game, and how it was lost if not by the main core falling
+    level_result: Option<(usize, Option<LevelFailure>)>,
+}
+
+pub(crate) struct WonView {
+    model: ECS,
+    level: Option<usize>,
 }
 
 #[derive(Clone, Properties)]
@@ -24,12 +59,113 @@ enum ClickMsg {
     Clicked,
 }
 
+#[derive(Copy, Clone)]
+pub(crate) enum NewGameMsg {
+    MobsBreakWallsToggled,
+}
+
 impl Component for NewGameView {
+    type Message = NewGameMsg;
+    type Properties = EcsProps;
+
+    fn create(props: Self::Properties, link: ComponentLink<Self>) -> Self {
+        NewGameView {
+            link,
+            model: props.ecs,
+            endless_best: records::load_endless_best(),
+            levels_unlocked: records::load_levels_unlocked(),
+            mobs_break_walls: records::load_mobs_break_walls(),
+        }
+    }
+
+    fn update(&mut self, msg: Self::Message) -> bool {
+        match msg {
+            NewGameMsg::MobsBreakWallsToggled => {
+                self.mobs_break_walls = !self.mobs_break_walls;
+                records::store_mobs_break_walls(self.mobs_break_walls);
+                true
+            }
+        }
+    }
+
+    fn change(&mut self, props: Self::Properties) -> bool {
+        self.model = props.ecs;
+        true
+    }
+
+    fn view(&self) -> Html {
+        let levels: Html = LEVELS
+            .iter()
+            .enumerate()
+            .map(|(idx, level)| {
+                if idx < self.levels_unlocked {
+                    html! {
+                        <div class="campaign-level">
+                            <StartGameBtn ecs=self.model.clone() choice=StartChoice::Level(idx) />
+                            <div>{ level.objective() }</div>
+                        </div>
+                    }
+                } else {
+                    html! {
+                        <div class="campaign-level">
+                            <div class="new-game-button locked">{ format!("Level {}: locked", idx + 1) }</div>
+                            <div>{ "Win the level before to unlock it" }</div>
+                        </div>
+                    }
+                }
+            })
+            .collect();
+
+        let (walls_label, walls_description) = if self.mobs_break_walls {
+            (
+                "Mobs break walls: on",
+                "Wall the cores off entirely if you like; mobs will knock their way through.",
+            )
+        } else {
+            ("Mobs break walls: off", "There must always be a way from every spawn to a core.")
+        };
+        let walls_cb = self.link.callback(|_: MouseEvent| NewGameMsg::MobsBreakWallsToggled);
+
+        html! {
+            <div class="new-game-menu">
+                <div><p>{ "Radishes Have Their Own Value" }</p></div>
+                <StartGameBtn ecs=self.model.clone() choice=StartChoice::Classic />
+                <StartGameBtn ecs=self.model.clone() choice=StartChoice::Endless />
+                <div class="new-game-button" onclick=walls_cb>{ walls_label }</div>
+                <div><p>{ walls_description }</p></div>
+                { endless_best_view(self.endless_best) }
+                <div><p>{ "Campaign" }</p></div>
+                { levels }
+            </div>
+        }
+    }
+}
+
+impl Component for DiedView {
     type Message = NoMsg;
     type Properties = EcsProps;
 
     fn create(props: Self::Properties, _link: ComponentLink<Self>) -> Self {
-        NewGameView { model: props.ecs }
+        let (endless_result, level_result) = props.ecs.with(|_, r| {
+            let mode = *r.get_or_default::<GameMode>();
+            match mode {
+                GameMode::Endless { .. } => {
+                    let result = r.get::<EndlessResult>().map(|result| (result.survived, result.new_best));
+                    (result, None)
+                }
+                GameMode::Campaign { level } => {
+                    let failure = LEVELS[level].failure(&r.get_or_default::<RunStats>());
+                    (None, Some((level, failure)))
+                }
+                GameMode::Classic => (None, None),
+            }
+        });
+
+        DiedView {
+            model: props.ecs,
+            endless_result,
+            level_result,
+        }
     }
 
     fn update(&mut self, msg: Self::Message) -> bool {
@@ -42,21 +178,58 @@ impl Component for NewGameView {
     }
 
     fn view(&self) -> Html {
+        let endless_result = match self.endless_result {
+            Some((survived, true)) => html! { <div><p>{ format!("You survived {} waves, your best yet!", survived) }</p></div> },
+            Some((survived, false)) => html! { <div><p>{ format!("You survived {} waves.", survived) }</p></div> },
+            None => html! {},
+        };
+
+        let (reason, retry) = match self.level_result {
+            Some((level, failure)) => (
+                format!(
+                    "{} failed. {}",
+                    LEVELS[level].name,
+                    failure.map_or("The main core fell.", LevelFailure::description)
+                ),
+                html! { <StartGameBtn ecs=self.model.clone() choice=StartChoice::Level(level) /> },
+            ),
+            None => (
+                "If your health drops below zero, you will lose the game. That's probably what happened to you. It's okay. It's probably okay."
+                    .to_string(),
+                html! {},
+            ),
+        };
+
         html! {
             <div class="new-game-menu">
-                <div><p>{ "Radishes Have Their Own Value" }</p></div>
-                <StartGameBtn ecs=self.model.clone() />
+                { endless_result }
+                <div><p>{ reason }</p></div>
+                <summary_view::RunSummary ecs=self.model.clone() />
+                { retry }
+                <StartGameBtn ecs=self.model.clone() choice=StartChoice::Classic />
+                <StartGameBtn ecs=self.model.clone() choice=StartChoice::Endless />
+                <MenuBtn ecs=self.model.clone() />
             </div>
         }
     }
 }
 
-impl Component for DiedView {
+impl Component for WonView {
     type Message = NoMsg;
     type Properties = EcsProps;
 
     fn create(props: Self::Properties, _link: ComponentLink<Self>) -> Self {
-        DiedView { model: props.ecs }
+        let level = props.ecs.with(|_, r| match *r.get_or_default::<GameMode>() {
+            GameMode::Campaign { level } => Some(level),
+            GameMode::Classic | GameMode::Endless { .. } => None,
+        });
+
+        // unlocked as soon as it's won, so the new game screen already knows
+        if let Some(level) = level {
+            records::record_level_won(level);
+        }
+
+        WonView { model: props.ecs, level }
     }
 
     fn update(&mut self, msg: Self::Message) -> bool {
@@ -69,21 +242,122 @@ impl Component for DiedView {
     }
 
     fn view(&self) -> Html {
+        let (title, objective, next) = match self.level {
+            Some(level) => {
+                let next = if level + 1 < LEVELS.len() {
+                    html! { <StartGameBtn ecs=self.model.clone() choice=StartChoice::Level(level + 1) /> }
+                } else {
+                    html! { <div><p>{ "That was the last level. Well done!" }</p></div> }
+                };
+                (format!("{} complete!", LEVELS[level].name), LEVELS[level].objective(), next)
+            }
+            None => ("You won!".to_string(), String::new(), html! {}),
+        };
+
         html! {
             <div class="new-game-menu">
-                <div><p>{ "If your health drops below zero, you will lose the game. That's probably what happened to you. It's okay. It's probably okay." }</p></div>
-                <StartGameBtn ecs=self.model.clone() />
+                <div><p>{ title }</p></div>
+                <div><p>{ objective }</p></div>
+                <summary_view::RunSummary ecs=self.model.clone() />
+                { next }
+                <MenuBtn ecs=self.model.clone() />
             </div>
         }
     }
 }
 
+fn endless_best_view(best: Option<usize>) -> Html {
+    match best {
+        Some(best) => html! { <div><p>{ format!("Most endless waves survived: {}", best) }</p></div> },
+        None => html! {},
+    }
+}
+
+/// What kind of game a start button starts
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+enum StartChoice {
+    Classic,
+    /// With a fresh seed each time
+    Endless,
+    /// By its index in `LEVELS`
+    Level(usize),
+}
+
+#[derive(Clone, Properties)]
+struct StartGameProps {
+    ecs: ECS,
+    choice: StartChoice,
+}
+
 struct StartGameBtn {
     link: ComponentLink<Self>,
     model: ECS,
+    choice: StartChoice,
 }
 
 impl Component for StartGameBtn {
+    type Message = ClickMsg;
+    type Properties = StartGameProps;
+
+    fn create(props: Self::Properties, link: ComponentLink<Self>) -> Self {
+        Self {
+            link,
+            model: props.ecs,
+            choice: props.choice,
+        }
+    }
+
+    fn update(&mut self, msg: Self::Message) -> bool {
+        match msg {
+            ClickMsg::Clicked => {
+                let mode = match self.choice {
+                    StartChoice::Classic => GameMode::Classic,
+                    StartChoice::Endless => GameMode::Endless { seed: rand::random() },
+                    StartChoice::Level(level) => GameMode::Campaign { level },
+                };
+                let blocking = if records::load_mobs_break_walls() {
+                    BlockingRule::MobsBreakWalls
+                } else {
+                    BlockingRule::NeverBlock
+                };
+                crate::game_view::init_ecs(&self.model, mode, blocking);
+                self.model.with(|_, r| {
+                    r.insert(GameState::MainGame);
+                });
+                true
+            }
+        }
+    }
+
+    fn change(&mut self, props: Self::Properties) -> bool {
+        self.model = props.ecs;
+        self.choice = props.choice;
+        true
+    }
+
+    fn view(&self) -> Html {
+        let label = match self.choice {
+            StartChoice::Classic => "Start Game".to_string(),
+            StartChoice::Endless => "Start Endless".to_string(),
+            StartChoice::Level(level) => format!("Level {}: {}", level + 1, LEVELS[level].name),
+        };
+        let click_cb = self.link.callback(|_: MouseEvent| ClickMsg::Clicked);
+
+        html! {
+            <div class="new-game-button" onclick=click_cb>
+                { label }
+            </div>
+        }
+    }
+}
+
+/// Back to the new game screen, to pick another level or mode
+struct MenuBtn {
+    link: ComponentLink<Self>,
+    model: ECS,
+}
+
+impl Component for MenuBtn {
     type Message = ClickMsg;
     type Properties = EcsProps;
 
@@ -94,9 +368,8 @@ impl Component for StartGameBtn {
     fn update(&mut self, msg: Self::Message) -> bool {
         match msg {
             ClickMsg::Clicked => {
-                crate::game_view::init_ecs(&self.model);
                 self.model.with(|_, r| {
-                    r.insert(GameState::MainGame);
+                    r.insert(GameState::Opening);
                 });
                 true
             }
@@ -113,7 +386,7 @@ impl Component for StartGameBtn {
 
         html! {
             <div class="new-game-button" onclick=click_cb>
-                { "Start Game" }
+                { "Back to Menu" }
             </div>
         }
     }
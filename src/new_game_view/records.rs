@@ -0,0 +1,73 @@
+//! Bests and choices worth keeping from one game to the next, kept in local storage
+
+use yew::{
+    format::{Json, Text},
+    services::{storage::Area, StorageService},
+};
+
+use crate::resources::LEVELS;
+
+/// Not the old "radishes.endless.best", which held the last wave launched rather than survived
+const ENDLESS_BEST_STORAGE_KEY: &str = "radishes.endless.survived";
+
+/// The most waves survived in an endless game, if one has survived any (and it can be read)
+pub(super) fn load_endless_best() -> Option<usize> {
+    StorageService::new(Area::Local)
+        .ok()
+        .and_then(|storage| storage.restore::<Text>(ENDLESS_BEST_STORAGE_KEY).ok())
+        .and_then(|text| serde_json::from_str::<usize>(&text).ok())
+}
+
+/// Keeps the number of waves survived as the best, if it is; says whether it was. Surviving none
+/// is never a best.
+pub(super) fn record_endless_waves(survived: usize) -> bool {
+    if survived == 0 || load_endless_best().is_some_and(|best| best >= survived) {
+        return false;
+    }
+
+    if let Ok(mut storage) = StorageService::new(Area::Local) {
+        storage.store(ENDLESS_BEST_STORAGE_KEY, Json(&survived));
+    }
+    true
+}
+
+const LEVELS_UNLOCKED_STORAGE_KEY: &str = "radishes.campaign.unlocked";
+
+/// How many of the campaign's levels can be played, counting from the first, which always can
+pub(super) fn load_levels_unlocked() -> usize {
+    StorageService::new(Area::Local)
+        .ok()
+        .and_then(|storage| storage.restore::<Text>(LEVELS_UNLOCKED_STORAGE_KEY).ok())
+        .and_then(|text| serde_json::from_str::<usize>(&text).ok())
+        .unwrap_or(1)
+        .clamp(1, LEVELS.len())
+}
+
+/// Unlocks the level after the one won, if there is one and it isn't already
+pub(super) fn record_level_won(level: usize) {
+    let unlocked = (level + 2).min(LEVELS.len());
+    if unlocked <= load_levels_unlocked() {
+        return;
+    }
+
+    if let Ok(mut storage) = StorageService::new(Area::Local) {
+        storage.store(LEVELS_UNLOCKED_STORAGE_KEY, Json(&unlocked));
+    }
+}
+
+const MOBS_BREAK_WALLS_STORAGE_KEY: &str = "radishes.mobs_break_walls";
+
+/// Whether classic and endless games let the player wall the cores off, for mobs to break through
+pub(super) fn load_mobs_break_walls() -> bool {
+    StorageService::new(Area::Local)
+        .ok()
+        .and_then(|storage| storage.restore::<Text>(MOBS_BREAK_WALLS_STORAGE_KEY).ok())
+        .and_then(|text| serde_json::from_str::<bool>(&text).ok())
+        .unwrap_or(false)
+}
+
+pub(super) fn store_mobs_break_walls(mobs_break_walls: bool) {
+    if let Ok(mut storage) = StorageService::new(Area::Local) {
+        storage.store(MOBS_BREAK_WALLS_STORAGE_KEY, Json(&mobs_break_walls));
+    }
+}
@@ -0,0 +1,272 @@
+//! How the game went, once it's over: the headline numbers, a chart or two per wave, and where the
+//! damage and the resources came from and went.
+
+use std::collections::BTreeSet;
+
+use yew::prelude::*;
+
+use super::{EcsProps, NoMsg};
+use crate::{resources::*, ECS};
+
+const CHART_WIDTH: f64 = 480.;
+const CHART_HEIGHT: f64 = 160.;
+/// Room along the bottom for the wave numbers, and up the left for the scale
+const AXIS_MARGIN: f64 = 24.;
+/// Past this many waves, only every so many are labelled, so the numbers don't run together
+const MAX_WAVE_LABELS: usize = 20;
+
+const KILLED_COLOR: &str = "#40a040";
+const LEAKED_COLOR: &str = "#d04040";
+const REMAINING_COLOR: &str = "#a0a0a0";
+const DAMAGE_COLOR: &str = "#d0a020";
+
+pub(crate) struct RunSummary {
+    model: ECS,
+}
+
+impl Component for RunSummary {
+    type Message = NoMsg;
+    type Properties = EcsProps;
+
+    fn create(props: Self::Properties, _link: ComponentLink<Self>) -> Self {
+        RunSummary { model: props.ecs }
+    }
+
+    fn update(&mut self, msg: Self::Message) -> bool {
+        match msg {}
+    }
+
+    fn change(&mut self, props: Self::Properties) -> bool {
+        self.model = props.ecs;
+        true
+    }
+
+    fn view(&self) -> Html {
+        let stats = self.model.with(|_, r| r.get_or_default::<RunStats>().clone());
+
+        let headline = format!(
+            "Waves survived: {}. Mobs killed: {}. Mobs leaked: {}. Damage dealt: {}.",
+            stats.waves_survived(),
+            stats.total_killed(),
+            stats.total_leaked(),
+            stats.total_damage()
+        );
+
+        let mob_bars: Vec<Bar> = stats
+            .waves
+            .iter()
+            .map(|(wave_num, wave)| Bar {
+                wave_num: *wave_num,
+                segments: vec![
+                    (wave.killed as f64, KILLED_COLOR),
+                    (wave.leaked as f64, LEAKED_COLOR),
+                    (wave.spawned.saturating_sub(wave.killed + wave.leaked) as f64, REMAINING_COLOR),
+                ],
+                tooltip: format!(
+                    "Wave {}: {} killed, {} leaked, of {}",
+                    wave_num, wave.killed, wave.leaked, wave.spawned
+                ),
+            })
+            .collect();
+
+        let damage_bars: Vec<Bar> = stats
+            .waves
+            .iter()
+            .map(|(wave_num, wave)| Bar {
+                wave_num: *wave_num,
+                segments: vec![(wave.damage as f64, DAMAGE_COLOR)],
+                tooltip: format!("Wave {}: {} damage", wave_num, wave.damage),
+            })
+            .collect();
+
+        html! {
+            <div class="run-summary">
+                <p>{ headline }</p>
+                { chart("Mobs per wave (killed, leaked, still out)", &mob_bars) }
+                { chart("Damage dealt per wave", &damage_bars) }
+                { damage_table(&stats) }
+                { structure_table(&stats) }
+                { resources_table(&stats) }
+            </div>
+        }
+    }
+}
+
+/// One wave's column of a chart, stacked bottom to top
+struct Bar {
+    wave_num: usize,
+    segments: Vec<(f64, &'static str)>,
+    tooltip: String,
+}
+
+fn chart(title: &str, bars: &[Bar]) -> Html {
+    if bars.is_empty() {
+        return html! {
+            <div class="run-summary-chart">
+                <b>{ title }</b>
+                <div>{ "No waves were launched" }</div>
+            </div>
+        };
+    }
+
+    let max = bars
+        .iter()
+        .map(|bar| bar.segments.iter().map(|(value, _)| value).sum::<f64>())
+        .fold(0., f64::max)
+        .max(1.);
+
+    let plot_width = CHART_WIDTH - AXIS_MARGIN;
+    let plot_height = CHART_HEIGHT - AXIS_MARGIN;
+    let slot_width = plot_width / bars.len() as f64;
+    let bar_width = (slot_width * 0.8).max(1.);
+    let label_every = bars.len().div_ceil(MAX_WAVE_LABELS);
+
+    let columns: Html = bars
+        .iter()
+        .enumerate()
+        .map(|(idx, bar)| {
+            let x = AXIS_MARGIN + idx as f64 * slot_width + (slot_width - bar_width) / 2.;
+            let mut bottom = plot_height;
+
+            let segments: Html = bar
+                .segments
+                .iter()
+                .filter(|(value, _)| *value > 0.)
+                .map(|(value, color)| {
+                    let height = value / max * plot_height;
+                    bottom -= height;
+
+                    html! {
+                        <rect x=x.to_string() y=bottom.to_string() width=bar_width.to_string() height=height.to_string() fill=*color>
+                            <title>{ bar.tooltip.clone() }</title>
+                        </rect>
+                    }
+                })
+                .collect();
+
+            let label = if idx % label_every == 0 {
+                html! {
+                    <text x=(x + bar_width / 2.).to_string() y=(CHART_HEIGHT - 6.).to_string() text-anchor="middle" font-size="10">
+                        { bar.wave_num }
+                    </text>
+                }
+            } else {
+                html! {}
+            };
+
+            html! {
+                <g>
+                    { segments }
+                    { label }
+                </g>
+            }
+        })
+        .collect();
+
+    let view_box = format!("0 0 {} {}", CHART_WIDTH, CHART_HEIGHT);
+
+    html! {
+        <div class="run-summary-chart">
+            <b>{ title }</b>
+            <svg width=CHART_WIDTH.to_string() height=CHART_HEIGHT.to_string() viewBox=view_box>
+                <line x1=AXIS_MARGIN.to_string() y1="0" x2=AXIS_MARGIN.to_string() y2=plot_height.to_string() stroke="black" />
+                <line x1=AXIS_MARGIN.to_string() y1=plot_height.to_string() x2=CHART_WIDTH.to_string() y2=plot_height.to_string() stroke="black" />
+                <text x=(AXIS_MARGIN - 4.).to_string() y="10" text-anchor="end" font-size="10">{ max }</text>
+                <text x=(AXIS_MARGIN - 4.).to_string() y=plot_height.to_string() text-anchor="end" font-size="10">{ "0" }</text>
+                { columns }
+            </svg>
+        </div>
+    }
+}
+
+fn damage_table(stats: &RunStats) -> Html {
+    let sources: BTreeSet<DamageSource> = stats.damage_by_source.keys().chain(stats.kills_by_source.keys()).copied().collect();
+
+    if sources.is_empty() {
+        return html! {};
+    }
+
+    let rows: Html = sources
+        .into_iter()
+        .map(|source| {
+            let damage = stats.damage_by_source.get(&source).copied().unwrap_or(0);
+            let kills = stats.kills_by_source.get(&source).copied().unwrap_or(0);
+
+            html! {
+                <tr>
+                    <td>{ source.to_string() }</td>
+                    <td>{ damage }</td>
+                    <td>{ kills }</td>
+                </tr>
+            }
+        })
+        .collect();
+
+    html! {
+        <table class="run-summary-table">
+            <tr><th>{ "Source" }</th><th>{ "Damage" }</th><th>{ "Kills" }</th></tr>
+            { rows }
+        </table>
+    }
+}
+
+/// Each structure on its own, hardest hitting first
+fn structure_table(stats: &RunStats) -> Html {
+    if stats.by_structure.is_empty() {
+        return html! {};
+    }
+
+    let mut structures: Vec<_> = stats.by_structure.iter().collect();
+    structures.sort_by_key(|(_, structure)| std::cmp::Reverse(structure.damage));
+
+    let rows: Html = structures
+        .into_iter()
+        .map(|((kind, (x, y)), structure)| {
+            html! {
+                <tr>
+                    <td>{ format!("{:?} at ({}, {})", kind, x, y) }</td>
+                    <td>{ structure.damage }</td>
+                    <td>{ structure.kills }</td>
+                </tr>
+            }
+        })
+        .collect();
+
+    html! {
+        <table class="run-summary-table">
+            <tr><th>{ "Structure" }</th><th>{ "Damage" }</th><th>{ "Kills" }</th></tr>
+            { rows }
+        </table>
+    }
+}
+
+fn resources_table(stats: &RunStats) -> Html {
+    let categories: BTreeSet<ResourceCategory> = stats.earned.keys().chain(stats.spent.keys()).copied().collect();
+
+    if categories.is_empty() {
+        return html! {};
+    }
+
+    let rows: Html = categories
+        .into_iter()
+        .map(|category| {
+            let earned = stats.earned.get(&category).cloned().unwrap_or_default();
+            let spent = stats.spent.get(&category).cloned().unwrap_or_default();
+
+            html! {
+                <tr>
+                    <td>{ category.to_string() }</td>
+                    <td>{ earned.to_string() }</td>
+                    <td>{ spent.to_string() }</td>
+                </tr>
+            }
+        })
+        .collect();
+
+    html! {
+        <table class="run-summary-table">
+            <tr><th>{ "Resources" }</th><th>{ "Earned" }</th><th>{ "Spent" }</th></tr>
+            { rows }
+        </table>
+    }
+}
@@ -0,0 +1,474 @@
+use super::{
+    Affix, BlockingRule, MapRules, MobKind, MovementKind, Neighborhood, OwnedResource, OwnedResources, RunStats, StructureKind, Tile,
+};
+use crate::components::Spawn;
+
+/// How many tiles of wall are left around a level's layout, for the player to dig into
+pub const LEVEL_MARGIN: i32 = 4;
+
+/// One level of the campaign; they're played in order, each one unlocked by winning the last
+pub struct Level {
+    pub name: &'static str,
+    pub briefing: &'static str,
+    /// One string per row of tiles, top to bottom, starting from tile (0, 0); see `LayoutCell`
+    pub layout: &'static [&'static str],
+    pub blocking: BlockingRule,
+    /// How mobs get around, unless overridden for their movement kind
+    pub mob_neighbors: Neighborhood,
+    pub kind_neighbors: &'static [(MovementKind, Neighborhood)],
+    /// How quickly every spawn in the layout sends its mobs; what it sends is down to the waves
+    pub spawn: Spawn,
+    pub core_health: i32,
+    /// The level is over once the last of these has been dealt with
+    pub waves: &'static [ScriptedWave],
+    /// What the player can build (on open ground)
+    pub structures: &'static [StructureKind],
+    /// Which tile changes the player can make, from and to
+    pub transforms: &'static [(Tile, Tile)],
+    pub starting_resources: &'static [(OwnedResource, i64)],
+    pub victory: VictoryCondition,
+}
+
+/// What one wave of a level sends from each spawn
+pub struct ScriptedWave {
+    /// In the order they set off
+    pub mobs: &'static [(MobKind, usize)],
+    /// Every mob's health, as a percentage of what its kind usually has
+    pub health_percent: i32,
+    pub affixes: &'static [Affix],
+}
+
+/// What it takes to win a level, besides keeping the main core standing through all its waves
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum VictoryCondition {
+    SurviveAll,
+    /// Lost as soon as more than this many mobs reach a core
+    LeakAtMost(usize),
+    /// Lost as soon as more than this has been spent, on building and tile changes together. Only
+    /// the resources listed are limited; the rest can be spent freely.
+    UnderBudget(&'static [(OwnedResource, i64)]),
+}
+
+/// How a level was lost, other than the main core falling
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum LevelFailure {
+    TooManyLeaks,
+    OverBudget,
+}
+
+impl LevelFailure {
+    pub fn description(self) -> &'static str {
+        match self {
+            LevelFailure::TooManyLeaks => "Too many mobs got through.",
+            LevelFailure::OverBudget => "You went over budget.",
+        }
+    }
+}
+
+/// What a character of a level's layout stands for
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum LayoutCell {
+    Tile(Tile),
+    /// A spawn, on open ground
+    Spawn,
+    /// The main core, on open ground
+    MainCore,
+}
+
+impl LayoutCell {
+    pub fn from_char(c: char) -> Option<LayoutCell> {
+        match c {
+            '.' => Some(LayoutCell::Tile(Tile::Open)),
+            '#' => Some(LayoutCell::Tile(Tile::Wall)),
+            ',' => Some(LayoutCell::Tile(Tile::Mud)),
+            '=' => Some(LayoutCell::Tile(Tile::Road)),
+            '~' => Some(LayoutCell::Tile(Tile::Water)),
+            'S' => Some(LayoutCell::Spawn),
+            'C' => Some(LayoutCell::MainCore),
+            _ => None,
+        }
+    }
+
+    pub fn tile(self) -> Tile {
+        match self {
+            LayoutCell::Tile(tile) => tile,
+            LayoutCell::Spawn | LayoutCell::MainCore => Tile::Open,
+        }
+    }
+}
+
+impl Level {
+    /// Every cell of the layout, with its tile coordinates; characters which don't stand for
+    /// anything are skipped
+    pub fn cells(&self) -> impl Iterator<Item = ((i32, i32), LayoutCell)> + '_ {
+        self.layout.iter().enumerate().flat_map(|(y, row)| {
+            row.chars()
+                .enumerate()
+                .filter_map(move |(x, c)| LayoutCell::from_char(c).map(|cell| ((x as i32, y as i32), cell)))
+        })
+    }
+
+    pub fn map_rules(&self) -> MapRules {
+        MapRules {
+            blocking: self.blocking,
+            mob_neighbors: self.mob_neighbors,
+            kind_neighbors: self.kind_neighbors.iter().copied().collect(),
+            ..MapRules::default()
+        }
+    }
+
+    /// Width and height of the layout, in tiles
+    pub fn size(&self) -> (i32, i32) {
+        let width = self.layout.iter().map(|row| row.chars().count()).max().unwrap_or(0);
+        (width as i32, self.layout.len() as i32)
+    }
+
+    pub fn starting_resources(&self) -> OwnedResources {
+        to_owned_resources(self.starting_resources)
+    }
+
+    /// The scripted wave with the given number (counting from 1), if there is one
+    pub fn wave(&self, wave_num: usize) -> Option<&ScriptedWave> {
+        wave_num.checked_sub(1).and_then(|idx| self.waves.get(idx))
+    }
+
+    /// e.g. "Survive 5 waves, letting at most 2 mobs through"
+    pub fn objective(&self) -> String {
+        let waves = self.waves.len();
+        match self.victory {
+            VictoryCondition::SurviveAll => format!("Survive {} waves", waves),
+            VictoryCondition::LeakAtMost(0) => format!("Survive {} waves without letting a single mob through", waves),
+            VictoryCondition::LeakAtMost(leaks) => format!("Survive {} waves, letting at most {} mobs through", waves, leaks),
+            VictoryCondition::UnderBudget(budget) => {
+                format!("Survive {} waves, spending no more than {}", waves, to_owned_resources(budget))
+            }
+        }
+    }
+
+    /// How many of the level's waves have been launched, and every mob in them killed or leaked
+    pub fn waves_over(&self, stats: &RunStats) -> usize {
+        (1..=self.waves.len())
+            .filter(|wave_num| stats.waves.get(wave_num).is_some_and(|wave| wave.is_over()))
+            .count()
+    }
+
+    pub fn all_waves_over(&self, stats: &RunStats) -> bool {
+        self.waves_over(stats) == self.waves.len()
+    }
+
+    /// How the level is going, a line at a time, e.g. "Waves over: 2 of 5"
+    pub fn progress(&self, stats: &RunStats) -> Vec<String> {
+        let mut lines = vec![format!("Waves over: {} of {}", self.waves_over(stats), self.waves.len())];
+
+        match self.victory {
+            VictoryCondition::SurviveAll => {}
+            VictoryCondition::LeakAtMost(leaks) => lines.push(format!("Leaked: {} of at most {}", stats.total_leaked(), leaks)),
+            VictoryCondition::UnderBudget(budget) => lines.push(format!(
+                "Spent: {} of at most {}",
+                spent_from(budget, stats),
+                to_owned_resources(budget)
+            )),
+        }
+
+        lines
+    }
+
+    /// How the level has been lost, if it has been, other than by the main core falling
+    pub fn failure(&self, stats: &RunStats) -> Option<LevelFailure> {
+        match self.victory {
+            VictoryCondition::SurviveAll => None,
+            VictoryCondition::LeakAtMost(leaks) => (stats.total_leaked() > leaks).then_some(LevelFailure::TooManyLeaks),
+            VictoryCondition::UnderBudget(budget) => {
+                (!to_owned_resources(budget).can_pay(&spent_from(budget, stats))).then_some(LevelFailure::OverBudget)
+            }
+        }
+    }
+}
+
+/// What's been spent of the resources the budget limits, leaving out the rest
+fn spent_from(budget: &[(OwnedResource, i64)], stats: &RunStats) -> OwnedResources {
+    let spent = stats.total_spent();
+    budget.iter().fold(OwnedResources::new(), |resources, &(kind, _)| {
+        resources.with(kind, spent.0.get(&kind).copied().unwrap_or(0))
+    })
+}
+
+fn to_owned_resources(amounts: &[(OwnedResource, i64)]) -> OwnedResources {
+    amounts
+        .iter()
+        .fold(OwnedResources::new(), |resources, &(kind, amount)| resources.with(kind, amount))
+}
+
+const GAS_TRAPS_ONLY: &[StructureKind] = &[StructureKind::GasTrap];
+
+const WALLS_ONLY: &[(Tile, Tile)] = &[(Tile::Open, Tile::Wall), (Tile::Wall, Tile::Open)];
+
+const WALLS_AND_MUD: &[(Tile, Tile)] = &[
+    (Tile::Open, Tile::Wall),
+    (Tile::Wall, Tile::Open),
+    (Tile::Open, Tile::Mud),
+    (Tile::Mud, Tile::Open),
+];
+
+const EVERY_TRANSFORM: &[(Tile, Tile)] = &[
+    (Tile::Open, Tile::Wall),
+    (Tile::Wall, Tile::Open),
+    (Tile::Open, Tile::Mud),
+    (Tile::Mud, Tile::Open),
+    (Tile::Open, Tile::Road),
+    (Tile::Road, Tile::Open),
+];
+
+const fn wave(mobs: &'static [(MobKind, usize)], health_percent: i32) -> ScriptedWave {
+    ScriptedWave {
+        mobs,
+        health_percent,
+        affixes: &[],
+    }
+}
+
+pub const LEVELS: &[Level] = &[
+    Level {
+        name: "First Furrow",
+        briefing: "One spawn, one core, and one long way between them. Gas traps along the path should do it.",
+        layout: &["S..........", "##########.", "...........", ".##########", "..........C"],
+        blocking: BlockingRule::NeverBlock,
+        mob_neighbors: Neighborhood::FourWay,
+        kind_neighbors: &[],
+        spawn: Spawn {
+            first_wave: 0,
+            wave_interval: 1,
+            mobs_per_wave: 0,
+            ticks_per_mob: 40,
+        },
+        core_health: 10,
+        waves: &[
+            wave(&[(MobKind::Walker, 3)], 100),
+            wave(&[(MobKind::Walker, 5)], 100),
+            wave(&[(MobKind::Walker, 6)], 120),
+        ],
+        structures: GAS_TRAPS_ONLY,
+        transforms: WALLS_ONLY,
+        starting_resources: &[(OwnedResource::Money, 50), (OwnedResource::Wood, 20)],
+        victory: VictoryCondition::SurviveAll,
+    },
+    Level {
+        name: "Wet Feet",
+        briefing: "Swimmers can take the water straight across, diagonals and all, and you can't stop them trying. Don't let more than two through.",
+        layout: &["S....~~~~~~~....C", ".###############.", ".###############.", "S......,,,,,....."],
+        blocking: BlockingRule::NeverBlock,
+        mob_neighbors: Neighborhood::FourWay,
+        kind_neighbors: &[(MovementKind::Swimmer, Neighborhood::EightWay)],
+        spawn: Spawn {
+            first_wave: 0,
+            wave_interval: 1,
+            mobs_per_wave: 0,
+            ticks_per_mob: 40,
+        },
+        core_health: 20,
+        waves: &[
+            wave(&[(MobKind::Walker, 3), (MobKind::Swimmer, 1)], 100),
+            wave(&[(MobKind::Walker, 4), (MobKind::Swimmer, 1)], 100),
+            wave(&[(MobKind::Swimmer, 3)], 110),
+            wave(&[(MobKind::Walker, 4), (MobKind::Swimmer, 2)], 120),
+            wave(&[(MobKind::Walker, 5), (MobKind::Swimmer, 3)], 130),
+        ],
+        structures: GAS_TRAPS_ONLY,
+        transforms: WALLS_AND_MUD,
+        starting_resources: &[(OwnedResource::Money, 60), (OwnedResource::Wood, 30)],
+        victory: VictoryCondition::LeakAtMost(2),
+    },
+    Level {
+        name: "Lean Harvest",
+        briefing: "There's money to spare, but not to spend. Make every trap count.",
+        layout: &[
+            "S...............",
+            "###############.",
+            "................",
+            ".###############",
+            "...............C",
+        ],
+        blocking: BlockingRule::NeverBlock,
+        mob_neighbors: Neighborhood::FourWay,
+        kind_neighbors: &[],
+        spawn: Spawn {
+            first_wave: 0,
+            wave_interval: 1,
+            mobs_per_wave: 0,
+            ticks_per_mob: 30,
+        },
+        core_health: 15,
+        waves: &[
+            wave(&[(MobKind::Walker, 4)], 100),
+            wave(&[(MobKind::Walker, 5)], 110),
+            wave(&[(MobKind::Walker, 6)], 120),
+            wave(&[(MobKind::Walker, 6)], 140),
+            wave(&[(MobKind::Walker, 8)], 150),
+        ],
+        structures: GAS_TRAPS_ONLY,
+        transforms: EVERY_TRANSFORM,
+        starting_resources: &[(OwnedResource::Money, 150), (OwnedResource::Wood, 60)],
+        victory: VictoryCondition::UnderBudget(&[(OwnedResource::Money, 60)]),
+    },
+    Level {
+        name: "The Big One",
+        briefing: "Mobs here move diagonally, will break through walls if it's quicker, and the last wave brings something big.",
+        layout: &[
+            "S.............",
+            "=============.",
+            "..............",
+            ".#############",
+            ".............C",
+        ],
+        blocking: BlockingRule::MobsBreakWalls,
+        mob_neighbors: Neighborhood::EightWay,
+        kind_neighbors: &[],
+        spawn: Spawn {
+            first_wave: 0,
+            wave_interval: 1,
+            mobs_per_wave: 0,
+            ticks_per_mob: 30,
+        },
+        core_health: 25,
+        waves: &[
+            wave(&[(MobKind::Walker, 5)], 100),
+            wave(&[(MobKind::Walker, 5), (MobKind::Swimmer, 2)], 120),
+            wave(&[(MobKind::Walker, 8)], 140),
+            ScriptedWave {
+                mobs: &[(MobKind::Walker, 6)],
+                health_percent: 150,
+                affixes: &[Affix::Fast],
+            },
+            wave(&[(MobKind::Walker, 8), (MobKind::Swimmer, 3)], 170),
+            ScriptedWave {
+                mobs: &[(MobKind::Walker, 8)],
+                health_percent: 180,
+                affixes: &[Affix::Armored],
+            },
+            wave(&[(MobKind::Walker, 10), (MobKind::Swimmer, 4)], 200),
+            ScriptedWave {
+                mobs: &[(MobKind::Walker, 6), (MobKind::Boss, 1)],
+                health_percent: 200,
+                affixes: &[Affix::Regenerating],
+            },
+        ],
+        structures: GAS_TRAPS_ONLY,
+        transforms: EVERY_TRANSFORM,
+        starting_resources: &[(OwnedResource::Money, 80), (OwnedResource::Wood, 50)],
+        victory: VictoryCondition::SurviveAll,
+    },
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resources::{ResourceCategory, ALL_RESOURCES};
+
+    /// Stats where the first so many of the level's waves have each sent one mob, which was killed
+    fn stats_with_waves_over(waves: usize) -> RunStats {
+        let mut stats = RunStats::default();
+        for wave_num in 1..=waves {
+            let wave = stats.wave_mut(wave_num);
+            wave.spawned = 1;
+            wave.killed = 1;
+        }
+        stats
+    }
+
+    fn spend(stats: &mut RunStats, amounts: &OwnedResources) {
+        stats.add_spent(ResourceCategory::TileChanges, amounts);
+    }
+
+    #[test]
+    fn objective_counts_every_wave() {
+        for level in LEVELS {
+            let objective = level.objective();
+            assert!(
+                objective.starts_with(&format!("Survive {} waves", level.waves.len())),
+                "{}: {}",
+                level.name,
+                objective
+            );
+
+            if let VictoryCondition::UnderBudget(budget) = level.victory {
+                assert!(
+                    objective.ends_with(&to_owned_resources(budget).to_string()),
+                    "{}: {}",
+                    level.name,
+                    objective
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn all_waves_over_only_once_the_last_is() {
+        for level in LEVELS {
+            let waves = level.waves.len();
+            assert!(!level.all_waves_over(&RunStats::default()), "{}", level.name);
+            assert!(!level.all_waves_over(&stats_with_waves_over(waves - 1)), "{}", level.name);
+            assert!(level.all_waves_over(&stats_with_waves_over(waves)), "{}", level.name);
+
+            // a wave with a mob still out isn't over
+            let mut stats = stats_with_waves_over(waves);
+            stats.wave_mut(waves).spawned += 1;
+            assert!(!level.all_waves_over(&stats), "{}", level.name);
+        }
+    }
+
+    #[test]
+    fn not_failed_at_the_start() {
+        for level in LEVELS {
+            assert_eq!(level.failure(&RunStats::default()), None, "{}", level.name);
+        }
+    }
+
+    #[test]
+    fn failure_follows_the_victory_condition() {
+        for level in LEVELS {
+            let mut stats = stats_with_waves_over(level.waves.len());
+
+            match level.victory {
+                VictoryCondition::SurviveAll => {
+                    stats.wave_mut(1).leaked += 100;
+                    spend(&mut stats, &level.starting_resources());
+                    assert_eq!(level.failure(&stats), None, "{}", level.name);
+                }
+                VictoryCondition::LeakAtMost(leaks) => {
+                    stats.wave_mut(1).leaked = leaks;
+                    assert_eq!(level.failure(&stats), None, "{}", level.name);
+                    stats.wave_mut(1).leaked += 1;
+                    assert_eq!(level.failure(&stats), Some(LevelFailure::TooManyLeaks), "{}", level.name);
+                }
+                VictoryCondition::UnderBudget(budget) => {
+                    spend(&mut stats, &to_owned_resources(budget));
+                    assert_eq!(level.failure(&stats), None, "{}", level.name);
+
+                    for &(kind, _) in budget {
+                        let mut over = stats.clone();
+                        spend(&mut over, &OwnedResources::new().with(kind, 1));
+                        assert_eq!(level.failure(&over), Some(LevelFailure::OverBudget), "{}", level.name);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Anything the budget doesn't list, like the Wood a gas trap or a wall needs, can be spent
+    /// without failing the level
+    #[test]
+    fn budget_only_limits_what_it_lists() {
+        for level in LEVELS {
+            if let VictoryCondition::UnderBudget(budget) = level.victory {
+                let mut stats = RunStats::default();
+                for &kind in ALL_RESOURCES {
+                    if budget.iter().all(|&(limited, _)| limited != kind) {
+                        spend(&mut stats, &OwnedResources::new().with(kind, 1000));
+                    }
+                }
+
+                assert_eq!(level.failure(&stats), None, "{}", level.name);
+            }
+        }
+    }
+}
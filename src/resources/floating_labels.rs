@@ -0,0 +1,105 @@
+use legion::Entity;
+
+use super::OwnedResource;
+
+/// How long a label stays up, in ticks; it fades out over the whole time
+const LABEL_TICKS: u32 = 20;
+/// Damage to the same target within this many ticks of a label appearing is added to it, so a
+/// mob choking on gas shows one climbing number rather than a stream of ones
+const MERGE_TICKS: u32 = 10;
+/// Pixels per tick that labels drift upward
+const RISE_SPEED: f64 = 0.8;
+/// Most labels up at once; past this, the oldest are dropped
+const MAX_LABELS: usize = 200;
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum LabelKind {
+    Damage,
+    Reward(OwnedResource),
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct FloatingLabel {
+    /// Position in world pixels
+    pub x: f64,
+    pub y: f64,
+    pub kind: LabelKind,
+    pub amount: i64,
+    age: u32,
+    // who took the damage, for merging
+    target: Option<Entity>,
+}
+
+impl FloatingLabel {
+    pub fn text(&self) -> String {
+        match self.kind {
+            LabelKind::Damage => format!("-{}", self.amount),
+            LabelKind::Reward(resource) => format!("+{} {}", self.amount, resource),
+        }
+    }
+
+    /// From 1 (just appeared) down toward 0 (about to go)
+    pub fn opacity(&self) -> f64 {
+        1. - self.age as f64 / LABEL_TICKS as f64
+    }
+}
+
+/// Short-lived numbers drawn over the map, showing damage dealt and resources gained
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct FloatingLabels {
+    labels: Vec<FloatingLabel>,
+}
+
+impl FloatingLabels {
+    pub fn add_damage(&mut self, target: Entity, x: f64, y: f64, amount: i64) {
+        let recent = self
+            .labels
+            .iter_mut()
+            .find(|label| label.target == Some(target) && label.age < MERGE_TICKS);
+
+        match recent {
+            Some(label) => label.amount += amount,
+            None => self.add(FloatingLabel {
+                x,
+                y,
+                kind: LabelKind::Damage,
+                amount,
+                age: 0,
+                target: Some(target),
+            }),
+        }
+    }
+
+    pub fn add_reward(&mut self, x: f64, y: f64, resource: OwnedResource, amount: i64) {
+        self.add(FloatingLabel {
+            x,
+            y,
+            kind: LabelKind::Reward(resource),
+            amount,
+            age: 0,
+            target: None,
+        });
+    }
+
+    fn add(&mut self, label: FloatingLabel) {
+        if self.labels.len() >= MAX_LABELS {
+            self.labels.remove(0);
+        }
+
+        self.labels.push(label);
+    }
+
+    /// Float every label upward, and drop the ones which have faded out
+    pub fn tick(&mut self) {
+        for label in self.labels.iter_mut() {
+            label.age += 1;
+            label.y -= RISE_SPEED;
+        }
+
+        self.labels.retain(|label| label.age < LABEL_TICKS);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &FloatingLabel> + '_ {
+        self.labels.iter()
+    }
+}
@@ -0,0 +1,273 @@
+use std::ops::Range;
+
+/// Side length of the square chunks a `Grid` allocates in
+const CHUNK_SIZE: i32 = 16;
+const CHUNK_AREA: usize = (CHUNK_SIZE * CHUNK_SIZE) as usize;
+
+/// The rectangle of tiles which exist at all. Everything outside it is permanently the default.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct WorldBounds {
+    /// Leftmost tile (inclusive)
+    pub x_min: i32,
+    /// Topmost tile (inclusive)
+    pub y_min: i32,
+    /// Rightmost tile (exclusive)
+    pub x_max: i32,
+    /// Bottommost tile (exclusive)
+    pub y_max: i32,
+}
+
+impl WorldBounds {
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        self.x_min <= x && x < self.x_max && self.y_min <= y && y < self.y_max
+    }
+
+    pub fn width(&self) -> i32 {
+        (self.x_max - self.x_min).max(0)
+    }
+
+    pub fn height(&self) -> i32 {
+        (self.y_max - self.y_min).max(0)
+    }
+}
+
+/// Dense storage for a value per tile, over a fixed world rectangle. Memory is only allocated
+/// for chunks which have been written to; every unwritten tile (and every tile outside the
+/// bounds) reads as the default.
+///
+/// Iteration always goes chunk by chunk (rows of chunks, top to bottom), then row by row within
+/// each chunk, so it is the same on every run.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Grid<T: Copy> {
+    bounds: WorldBounds,
+    default: T,
+    chunks_wide: i32,
+    chunks: Vec<Option<Box<[T]>>>,
+}
+
+impl<T: Copy + PartialEq> Grid<T> {
+    pub fn new(bounds: WorldBounds, default: T) -> Self {
+        let chunks_wide = div_round_up(bounds.width(), CHUNK_SIZE);
+        let chunks_tall = div_round_up(bounds.height(), CHUNK_SIZE);
+
+        Grid {
+            bounds,
+            default,
+            chunks_wide,
+            chunks: vec![None; (chunks_wide * chunks_tall) as usize],
+        }
+    }
+
+    pub fn bounds(&self) -> WorldBounds {
+        self.bounds
+    }
+
+    pub fn get(&self, x: i32, y: i32) -> T {
+        match self.index_of(x, y) {
+            Some((chunk, cell)) => self.chunks[chunk].as_ref().map(|c| c[cell]).unwrap_or(self.default),
+            None => self.default,
+        }
+    }
+
+    /// Writes outside the bounds are dropped, since reads there will always give the default.
+    pub fn set(&mut self, x: i32, y: i32, value: T) {
+        if let Some(cell) = self.get_mut(x, y) {
+            *cell = value;
+        }
+    }
+
+    /// Mutable access to the given tile, allocating its chunk if needed. None if out of bounds.
+    pub fn get_mut(&mut self, x: i32, y: i32) -> Option<&mut T> {
+        let (chunk, cell) = self.index_of(x, y)?;
+        let default = self.default;
+
+        let chunk = self.chunks[chunk].get_or_insert_with(|| vec![default; CHUNK_AREA].into_boxed_slice());
+
+        Some(&mut chunk[cell])
+    }
+
+    pub fn values_mut(&mut self) -> impl Iterator<Item = &mut T> + '_ {
+        self.chunks.iter_mut().flatten().flat_map(|chunk| chunk.iter_mut())
+    }
+
+    /// Chunks are indexed 0 .. num_chunks, in iteration order
+    pub fn num_chunks(&self) -> usize {
+        self.chunks.len()
+    }
+
+    /// Every in-bounds tile of the given chunk, with its value, if that chunk is allocated
+    pub fn iter_chunk(&self, chunk: usize) -> impl Iterator<Item = ((i32, i32), T)> + '_ {
+        let cells = self.chunks[chunk].as_ref();
+        let (x_origin, y_origin) = self.chunk_origin(chunk);
+        let bounds = self.bounds;
+
+        cells
+            .into_iter()
+            .flat_map(|cells| cells.iter().copied().enumerate())
+            .map(move |(cell, value)| {
+                let x = x_origin + cell as i32 % CHUNK_SIZE;
+                let y = y_origin + cell as i32 / CHUNK_SIZE;
+                ((x, y), value)
+            })
+            .filter(move |((x, y), _)| bounds.contains(*x, *y))
+    }
+
+    /// The tiles covered by each row of chunks, top to bottom, as `y_start..y_end`
+    pub fn chunk_rows(&self) -> impl Iterator<Item = Range<i32>> {
+        let bounds = self.bounds;
+
+        (bounds.y_min..bounds.y_max)
+            .step_by(CHUNK_SIZE as usize)
+            .map(move |y_start| y_start..(y_start + CHUNK_SIZE).min(bounds.y_max))
+    }
+
+    /// Whether the chunk holding the given tile is allocated; if not, the whole chunk is default
+    pub fn is_allocated(&self, x: i32, y: i32) -> bool {
+        match self.index_of(x, y) {
+            Some((chunk, _)) => self.chunks[chunk].is_some(),
+            None => false,
+        }
+    }
+
+    /// Free any chunks which have gone back to holding nothing but the default
+    pub fn release_empty_chunks(&mut self) {
+        let default = self.default;
+
+        for chunk in self.chunks.iter_mut() {
+            if chunk.as_ref().map(|c| c.iter().all(|v| *v == default)).unwrap_or(false) {
+                *chunk = None;
+            }
+        }
+    }
+
+    fn chunk_origin(&self, chunk: usize) -> (i32, i32) {
+        let chunk = chunk as i32;
+        (
+            self.bounds.x_min + (chunk % self.chunks_wide) * CHUNK_SIZE,
+            self.bounds.y_min + (chunk / self.chunks_wide) * CHUNK_SIZE,
+        )
+    }
+
+    fn index_of(&self, x: i32, y: i32) -> Option<(usize, usize)> {
+        if !self.bounds.contains(x, y) {
+            return None;
+        }
+
+        let dx = x - self.bounds.x_min;
+        let dy = y - self.bounds.y_min;
+
+        let chunk = (dy / CHUNK_SIZE) * self.chunks_wide + (dx / CHUNK_SIZE);
+        let cell = (dy % CHUNK_SIZE) * CHUNK_SIZE + (dx % CHUNK_SIZE);
+
+        Some((chunk as usize, cell as usize))
+    }
+}
+
+fn div_round_up(amt: i32, div: i32) -> i32 {
+    (amt + div - 1) / div
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // not a whole number of chunks either way, and either side of zero
+    const BOUNDS: WorldBounds = WorldBounds {
+        x_min: -20,
+        y_min: -17,
+        x_max: 13,
+        y_max: 30,
+    };
+
+    #[test]
+    fn reads_back_writes_across_chunk_boundaries() {
+        let mut grid = Grid::new(BOUNDS, 0);
+
+        for x in BOUNDS.x_min..BOUNDS.x_max {
+            for y in BOUNDS.y_min..BOUNDS.y_max {
+                grid.set(x, y, x * 1000 + y);
+            }
+        }
+
+        for x in BOUNDS.x_min..BOUNDS.x_max {
+            for y in BOUNDS.y_min..BOUNDS.y_max {
+                assert_eq!(grid.get(x, y), x * 1000 + y, "at ({}, {})", x, y);
+            }
+        }
+
+        // the tiles either side of each chunk edge are separate
+        for x in [-20, -5, -4, 11, 12].iter().copied() {
+            for y in [-17, -2, -1, 14, 15, 29].iter().copied() {
+                assert_eq!(grid.get(x, y), x * 1000 + y, "at ({}, {})", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn out_of_bounds_is_always_default() {
+        let mut grid = Grid::new(BOUNDS, 7);
+
+        for (x, y) in [(-21, 0), (13, 0), (0, -18), (0, 30), (-21, -18), (13, 30)].iter().copied() {
+            grid.set(x, y, 1);
+            assert_eq!(grid.get(x, y), 7);
+            assert!(grid.get_mut(x, y).is_none());
+            assert!(!grid.is_allocated(x, y));
+        }
+
+        assert_eq!(grid, Grid::new(BOUNDS, 7));
+    }
+
+    #[test]
+    fn iterates_chunks_in_bounds_only() {
+        let mut grid = Grid::new(BOUNDS, 0);
+        grid.set(12, 29, 1);
+        grid.set(-20, -17, 2);
+
+        assert_eq!(grid.num_chunks(), 3 * 3);
+
+        let values: Vec<((i32, i32), i32)> = (0..grid.num_chunks())
+            .flat_map(|chunk| grid.iter_chunk(chunk).collect::<Vec<_>>())
+            .filter(|(_, value)| *value != 0)
+            .collect();
+        assert_eq!(values, vec![((-20, -17), 2), ((12, 29), 1)]);
+
+        // the corner chunk pokes out past the bounds, so only has one column of 15 tiles
+        let last = grid.num_chunks() - 1;
+        assert_eq!(grid.iter_chunk(last).count(), 15);
+        assert_eq!(grid.iter_chunk(1).count(), 0);
+
+        let rows: Vec<Range<i32>> = grid.chunk_rows().collect();
+        assert_eq!(rows, vec![-17..-1, -1..15, 15..30]);
+    }
+
+    #[test]
+    fn releases_chunks_which_went_back_to_default() {
+        let mut grid = Grid::new(BOUNDS, 0);
+        grid.set(-5, -2, 3);
+        grid.set(-4, -1, 4);
+
+        assert!(grid.is_allocated(-5, -2));
+        assert!(grid.is_allocated(-4, -1));
+        assert!(!grid.is_allocated(-4, -2));
+
+        grid.set(-4, -1, 0);
+        grid.release_empty_chunks();
+
+        assert!(grid.is_allocated(-5, -2));
+        assert!(!grid.is_allocated(-4, -1));
+        assert_eq!(grid.get(-5, -2), 3);
+        assert_eq!(grid.get(-4, -1), 0);
+
+        for value in grid.values_mut() {
+            *value = 0;
+        }
+        grid.release_empty_chunks();
+
+        assert_eq!(grid, Grid::new(BOUNDS, 0));
+
+        // and they come back when written again
+        grid.set(-4, -1, 5);
+        assert!(grid.is_allocated(-4, -1));
+        assert_eq!(grid.get(-4, -1), 5);
+    }
+}
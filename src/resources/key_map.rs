@@ -0,0 +1,218 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+/// Something the player can ask for from the keyboard, apart from which key they used to do it
+#[derive(Serialize, Deserialize, Copy, Clone, Eq, PartialEq, Debug, Hash, Ord, PartialOrd)]
+pub enum InputAction {
+    PanUp,
+    PanDown,
+    PanLeft,
+    PanRight,
+    Unselect,
+    LaunchWave,
+    ToggleAutoLaunch,
+    TogglePause,
+    CycleSpeed,
+    /// Build another of whatever was built last, on the selected tile
+    BuildLast,
+    /// Sell the structure on the selected tile
+    Sell,
+    /// Select the tile of the next structure along
+    CycleSelection,
+    /// Take back the last tile change, build or sale
+    Undo,
+}
+
+pub const ALL_INPUT_ACTIONS: &[InputAction] = &[
+    InputAction::PanUp,
+    InputAction::PanDown,
+    InputAction::PanLeft,
+    InputAction::PanRight,
+    InputAction::Unselect,
+    InputAction::LaunchWave,
+    InputAction::ToggleAutoLaunch,
+    InputAction::TogglePause,
+    InputAction::CycleSpeed,
+    InputAction::BuildLast,
+    InputAction::Sell,
+    InputAction::CycleSelection,
+    InputAction::Undo,
+];
+
+impl InputAction {
+    pub fn description(self) -> &'static str {
+        match self {
+            InputAction::PanUp => "Pan up",
+            InputAction::PanDown => "Pan down",
+            InputAction::PanLeft => "Pan left",
+            InputAction::PanRight => "Pan right",
+            InputAction::Unselect => "Unselect",
+            InputAction::LaunchWave => "Launch wave",
+            InputAction::ToggleAutoLaunch => "Toggle auto-launch",
+            InputAction::TogglePause => "Pause / unpause",
+            InputAction::CycleSpeed => "Change speed",
+            InputAction::BuildLast => "Build last structure again",
+            InputAction::Sell => "Sell selected structure",
+            InputAction::CycleSelection => "Select next structure",
+            InputAction::Undo => "Undo",
+        }
+    }
+
+    /// Whether the action lasts as long as the key is down, rather than happening once per press
+    pub fn is_held(self) -> bool {
+        matches!(
+            self,
+            InputAction::PanUp | InputAction::PanDown | InputAction::PanLeft | InputAction::PanRight
+        )
+    }
+
+    fn default_keys(self) -> &'static [&'static str] {
+        match self {
+            InputAction::PanUp => &["KeyW", "ArrowUp"],
+            InputAction::PanDown => &["KeyS", "ArrowDown"],
+            InputAction::PanLeft => &["KeyA", "ArrowLeft"],
+            InputAction::PanRight => &["KeyD", "ArrowRight"],
+            InputAction::Unselect => &["Escape"],
+            InputAction::LaunchWave => &["Space"],
+            InputAction::ToggleAutoLaunch => &["KeyL"],
+            InputAction::TogglePause => &["KeyP"],
+            InputAction::CycleSpeed => &["KeyF"],
+            InputAction::BuildLast => &["KeyB"],
+            InputAction::Sell => &["KeyX"],
+            InputAction::CycleSelection => &["Tab"],
+            InputAction::Undo => &["KeyZ"],
+        }
+    }
+}
+
+/// Which action each key (as a `KeyboardEvent.code`, so it doesn't depend on the keyboard layout)
+/// triggers. A key triggers at most one action, but an action can have any number of keys.
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Debug)]
+pub struct KeyMap {
+    keys: BTreeMap<String, InputAction>,
+}
+
+impl Default for KeyMap {
+    fn default() -> Self {
+        let mut key_map = KeyMap { keys: BTreeMap::new() };
+        key_map.fill_defaults();
+        key_map
+    }
+}
+
+impl KeyMap {
+    pub fn action_for(&self, code: &str) -> Option<InputAction> {
+        self.keys.get(code).copied()
+    }
+
+    /// Every key bound to the action, in a steady order
+    pub fn keys_for(&self, action: InputAction) -> impl Iterator<Item = &str> + '_ {
+        self.keys
+            .iter()
+            .filter(move |(_, bound)| **bound == action)
+            .map(|(code, _)| code.as_str())
+    }
+
+    /// Bind the key to the action, taking it away from whatever it did before
+    pub fn bind(&mut self, code: String, action: InputAction) {
+        self.keys.insert(code, action);
+    }
+
+    pub fn unbind(&mut self, code: &str) {
+        self.keys.remove(code);
+    }
+
+    /// Give every action without a key its default ones, where they're still free; for key maps
+    /// saved before the action existed
+    pub fn fill_defaults(&mut self) {
+        for action in ALL_INPUT_ACTIONS.iter().copied() {
+            if self.keys_for(action).next().is_some() {
+                continue;
+            }
+
+            for code in action.default_keys() {
+                self.keys.entry(code.to_string()).or_insert(action);
+            }
+        }
+    }
+}
+
+/// Actions the player is holding a key down for right now
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct HeldActions {
+    // one entry per key, so letting go of one of two keys for the same action doesn't end it
+    held: BTreeMap<String, InputAction>,
+}
+
+impl HeldActions {
+    pub fn press(&mut self, code: String, action: InputAction) {
+        self.held.insert(code, action);
+    }
+
+    pub fn release(&mut self, code: &str) {
+        self.held.remove(code);
+    }
+
+    pub fn release_all(&mut self) {
+        self.held.clear();
+    }
+
+    pub fn is_held(&self, action: InputAction) -> bool {
+        self.held.values().any(|held| *held == action)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(key_map: &KeyMap) -> KeyMap {
+        let text = serde_json::to_string(key_map).expect("Key maps should serialize");
+        serde_json::from_str(&text).expect("Key maps should deserialize")
+    }
+
+    #[test]
+    fn key_maps_survive_saving_and_loading() {
+        let mut key_map = KeyMap::default();
+        key_map.bind("KeyQ".to_string(), InputAction::Undo);
+        key_map.bind("KeyW".to_string(), InputAction::Sell);
+        key_map.unbind("Space");
+
+        let loaded = round_trip(&key_map);
+
+        assert_eq!(loaded, key_map);
+        assert_eq!(loaded.action_for("KeyW"), Some(InputAction::Sell));
+        assert_eq!(loaded.action_for("Space"), None);
+        assert_eq!(loaded.keys_for(InputAction::Undo).collect::<Vec<_>>(), vec!["KeyQ", "KeyZ"]);
+    }
+
+    #[test]
+    fn actions_newer_than_the_saved_map_get_their_defaults() {
+        // as saved before there was an undo key
+        let text = r#"{"keys":{"KeyW":"PanUp","KeyS":"PanDown","Escape":"Unselect"}}"#;
+        let mut key_map: KeyMap = serde_json::from_str(text).expect("Old key maps should still load");
+        assert_eq!(key_map.keys_for(InputAction::Undo).next(), None);
+
+        key_map.fill_defaults();
+
+        assert_eq!(key_map.action_for("KeyZ"), Some(InputAction::Undo));
+        for action in ALL_INPUT_ACTIONS.iter().copied() {
+            assert!(key_map.keys_for(action).next().is_some(), "{:?} has no key", action);
+        }
+    }
+
+    #[test]
+    fn defaults_never_take_a_key_the_player_bound() {
+        let mut key_map = KeyMap::default();
+        key_map.bind("KeyZ".to_string(), InputAction::Sell);
+
+        let mut loaded = round_trip(&key_map);
+        loaded.fill_defaults();
+
+        assert_eq!(loaded.action_for("KeyZ"), Some(InputAction::Sell));
+        assert_eq!(loaded.keys_for(InputAction::Undo).next(), None);
+        // the key it had already is kept alongside
+        assert_eq!(loaded.keys_for(InputAction::Sell).collect::<Vec<_>>(), vec!["KeyX", "KeyZ"]);
+    }
+}
@@ -1,20 +1,150 @@
 use std::{
     cmp::Ordering,
-    collections::{BinaryHeap, HashMap},
+    collections::{BTreeMap, BTreeSet, BinaryHeap, HashMap, HashSet},
+    ops::Range,
 };
 
 use serde::Deserialize;
 
+use super::grid::{Grid, WorldBounds};
+
 #[derive(Deserialize, Copy, Clone, Eq, PartialEq, Debug, Hash)]
 pub enum Tile {
     Open,
     Wall,
-    Spawn,
-    Core,
+    /// Passable, but everything slogs through it
+    Mud,
+    /// Passable, and faster than open ground
+    Road,
+    /// Only passable for mobs which can swim
+    Water,
 }
 
 const DEFAULT_TILE: Tile = Tile::Wall;
 
+/// How much damage a wall takes before it crumbles (mobs deal about one a tick)
+pub const WALL_HEALTH: i32 = 120;
+
+/// Path cost of half of a wall tile, when mobs are allowed to break walls. Crossing a wall costs
+/// roughly what crossing an open tile does plus the time spent knocking it down (an open tile is
+/// 20, and takes a mob about 16 ticks to cross)
+const BREAKABLE_WALL_COST: i32 = 75;
+
+/// What the player is allowed to do to the paths between spawns and cores
+#[derive(Deserialize, Copy, Clone, Eq, PartialEq, Debug)]
+pub enum BlockingRule {
+    /// Paths can be lengthened but never cut; tile changes that would cut one are refused
+    NeverBlock,
+    /// Paths may be cut entirely; mobs then path through walls and break them down to get by
+    MobsBreakWalls,
+}
+
+/// Cost of a diagonal step, in tenths of the straight step between the same two tiles
+const DIAGONAL_COST_TENTHS: i32 = 14;
+
+/// Which tiles count as next to each other, for mobs finding their way and for gas spreading
+#[derive(Deserialize, Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Neighborhood {
+    /// Only the directly adjacent tiles
+    FourWay,
+    /// The diagonals as well; a diagonal step is never allowed past the corner of a wall
+    EightWay,
+}
+
+impl Neighborhood {
+    /// The neighbors of the given tile which are inside the bounds, orthogonal ones first. Path
+    /// searches must stick to these, since past the edge is an endless stretch of (possibly
+    /// breakable) wall.
+    fn of(self, bounds: WorldBounds, pos: (i32, i32)) -> impl Iterator<Item = (i32, i32)> {
+        let (x, y) = pos;
+        let diagonals = match self {
+            Neighborhood::FourWay => &[][..],
+            Neighborhood::EightWay => &[(-1, -1), (1, -1), (-1, 1), (1, 1)][..],
+        };
+
+        IntoIterator::into_iter(neighbors(x, y))
+            .chain(diagonals.iter().map(move |(dx, dy)| (x + dx, y + dy)))
+            .filter(move |(x, y)| bounds.contains(*x, *y))
+    }
+}
+
+/// Everything about how a map behaves which is fixed when it's made
+#[derive(Deserialize, Clone, Eq, PartialEq, Debug)]
+pub struct MapRules {
+    pub blocking: BlockingRule,
+    /// How mobs get around, unless overridden for their movement kind below
+    pub mob_neighbors: Neighborhood,
+    /// Movement kinds which get around differently from the rest
+    #[serde(default)]
+    pub kind_neighbors: BTreeMap<MovementKind, Neighborhood>,
+    pub gas_neighbors: Neighborhood,
+}
+
+impl Default for MapRules {
+    fn default() -> Self {
+        MapRules {
+            blocking: BlockingRule::NeverBlock,
+            mob_neighbors: Neighborhood::FourWay,
+            kind_neighbors: BTreeMap::new(),
+            gas_neighbors: Neighborhood::FourWay,
+        }
+    }
+}
+
+impl MapRules {
+    pub fn neighbors_for(&self, kind: MovementKind) -> Neighborhood {
+        self.kind_neighbors.get(&kind).copied().unwrap_or(self.mob_neighbors)
+    }
+}
+
+/// How a mob gets around, which decides which tiles it can cross and how quickly
+#[derive(Deserialize, Copy, Clone, Eq, PartialEq, Debug, Hash, Ord, PartialOrd)]
+pub enum MovementKind {
+    Walker,
+    Swimmer,
+}
+
+pub const ALL_TILES: &[Tile] = &[Tile::Open, Tile::Wall, Tile::Mud, Tile::Road, Tile::Water];
+
+pub const ALL_MOVEMENT_KINDS: &[MovementKind] = &[MovementKind::Walker, MovementKind::Swimmer];
+
+impl MovementKind {
+    /// How fast this kind of mob crosses the given tile, as a percentage of its usual speed;
+    /// None if it can't cross it at all
+    pub fn speed_percent(self, tile: Tile) -> Option<i32> {
+        match tile {
+            Tile::Open => Some(100),
+            Tile::Wall => None,
+            Tile::Mud => Some(50),
+            Tile::Road => Some(150),
+            Tile::Water => match self {
+                MovementKind::Walker => None,
+                MovementKind::Swimmer => Some(50),
+            },
+        }
+    }
+
+    /// Path cost of crossing half of the given tile (so a step between two tiles costs the sum
+    /// of both), in inverse proportion to the speed across it; an open tile costs 10
+    fn tile_cost(self, tile: Tile, blocking: BlockingRule) -> Option<i32> {
+        match (tile, blocking) {
+            (Tile::Wall, BlockingRule::MobsBreakWalls) => Some(BREAKABLE_WALL_COST),
+            _ => self.speed_percent(tile).map(|percent| 1000 / percent),
+        }
+    }
+}
+
+/// Where a mob is trying to get to
+#[derive(Deserialize, Copy, Clone, Eq, PartialEq, Debug, Hash, Ord, PartialOrd)]
+pub enum PathTarget {
+    /// Whichever core is cheapest to reach
+    NearestCore,
+    /// One particular core, by tile position; if it's gone, this falls back to the nearest core
+    Core { x: i32, y: i32 },
+}
+
+/// Which tiles gas can spread onto. Cores and spawns sit on ordinary tiles, but gas is kept off
+/// them too; the map passes those along separately.
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
 struct GasFlow;
 
@@ -27,105 +157,400 @@ impl PassableChecker for GasFlow {
         match tile {
             Tile::Open => true,
             Tile::Wall => false,
-            Tile::Spawn => false,
-            Tile::Core => false,
+            Tile::Mud => true,
+            Tile::Road => true,
+            Tile::Water => true,
         }
     }
 }
 
 #[derive(Clone, Eq, PartialEq, Debug)]
 pub struct Map {
-    map: HashMap<(i32, i32), Tile>,
+    map: Grid<Tile>,
 
-    dijkstra_maps_dirty: bool,
-    core_paths: DijkstraMap,
+    // one set of paths per movement kind (since they disagree on what can be crossed) and per
+    // target; every core gets its own, on top of the shared "nearest core" paths
+    core_paths: BTreeMap<(MovementKind, PathTarget), DijkstraMap>,
+
+    // where the cores and spawns are, as last given by their entities
+    cores: BTreeSet<(i32, i32)>,
+    spawns: BTreeSet<(i32, i32)>,
 
     poison_gas_map: FlowMap<GasFlow>,
+
+    rules: MapRules,
+    // damage taken so far by each wall; reset whenever the tile changes
+    wall_damage: Grid<i32>,
+
+    // bumped whenever a tile, core or spawn changes, so anything worked out from the map can
+    // tell when it's out of date
+    revision: u64,
 }
 
+/// The rules a DijkstraMap is computed under
+#[derive(Copy, Clone)]
+struct PathRules<'a> {
+    /// Which tiles are the objective (cost zero)
+    is_goal: &'a dyn Fn((i32, i32)) -> bool,
+    /// Cost of crossing half of the tile, or None if it can't be entered at all
+    tile_cost: &'a dyn Fn(Tile) -> Option<i32>,
+    /// Which tiles can be stepped to directly
+    neighbors: Neighborhood,
+}
+
+impl PathRules<'_> {
+    fn is_goal(&self, pos: (i32, i32)) -> bool {
+        (self.is_goal)(pos)
+    }
+
+    fn tile_cost(&self, tile: Tile) -> Option<i32> {
+        (self.tile_cost)(tile)
+    }
+
+    fn is_passable(&self, tile: Tile) -> bool {
+        self.tile_cost(tile).is_some()
+    }
+
+    /// Cost of moving from the center of one tile to the center of a neighboring one, given
+    /// what's on each tile. A diagonal step also needs both tiles beside it to be passable (and
+    /// not walls, even breakable ones), so it never squeezes between or clips the corner of one.
+    fn step_cost(&self, tiles: &dyn Fn((i32, i32)) -> Tile, from: (i32, i32), to: (i32, i32)) -> Option<i32> {
+        let straight = self.tile_cost(tiles(from))? + self.tile_cost(tiles(to))?;
+
+        if from.0 == to.0 || from.1 == to.1 {
+            return Some(straight);
+        }
+
+        let beside_ok = |tile: Tile| tile != Tile::Wall && self.is_passable(tile);
+
+        if beside_ok(tiles((to.0, from.1))) && beside_ok(tiles((from.0, to.1))) {
+            Some(straight * DIAGONAL_COST_TENTHS / 10)
+        } else {
+            None
+        }
+    }
+}
+
+/// Path costs toward some objective, kept up to date as tiles change rather than being rebuilt
+/// from scratch. What counts as the objective, what each tile costs to cross, and which tiles
+/// neighbor each other are given by the PathRules passed in; the remaining assumptions are:
+/// - Neighbors are only ever inside the world bounds
+/// - Tiles are valid if and only if they are passable; impassable tiles are never given a weight
+/// - "Default" tiles will never be given a weight
 #[derive(Clone, Eq, PartialEq, Debug)]
 struct DijkstraMap {
-    // (x, y) -> cost, if set; None (no key) means inaccessible / no path
-    costs: HashMap<(i32, i32), i32>,
+    // (x, y) -> cost, if set; None means inaccessible / no path
+    costs: Grid<Option<i32>>,
 }
 
-impl DijkstraMap {
-    fn new() -> Self {
-        DijkstraMap { costs: HashMap::new() }
-    }
-
-    /// Recompute the path costs. Assumptions (which can be refactored out as lambdas as needed):
-    /// - The objective tile is just "core tiles"
-    /// - All travel is equally expensive (cost of 1)
-    /// - Neighbors are just those that are directly adjacent (4 way)
-    /// - Tiles are valid if and only if they are passable
-    /// - "Default" tiles will never be given a weight
-    fn recompute(&mut self, map: &HashMap<(i32, i32), Tile>) {
-        #[derive(Eq, PartialEq, Debug, Copy, Clone)]
-        struct NodeWeight {
-            cost: i32,
-            pos: (i32, i32),
-        };
+/// What a single tile change does to a DijkstraMap; see DijkstraMap::affected_by
+struct AffectedTiles {
+    stale: HashSet<(i32, i32)>,
+    shortcuts: Vec<(i32, i32)>,
+}
 
-        // Note cost cmp reversed, so the heap will be a min heap
-        impl Ord for NodeWeight {
-            fn cmp(&self, other: &Self) -> Ordering {
-                other.cost.cmp(&self.cost).then_with(|| self.pos.cmp(&other.pos))
-            }
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+struct NodeWeight {
+    cost: i32,
+    pos: (i32, i32),
+}
+
+// Note cost cmp reversed, so the heap will be a min heap
+impl Ord for NodeWeight {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.cost.cmp(&self.cost).then_with(|| self.pos.cmp(&other.pos))
+    }
+}
+
+impl PartialOrd for NodeWeight {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl DijkstraMap {
+    fn new(bounds: WorldBounds) -> Self {
+        DijkstraMap {
+            costs: Grid::new(bounds, None),
         }
+    }
 
-        impl PartialOrd for NodeWeight {
-            fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-                Some(self.cmp(other))
-            }
+    fn cost(&self, pos: (i32, i32)) -> Option<i32> {
+        self.costs.get(pos.0, pos.1)
+    }
+
+    /// Repair the path costs after the tile at `changed` was replaced (`old_tile` is what used to
+    /// be there; the map should already contain the new tile), or after it started or stopped
+    /// being part of the objective (in which case `old_tile` is just the current tile).
+    ///
+    /// Only the tiles whose best path ran through the changed tile are thrown out and recomputed
+    /// from their still-valid neighbors; if the change opened up a shorter route, the improvement
+    /// is then pushed outward from the changed tile. Either way the work is proportional to the
+    /// area whose costs actually change, rather than the whole map.
+    fn update_tile(&mut self, map: &Grid<Tile>, changed: (i32, i32), old_tile: Tile, rules: &PathRules) {
+        let bounds = map.bounds();
+        let tiles_before = |pos: (i32, i32)| if pos == changed { old_tile } else { map.get(pos.0, pos.1) };
+        let tiles_after = |pos: (i32, i32)| map.get(pos.0, pos.1);
+
+        let AffectedTiles { stale, shortcuts } = self.affected_by(bounds, &tiles_before, &tiles_after, changed, rules);
+
+        for pos in stale.iter() {
+            self.costs.set(pos.0, pos.1, None);
         }
 
         let mut to_process: BinaryHeap<NodeWeight> = BinaryHeap::new();
-        self.costs.clear();
 
-        // first, go through and find every "zero;" for now this means cores
-        for (pos, _) in map.iter().filter(|(_pos, tile)| **tile == Tile::Core) {
-            let weight = NodeWeight { cost: 0, pos: *pos };
-            to_process.push(weight);
+        for pos in stale.iter().chain(shortcuts.iter()).copied() {
+            let tile = map.get(pos.0, pos.1);
+
+            if !rules.is_passable(tile) {
+                continue;
+            }
+
+            if rules.is_goal(pos) {
+                to_process.push(NodeWeight { cost: 0, pos });
+            }
+
+            for neighbor_pos in rules.neighbors.of(bounds, pos) {
+                if let Some(neighbor_cost) = self.cost(neighbor_pos) {
+                    if let Some(step) = rules.step_cost(&tiles_after, pos, neighbor_pos) {
+                        to_process.push(NodeWeight {
+                            cost: neighbor_cost + step,
+                            pos,
+                        });
+                    }
+                }
+            }
         }
 
         while let Some(NodeWeight { cost, pos }) = to_process.pop() {
-            if let Some(old_cost) = self.costs.get(&pos) {
+            if let Some(old_cost) = self.cost(pos) {
                 // Then we've already seen it and this was a redundant add; skip it and move on
-                if *old_cost <= cost {
+                if old_cost <= cost {
                     continue;
                 }
             }
 
-            self.costs.insert(pos, cost);
+            self.costs.set(pos.0, pos.1, Some(cost));
 
-            if let Some(tile) = map.get(&pos) {
-                if tile.is_passable() {
-                    for neighbor_pos in neighbors(pos.0, pos.1).iter().copied() {
-                        let nw = NodeWeight {
-                            cost: cost + 1,
-                            pos: neighbor_pos,
-                        };
-                        to_process.push(nw);
+            for neighbor_pos in rules.neighbors.of(bounds, pos) {
+                let step = match rules.step_cost(&tiles_after, neighbor_pos, pos) {
+                    Some(step) => step,
+                    None => continue,
+                };
+
+                let improves = self.cost(neighbor_pos).map(|c| cost + step < c).unwrap_or(true);
+
+                if improves {
+                    to_process.push(NodeWeight {
+                        cost: cost + step,
+                        pos: neighbor_pos,
+                    });
+                }
+            }
+        }
+    }
+
+    /// Which costs may become wrong when the tile at `changed` goes from how `before` has it to
+    /// how `after` has it. That's the changed tile, plus every tile whose best path (as
+    /// currently computed) goes through it, or through a diagonal step beside it which is now
+    /// blocked or dearer; everything else still has an equally cheap path which avoids them.
+    ///
+    /// Also gives the tiles at either end of any diagonal step beside it which changed, since
+    /// those may have gained a shortcut without their own costs being stale.
+    fn affected_by(
+        &self,
+        bounds: WorldBounds,
+        before: &dyn Fn((i32, i32)) -> Tile,
+        after: &dyn Fn((i32, i32)) -> Tile,
+        changed: (i32, i32),
+        rules: &PathRules,
+    ) -> AffectedTiles {
+        let mut seeds = vec![changed];
+        let mut shortcuts = Vec::new();
+
+        if rules.neighbors == Neighborhood::EightWay {
+            let (x, y) = changed;
+            let beside = [
+                ((x - 1, y), (x, y - 1)),
+                ((x, y - 1), (x + 1, y)),
+                ((x + 1, y), (x, y + 1)),
+                ((x, y + 1), (x - 1, y)),
+            ];
+
+            for (a, b) in beside.iter().copied() {
+                if !bounds.contains(a.0, a.1) || !bounds.contains(b.0, b.1) {
+                    continue;
+                }
+
+                let old_step = rules.step_cost(before, a, b);
+
+                if old_step == rules.step_cost(after, a, b) {
+                    continue;
+                }
+
+                shortcuts.push(a);
+                shortcuts.push(b);
+
+                if let (Some(step), Some(cost_a), Some(cost_b)) = (old_step, self.cost(a), self.cost(b)) {
+                    if cost_a == cost_b + step {
+                        seeds.push(a);
+                    }
+                    if cost_b == cost_a + step {
+                        seeds.push(b);
+                    }
+                }
+            }
+        }
+
+        AffectedTiles {
+            stale: self.dependents(bounds, before, &seeds, rules),
+            shortcuts,
+        }
+    }
+
+    /// The given tiles, plus every tile whose best path to the objective (as currently computed,
+    /// with the tiles as `before` has them) goes through one of them
+    fn dependents(
+        &self,
+        bounds: WorldBounds,
+        before: &dyn Fn((i32, i32)) -> Tile,
+        seeds: &[(i32, i32)],
+        rules: &PathRules,
+    ) -> HashSet<(i32, i32)> {
+        let mut seen: HashSet<(i32, i32)> = seeds.iter().copied().collect();
+        let mut to_process = seeds.to_vec();
+
+        while let Some(pos) = to_process.pop() {
+            let cost = match self.cost(pos) {
+                Some(cost) => cost,
+                None => continue,
+            };
+
+            for neighbor_pos in rules.neighbors.of(bounds, pos) {
+                let step = match rules.step_cost(before, neighbor_pos, pos) {
+                    Some(step) => step,
+                    None => continue,
+                };
+
+                if self.cost(neighbor_pos) == Some(cost + step) && seen.insert(neighbor_pos) {
+                    to_process.push(neighbor_pos);
+                }
+            }
+        }
+
+        seen
+    }
+
+    /// Whether every one of `sources` could still reach the objective if the tiles in `changes`
+    /// were all replaced. Nothing is recomputed; sources whose current path avoids every changed
+    /// tile are fine as-is, and the rest just search for any tile which is still known to have a
+    /// path.
+    fn stays_connected(&self, map: &Grid<Tile>, changes: &HashMap<(i32, i32), Tile>, sources: &[(i32, i32)], rules: &PathRules) -> bool {
+        let bounds = map.bounds();
+        let tiles_before = |pos: (i32, i32)| map.get(pos.0, pos.1);
+        let tiles_after = |pos: (i32, i32)| changes.get(&pos).copied().unwrap_or_else(|| map.get(pos.0, pos.1));
+
+        // a path is only sure to survive if it avoids all of the changes at once
+        let mut stale: HashSet<(i32, i32)> = HashSet::new();
+        for changed in changes.keys().copied() {
+            stale.extend(self.affected_by(bounds, &tiles_before, &tiles_after, changed, rules).stale);
+        }
+
+        // Connectivity is symmetric, so anything found alongside a connected source is connected too
+        let mut connected: HashSet<(i32, i32)> = HashSet::new();
+
+        for source in sources.iter().copied() {
+            if !stale.contains(&source) && self.cost(source).is_some() {
+                continue;
+            }
+
+            if !rules.is_passable(tiles_after(source)) {
+                return false;
+            }
+
+            let mut seen = HashSet::new();
+            seen.insert(source);
+            let mut to_process = vec![source];
+            let mut found = false;
+
+            while let Some(pos) = to_process.pop() {
+                let has_path = !stale.contains(&pos) && self.cost(pos).is_some();
+
+                if rules.is_goal(pos) || has_path || connected.contains(&pos) {
+                    found = true;
+                    break;
+                }
+
+                for neighbor_pos in rules.neighbors.of(bounds, pos) {
+                    if rules.step_cost(&tiles_after, pos, neighbor_pos).is_some() && seen.insert(neighbor_pos) {
+                        to_process.push(neighbor_pos);
                     }
                 }
             }
+
+            if !found {
+                return false;
+            }
+
+            connected.extend(seen);
+        }
+
+        true
+    }
+}
+
+impl PathTarget {
+    fn is_goal(self, pos: (i32, i32), cores: &BTreeSet<(i32, i32)>) -> bool {
+        match self {
+            PathTarget::NearestCore => cores.contains(&pos),
+            PathTarget::Core { x, y } => pos == (x, y) && cores.contains(&pos),
         }
     }
 }
 
 impl Map {
-    pub fn new() -> Self {
+    /// Create a map of the given size, where every tile starts out as the default (a wall), and
+    /// paths can never be blocked, and everything moves four ways
+    pub fn new(bounds: WorldBounds) -> Self {
+        Map::with_rules(bounds, MapRules::default())
+    }
+
+    pub fn with_rules(bounds: WorldBounds, rules: MapRules) -> Self {
         Map {
-            map: HashMap::new(),
-            dijkstra_maps_dirty: false,
-            core_paths: DijkstraMap::new(),
-            poison_gas_map: FlowMap::new(GasFlow, 6, 1),
+            map: Grid::new(bounds, DEFAULT_TILE),
+            core_paths: ALL_MOVEMENT_KINDS
+                .iter()
+                .map(|kind| ((*kind, PathTarget::NearestCore), DijkstraMap::new(bounds)))
+                .collect(),
+            cores: BTreeSet::new(),
+            spawns: BTreeSet::new(),
+            poison_gas_map: FlowMap::new(bounds, GasFlow, rules.gas_neighbors, 6, 1),
+            rules,
+            wall_damage: Grid::new(bounds, 0),
+            revision: 0,
         }
     }
 
+    pub fn rules(&self) -> &MapRules {
+        &self.rules
+    }
+
+    /// Changes whenever the tiles, cores or spawns do; the same revision means the same paths
+    /// and the same places for gas to go
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    /// Whether the tile is part of the world at all; everything outside is a permanent wall
+    pub fn in_bounds(&self, x: i32, y: i32) -> bool {
+        self.map.bounds().contains(x, y)
+    }
+
     pub fn tick_gas_map(&mut self) {
-        self.poison_gas_map.tick(&self.map, DEFAULT_TILE);
+        let (cores, spawns) = (&self.cores, &self.spawns);
+        self.poison_gas_map
+            .tick(&self.map, &|pos| cores.contains(&pos) || spawns.contains(&pos));
     }
 
     pub fn add_gas_to_tile(&mut self, tile_x: i32, tile_y: i32, amount: i32) {
@@ -133,60 +558,287 @@ impl Map {
     }
 
     pub fn get_gas_amount(&self, tile_x: i32, tile_y: i32) -> i32 {
-        self.poison_gas_map.amounts.get(&(tile_x, tile_y)).copied().unwrap_or(0)
+        self.poison_gas_map.amounts.get(tile_x, tile_y)
+    }
+
+    /// Every tile in the same unbroken patch of gas as the given one (empty if it has no gas).
+    /// Gas only gets anywhere by spreading from tile to tile, so whatever put gas on the tile is
+    /// somewhere in the patch.
+    pub fn gas_cloud(&self, tile_x: i32, tile_y: i32) -> HashSet<(i32, i32)> {
+        let mut cloud = HashSet::new();
+        let mut to_visit = vec![(tile_x, tile_y)];
+
+        while let Some(pos) = to_visit.pop() {
+            if !self.in_bounds(pos.0, pos.1) || self.get_gas_amount(pos.0, pos.1) <= 0 || !cloud.insert(pos) {
+                continue;
+            }
+
+            to_visit.extend(self.poison_gas_map.neighbors.of(self.map.bounds(), pos));
+        }
+
+        cloud
+    }
+
+    /// Where gas from the given sources would spread on the map as it is now, ignoring any gas
+    /// already about: `steady` sources add their amount every tick, `bursts` just once. Runs a
+    /// separate gas map forward from empty until it settles (or for at most `max_ticks`), and
+    /// gives the most gas each tile held at any point along the way.
+    pub fn preview_gas(&self, steady: &[((i32, i32), i32)], bursts: &[((i32, i32), i32)], max_ticks: u32) -> HashMap<(i32, i32), i32> {
+        let mut gas = FlowMap::new(
+            self.map.bounds(),
+            GasFlow,
+            self.poison_gas_map.neighbors,
+            self.poison_gas_map.fluidity,
+            self.poison_gas_map.dispersal,
+        );
+
+        for ((x, y), amount) in bursts.iter().copied() {
+            gas.add_amount(x, y, amount);
+        }
+
+        let mut most: HashMap<(i32, i32), i32> = HashMap::new();
+
+        for _ in 0..max_ticks {
+            for ((x, y), amount) in steady.iter().copied() {
+                gas.add_amount(x, y, amount);
+            }
+
+            let before = gas.amounts.clone();
+            gas.tick(&self.map, &|pos| self.cores.contains(&pos) || self.spawns.contains(&pos));
+
+            for chunk in 0..gas.amounts.num_chunks() {
+                for (pos, amount) in gas.amounts.iter_chunk(chunk).filter(|(_, amount)| *amount > 0) {
+                    let held = most.entry(pos).or_insert(0);
+                    *held = (*held).max(amount);
+                }
+            }
+
+            if gas.amounts == before {
+                break;
+            }
+        }
+
+        most
     }
 
     pub fn get_tile(&self, x: i32, y: i32) -> Tile {
-        self.map.get(&(x, y)).copied().unwrap_or(DEFAULT_TILE)
+        self.map.get(x, y)
     }
 
+    /// Change the tile at the given position. Positions outside the bounds can't be changed.
     pub fn set_tile(&mut self, x: i32, y: i32, tile: Tile) {
-        self.dijkstra_maps_dirty = true;
-        self.map.insert((x, y), tile);
+        if !self.in_bounds(x, y) {
+            return;
+        }
+
+        let old_tile = self.get_tile(x, y);
+        self.map.set(x, y, tile);
+
+        if old_tile == tile {
+            return;
+        }
+
+        self.revision += 1;
+        self.wall_damage.set(x, y, 0);
+
+        let map_rules = &self.rules;
+        let cores = &self.cores;
+
+        for ((kind, target), paths) in self.core_paths.iter_mut() {
+            let rules = PathRules {
+                is_goal: &|pos| target.is_goal(pos, cores),
+                tile_cost: &|tile| kind.tile_cost(tile, map_rules.blocking),
+                neighbors: map_rules.neighbors_for(*kind),
+            };
+            paths.update_tile(&self.map, (x, y), old_tile, &rules);
+        }
     }
 
-    pub fn can_set_tile(&self, x: i32, y: i32, tile: Tile) -> bool {
-        let mut test = self.clone();
-        test.set_tile(x, y, tile);
-        test.recompute_dijkstra_maps();
+    /// Replace the set of tiles holding a core, which are what mobs path toward. Only the paths
+    /// around cores which appeared or disappeared are recomputed, so passing the same set again
+    /// costs next to nothing. Cores outside the bounds are ignored.
+    pub fn set_cores(&mut self, cores: BTreeSet<(i32, i32)>) {
+        let bounds = self.map.bounds();
 
-        for (pos, _) in test.map.iter().filter(|(_, tile)| **tile == Tile::Spawn) {
-            if test.core_paths.costs.get(pos).is_none() {
-                return false;
+        let removed: Vec<(i32, i32)> = self.cores.difference(&cores).copied().collect();
+        let added: Vec<(i32, i32)> = cores
+            .difference(&self.cores)
+            .copied()
+            .filter(|(x, y)| bounds.contains(*x, *y))
+            .collect();
+
+        if !removed.is_empty() || !added.is_empty() {
+            self.revision += 1;
+        }
+
+        // One core at a time, so each repair only has a single change to account for
+        for (x, y) in removed {
+            self.cores.remove(&(x, y));
+            self.core_paths.retain(|(_, target), _| *target != PathTarget::Core { x, y });
+            self.update_nearest_core_paths((x, y));
+        }
+
+        for (x, y) in added {
+            self.cores.insert((x, y));
+            self.update_nearest_core_paths((x, y));
+
+            let target = PathTarget::Core { x, y };
+            let map_rules = &self.rules;
+
+            for kind in ALL_MOVEMENT_KINDS.iter().copied() {
+                // Starting from nothing, "repairing" the core tile fills in the whole map
+                let mut paths = DijkstraMap::new(bounds);
+                let rules = PathRules {
+                    is_goal: &|pos| target.is_goal(pos, &self.cores),
+                    tile_cost: &|tile| kind.tile_cost(tile, map_rules.blocking),
+                    neighbors: map_rules.neighbors_for(kind),
+                };
+                paths.update_tile(&self.map, (x, y), self.map.get(x, y), &rules);
+
+                self.core_paths.insert((kind, target), paths);
             }
         }
+    }
 
-        true
+    /// Replace the set of tiles holding a spawn, which must stay connected to the cores
+    pub fn set_spawns(&mut self, spawns: BTreeSet<(i32, i32)>) {
+        if spawns != self.spawns {
+            self.revision += 1;
+            self.spawns = spawns;
+        }
     }
 
-    fn recompute_dijkstra_maps(&mut self) {
-        if !self.dijkstra_maps_dirty {
-            return;
+    pub fn has_core(&self, x: i32, y: i32) -> bool {
+        self.cores.contains(&(x, y))
+    }
+
+    pub fn spawns(&self) -> impl Iterator<Item = (i32, i32)> + '_ {
+        self.spawns.iter().copied()
+    }
+
+    pub fn cores(&self) -> impl Iterator<Item = (i32, i32)> + '_ {
+        self.cores.iter().copied()
+    }
+
+    fn update_nearest_core_paths(&mut self, changed: (i32, i32)) {
+        let map_rules = &self.rules;
+        let cores = &self.cores;
+        let tile = self.map.get(changed.0, changed.1);
+
+        for ((kind, target), paths) in self.core_paths.iter_mut() {
+            if *target != PathTarget::NearestCore {
+                continue;
+            }
+
+            let rules = PathRules {
+                is_goal: &|pos| target.is_goal(pos, cores),
+                tile_cost: &|tile| kind.tile_cost(tile, map_rules.blocking),
+                neighbors: map_rules.neighbors_for(*kind),
+            };
+            paths.update_tile(&self.map, changed, tile, &rules);
+        }
+    }
+
+    /// Whether the tile could be changed without cutting any spawn off from every core, for any
+    /// kind of mob. Tiles under a core or a spawn can't be changed at all. Cheap enough to call
+    /// every frame; the map is not modified or copied.
+    pub fn can_set_tile(&self, x: i32, y: i32, tile: Tile) -> bool {
+        self.can_set_tile_after(&HashMap::new(), x, y, tile)
+    }
+
+    /// As `can_set_tile`, but as if the `pending` tile changes had already been made. Checks a
+    /// batch of changes one at a time, each seeing the ones before it, without copying the map.
+    pub fn can_set_tile_after(&self, pending: &HashMap<(i32, i32), Tile>, x: i32, y: i32, tile: Tile) -> bool {
+        if !self.in_bounds(x, y) || self.cores.contains(&(x, y)) || self.spawns.contains(&(x, y)) {
+            return false;
         }
 
-        self.core_paths.recompute(&self.map);
+        let mut changes = pending.clone();
+        changes.insert((x, y), tile);
+
+        let spawns: Vec<(i32, i32)> = self.spawns.iter().copied().collect();
 
-        self.dijkstra_maps_dirty = false;
+        let map_rules = &self.rules;
+        let cores = &self.cores;
+
+        self.core_paths
+            .iter()
+            .filter(|((_, target), _)| *target == PathTarget::NearestCore)
+            .all(|((kind, target), paths)| {
+                let rules = PathRules {
+                    is_goal: &|pos| target.is_goal(pos, cores),
+                    tile_cost: &|tile| kind.tile_cost(tile, map_rules.blocking),
+                    neighbors: map_rules.neighbors_for(*kind),
+                };
+                paths.stays_connected(&self.map, &changes, &spawns, &rules)
+            })
     }
 
-    /// Get the tile coordinates of the best tile to move to, from here.
-    /// If there is no improvement possible (either because you're "there" or because there's no
-    /// path) just return the input.
-    pub fn move_toward_spawn(&mut self, start_x: i32, start_y: i32) -> (i32, i32) {
-        self.recompute_dijkstra_maps();
+    /// Whether a spawn placed on the given tile would have a path to some core, for every kind
+    /// of mob
+    pub fn can_add_spawn(&self, x: i32, y: i32) -> bool {
+        self.in_bounds(x, y)
+            && self
+                .core_paths
+                .iter()
+                .filter(|((_, target), _)| *target == PathTarget::NearestCore)
+                .all(|(_, paths)| paths.cost((x, y)).is_some())
+    }
 
-        let mut least_cost = self.core_paths.costs.get(&(start_x, start_y)).copied().unwrap_or(i32::max_value());
+    /// Whether the core at the given tile could be taken away without cutting any spawn off from
+    /// every other core, for any kind of mob. Like `can_add_spawn`, this only looks at the paths
+    /// already worked out for each core.
+    pub fn can_remove_core(&self, x: i32, y: i32) -> bool {
+        let others: Vec<PathTarget> = self
+            .cores
+            .iter()
+            .filter(|&&core| core != (x, y))
+            .map(|&(x, y)| PathTarget::Core { x, y })
+            .collect();
+
+        ALL_MOVEMENT_KINDS.iter().all(|&kind| {
+            self.spawns.iter().all(|&spawn| {
+                others.iter().any(|&target| {
+                    self.core_paths
+                        .get(&(kind, target))
+                        .is_some_and(|paths| paths.cost(spawn).is_some())
+                })
+            })
+        })
+    }
+
+    /// Get the tile coordinates of the best tile to move to, from here, for a mob of the given
+    /// movement kind heading for the given target. If there is no improvement possible (either
+    /// because you're "there" or because there's no path) just return the input.
+    pub fn move_toward_spawn(&self, kind: MovementKind, target: PathTarget, start_x: i32, start_y: i32) -> (i32, i32) {
+        let target = if self.core_paths.contains_key(&(kind, target)) {
+            target
+        } else {
+            PathTarget::NearestCore
+        };
+        let paths = &self.core_paths[&(kind, target)];
+        let map_rules = &self.rules;
+        let rules = PathRules {
+            is_goal: &|pos| target.is_goal(pos, &self.cores),
+            tile_cost: &|tile| kind.tile_cost(tile, map_rules.blocking),
+            neighbors: map_rules.neighbors_for(kind),
+        };
+
+        let tiles = |pos: (i32, i32)| self.get_tile(pos.0, pos.1);
+        let remaining_cost = paths.cost((start_x, start_y)).unwrap_or(i32::MAX);
+
+        let mut least_cost = i32::MAX;
         let mut winning_coords = (start_x, start_y);
 
-        for (x, y) in neighbors(start_x, start_y)
-            .iter()
-            .copied()
-            .filter(|(x, y)| self.map.get(&(*x, *y)).copied().unwrap_or(DEFAULT_TILE).is_passable())
-        {
-            let cost = self.core_paths.costs.get(&(x, y)).copied().unwrap_or(i32::max_value());
+        for (x, y) in rules.neighbors.of(self.map.bounds(), (start_x, start_y)) {
+            let (cost, step) = match (paths.cost((x, y)), rules.step_cost(&tiles, (start_x, start_y), (x, y))) {
+                (Some(cost), Some(step)) => (cost, step),
+                _ => continue,
+            };
 
-            if cost < least_cost {
-                least_cost = cost;
+            // only ever step closer to the objective, so the mob can't dither in place
+            if cost < remaining_cost && cost + step < least_cost {
+                least_cost = cost + step;
                 winning_coords = (x, y);
             }
         }
@@ -194,23 +846,55 @@ impl Map {
         winning_coords
     }
 
-    pub fn all_spawns(&self) -> Vec<(i32, i32)> {
-        self.map
-            .iter()
-            .filter(|(_pos, tile)| **tile == Tile::Spawn)
-            .map(|(pos, _)| *pos)
-            .collect()
+    /// The tiles a mob of the given movement kind would pass through on its way from the given
+    /// tile to the given target, starting with that tile; just the starting tile if it's already
+    /// there or has no path at all. Each step strictly lowers the remaining cost, so this always
+    /// ends.
+    pub fn route(&self, kind: MovementKind, target: PathTarget, start_x: i32, start_y: i32) -> Vec<(i32, i32)> {
+        let mut route = vec![(start_x, start_y)];
+        let mut here = (start_x, start_y);
+
+        loop {
+            let next = self.move_toward_spawn(kind, target, here.0, here.1);
+
+            if next == here {
+                return route;
+            }
+
+            route.push(next);
+            here = next;
+        }
     }
-}
 
-impl Tile {
-    fn is_passable(self) -> bool {
-        match self {
-            Tile::Open => true,
-            Tile::Wall => false,
-            Tile::Spawn => true,
-            Tile::Core => true,
+    /// Deal damage to the wall at the given tile, if walls can be broken at all. Once it has taken
+    /// WALL_HEALTH damage it crumbles into open ground. Returns whether it crumbled.
+    pub fn damage_wall(&mut self, x: i32, y: i32, amount: i32) -> bool {
+        if self.rules.blocking != BlockingRule::MobsBreakWalls || self.get_tile(x, y) != Tile::Wall {
+            return false;
+        }
+
+        let damage = match self.wall_damage.get_mut(x, y) {
+            Some(damage) => damage,
+            None => return false,
+        };
+
+        *damage += amount;
+
+        if *damage < WALL_HEALTH {
+            return false;
         }
+
+        self.set_tile(x, y, Tile::Open);
+        true
+    }
+
+    /// Remaining health of the wall at the given tile, if it is a wall which can be broken
+    pub fn wall_health(&self, x: i32, y: i32) -> Option<i32> {
+        if self.rules.blocking != BlockingRule::MobsBreakWalls || !self.in_bounds(x, y) || self.get_tile(x, y) != Tile::Wall {
+            return None;
+        }
+
+        Some(WALL_HEALTH - self.wall_damage.get(x, y))
     }
 }
 
@@ -220,8 +904,10 @@ fn neighbors(x: i32, y: i32) -> [(i32, i32); 4] {
 
 #[derive(Clone, Debug, Eq, PartialEq)]
 struct FlowMap<P: PassableChecker + Clone + 'static> {
-    amounts: HashMap<(i32, i32), i32>,
+    amounts: Grid<i32>,
     tile_checker: P,
+    // which tiles gas can spread to directly; diagonally, only if both tiles beside it are passable
+    neighbors: Neighborhood,
     // i32 for simplicity but should be nonnegative
     // this is the amount of fluid that can flow out of any particular square per tick
     fluidity: i32,
@@ -232,18 +918,20 @@ struct FlowMap<P: PassableChecker + Clone + 'static> {
 }
 
 impl<P: PassableChecker + Clone + 'static> FlowMap<P> {
-    pub fn new(tile_checker: P, fluidity: i32, dispersal: i32) -> FlowMap<P> {
+    pub fn new(bounds: WorldBounds, tile_checker: P, neighbors: Neighborhood, fluidity: i32, dispersal: i32) -> FlowMap<P> {
         Self {
-            amounts: HashMap::new(),
+            amounts: Grid::new(bounds, 0),
             tile_checker,
+            neighbors,
             fluidity,
             dispersal,
         }
     }
 
-    pub fn tick(&mut self, tiles: &HashMap<(i32, i32), Tile>, default_tile: Tile) {
+    /// `is_blocked` picks out tiles which gas can't flow onto whatever the tile is
+    pub fn tick(&mut self, tiles: &Grid<Tile>, is_blocked: &dyn Fn((i32, i32)) -> bool) {
         self.disperse();
-        self.flow(tiles, default_tile);
+        self.flow(tiles, is_blocked);
         self.cleanup();
     }
 
@@ -260,33 +948,392 @@ impl<P: PassableChecker + Clone + 'static> FlowMap<P> {
     /// Most complex part; each tile has an amount on it, and a fluidity
     /// Basically any tile that has any remaining fluidity can shift a gas unit to a
     /// tile which has fewer gas units
-    fn flow(&mut self, tiles: &HashMap<(i32, i32), Tile>, default_tile: Tile) {
-        let mut flow_from: Vec<(i32, i32)> = tiles.keys().copied().collect();
-        // We have to sort because this sloppy algorithm is not commutative (because we don't iterate)
-        // and we don't want to like, refresh the page and now the player's gas traps have different coverage
+    fn flow(&mut self, tiles: &Grid<Tile>, is_blocked: &dyn Fn((i32, i32)) -> bool) {
+        // This sloppy algorithm is not commutative (because we don't iterate), so the order matters;
+        // we go in sorted (x, y) order so we don't like, refresh the page and now the player's gas
+        // traps have different coverage. Unallocated chunks have no gas to give, so they're skipped;
+        // that's checked as we reach them, so gas which spills into a fresh chunk further along
+        // still gets to flow this tick.
+        let bounds = self.amounts.bounds();
+        let chunk_rows: Vec<Range<i32>> = self.amounts.chunk_rows().collect();
+
+        for tile_x in bounds.x_min..bounds.x_max {
+            for rows in chunk_rows.iter() {
+                if !self.amounts.is_allocated(tile_x, rows.start) {
+                    continue;
+                }
+
+                for tile_y in rows.clone() {
+                    let mut self_amount = self.amounts.get(tile_x, tile_y);
+
+                    if self_amount <= 1 {
+                        continue;
+                    }
+
+                    let passable = |x: i32, y: i32| self.tile_checker.is_passable(tiles.get(x, y)) && !is_blocked((x, y));
+
+                    let mut neighbors: Vec<(i32, i32)> = neighbors(tile_x, tile_y)
+                        .iter()
+                        .copied()
+                        .filter(|(x, y)| passable(*x, *y))
+                        .collect();
+
+                    if self.neighbors == Neighborhood::EightWay {
+                        for (dx, dy) in [(-1, -1), (1, -1), (-1, 1), (1, 1)].iter().copied() {
+                            let (x, y) = (tile_x + dx, tile_y + dy);
+
+                            if passable(x, y) && passable(x, tile_y) && passable(tile_x, y) {
+                                neighbors.push((x, y));
+                            }
+                        }
+                    }
+
+                    let mut remaining_fluidity = self.fluidity;
+                    let mut neighbor_index = 0;
+
+                    while remaining_fluidity > 0 && !neighbors.is_empty() && self_amount > 1 {
+                        neighbor_index %= neighbors.len();
+
+                        let (nx, ny) = neighbors[neighbor_index];
+
+                        let neighbor = match self.amounts.get_mut(nx, ny) {
+                            Some(neighbor) => neighbor,
+                            None => {
+                                neighbors.remove(neighbor_index);
+                                continue;
+                            }
+                        };
+
+                        if *neighbor + 1 < self_amount {
+                            self_amount -= 1;
+                            remaining_fluidity -= 1;
+                            *neighbor += 1;
+                            neighbor_index += 1;
+                        } else {
+                            neighbors.remove(neighbor_index);
+                        }
+                    }
+
+                    self.amounts.set(tile_x, tile_y, self_amount);
+                }
+            }
+        }
+    }
+
+    /// Delete unused squares to save space
+    fn cleanup(&mut self) {
+        self.amounts.release_empty_chunks()
+    }
+
+    pub fn add_amount(&mut self, tile_x: i32, tile_y: i32, to_add: i32) {
+        if let Some(amount) = self.amounts.get_mut(tile_x, tile_y) {
+            *amount = (*amount).saturating_add(to_add);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::*;
+
+    const BOUNDS: WorldBounds = WorldBounds {
+        x_min: -3,
+        y_min: -2,
+        x_max: 9,
+        y_max: 8,
+    };
+
+    fn all_positions() -> impl Iterator<Item = (i32, i32)> {
+        (BOUNDS.y_min..BOUNDS.y_max).flat_map(|y| (BOUNDS.x_min..BOUNDS.x_max).map(move |x| (x, y)))
+    }
+
+    /// Path costs worked out from scratch, the slow and obvious way
+    fn full_dijkstra(tiles: &Grid<Tile>, rules: &PathRules) -> Grid<Option<i32>> {
+        let mut costs = Grid::new(tiles.bounds(), None);
+        let tile_at = |pos: (i32, i32)| tiles.get(pos.0, pos.1);
+        let mut to_process = BinaryHeap::new();
+
+        for pos in all_positions() {
+            if rules.is_goal(pos) && rules.is_passable(tile_at(pos)) {
+                to_process.push(NodeWeight { cost: 0, pos });
+            }
+        }
+
+        while let Some(NodeWeight { cost, pos }) = to_process.pop() {
+            if costs.get(pos.0, pos.1).is_some() {
+                continue;
+            }
+            costs.set(pos.0, pos.1, Some(cost));
+
+            for neighbor_pos in rules.neighbors.of(tiles.bounds(), pos) {
+                if let Some(step) = rules.step_cost(&tile_at, neighbor_pos, pos) {
+                    if costs.get(neighbor_pos.0, neighbor_pos.1).is_none() {
+                        to_process.push(NodeWeight {
+                            cost: cost + step,
+                            pos: neighbor_pos,
+                        });
+                    }
+                }
+            }
+        }
+
+        costs
+    }
+
+    fn assert_matches_full_recompute(map: &Map, step: usize) {
+        for ((kind, target), paths) in map.core_paths.iter() {
+            let rules = PathRules {
+                is_goal: &|pos| target.is_goal(pos, &map.cores),
+                tile_cost: &|tile| kind.tile_cost(tile, map.rules.blocking),
+                neighbors: map.rules.neighbors_for(*kind),
+            };
+            let expected = full_dijkstra(&map.map, &rules);
+
+            for (x, y) in all_positions() {
+                assert_eq!(
+                    paths.cost((x, y)),
+                    expected.get(x, y),
+                    "step {}: {:?} paths to {:?} disagree at ({}, {})",
+                    step,
+                    kind,
+                    target,
+                    x,
+                    y
+                );
+            }
+        }
+    }
+
+    fn random_map(rng: &mut StdRng, rules: MapRules) -> Map {
+        let mut map = Map::with_rules(BOUNDS, rules);
+
+        for (x, y) in all_positions() {
+            let tile = if rng.gen_bool(0.6) {
+                Tile::Open
+            } else {
+                ALL_TILES[rng.gen_range(0..ALL_TILES.len())]
+            };
+            map.set_tile(x, y, tile);
+        }
+
+        for pos in [(-3, -2), (8, 7), (0, 5)].iter().copied() {
+            map.set_tile(pos.0, pos.1, Tile::Open);
+        }
+        map.set_spawns(vec![(-3, -2)].into_iter().collect());
+        map.set_cores(vec![(8, 7), (0, 5)].into_iter().collect());
+
+        map
+    }
+
+    fn all_rules() -> Vec<MapRules> {
+        let mut out = Vec::new();
+
+        for blocking in [BlockingRule::NeverBlock, BlockingRule::MobsBreakWalls].iter().copied() {
+            for mob_neighbors in [Neighborhood::FourWay, Neighborhood::EightWay].iter().copied() {
+                out.push(MapRules {
+                    blocking,
+                    mob_neighbors,
+                    ..MapRules::default()
+                });
+            }
+        }
+
+        // swimmers getting about differently from walkers
+        out.push(MapRules {
+            kind_neighbors: vec![(MovementKind::Swimmer, Neighborhood::EightWay)].into_iter().collect(),
+            ..MapRules::default()
+        });
+
+        out
+    }
+
+    #[test]
+    fn incremental_paths_match_full_recompute() {
+        for (seed, rules) in all_rules().into_iter().enumerate() {
+            let mut rng = StdRng::seed_from_u64(seed as u64);
+            let mut map = random_map(&mut rng, rules);
+            assert_matches_full_recompute(&map, 0);
+
+            for step in 1..=300 {
+                let (x, y) = (rng.gen_range(BOUNDS.x_min..BOUNDS.x_max), rng.gen_range(BOUNDS.y_min..BOUNDS.y_max));
+                map.set_tile(x, y, ALL_TILES[rng.gen_range(0..ALL_TILES.len())]);
+                assert_matches_full_recompute(&map, step);
+            }
+        }
+    }
+
+    #[test]
+    fn incremental_paths_follow_cores_coming_and_going() {
+        let mut rng = StdRng::seed_from_u64(99);
+        let mut map = random_map(&mut rng, all_rules()[1].clone());
+
+        for step in 1..=100 {
+            let cores: BTreeSet<(i32, i32)> = (0..rng.gen_range(1..4))
+                .map(|_| (rng.gen_range(BOUNDS.x_min..BOUNDS.x_max), rng.gen_range(BOUNDS.y_min..BOUNDS.y_max)))
+                .collect();
+            map.set_cores(cores);
+            assert_matches_full_recompute(&map, step);
+        }
+    }
+
+    #[test]
+    fn can_set_tile_matches_clone_and_check() {
+        for (seed, rules) in all_rules().into_iter().enumerate() {
+            let mut rng = StdRng::seed_from_u64(100 + seed as u64);
+            let mut map = random_map(&mut rng, rules);
+
+            for _ in 0..300 {
+                let (x, y) = (rng.gen_range(BOUNDS.x_min..BOUNDS.x_max), rng.gen_range(BOUNDS.y_min..BOUNDS.y_max));
+                let tile = ALL_TILES[rng.gen_range(0..ALL_TILES.len())];
+
+                let expected = !map.has_core(x, y) && !map.spawns.contains(&(x, y)) && {
+                    let mut changed = map.clone();
+                    changed.set_tile(x, y, tile);
+                    changed
+                        .core_paths
+                        .iter()
+                        .filter(|((_, target), _)| *target == PathTarget::NearestCore)
+                        .all(|(_, paths)| changed.spawns().all(|spawn| paths.cost(spawn).is_some()))
+                };
+
+                assert_eq!(map.can_set_tile(x, y, tile), expected, "setting ({}, {}) to {:?}", x, y, tile);
+
+                // keep the map changing, allowed or not, so both connected and cut-off spawns come up
+                if rng.gen_bool(0.5) && !map.has_core(x, y) && !map.spawns.contains(&(x, y)) {
+                    map.set_tile(x, y, tile);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn can_set_tile_after_matches_making_the_pending_changes() {
+        for (seed, rules) in all_rules().into_iter().enumerate() {
+            let mut rng = StdRng::seed_from_u64(300 + seed as u64);
+            let map = random_map(&mut rng, rules);
+
+            for _ in 0..20 {
+                // a batch of changes, each allowed given the ones before it
+                let mut pending = HashMap::new();
+                let mut changed = map.clone();
+
+                for _ in 0..rng.gen_range(1..12) {
+                    let (x, y) = (rng.gen_range(BOUNDS.x_min..BOUNDS.x_max), rng.gen_range(BOUNDS.y_min..BOUNDS.y_max));
+                    let tile = ALL_TILES[rng.gen_range(0..ALL_TILES.len())];
+
+                    if changed.can_set_tile(x, y, tile) {
+                        changed.set_tile(x, y, tile);
+                        pending.insert((x, y), tile);
+                    }
+                }
+
+                for _ in 0..30 {
+                    let (x, y) = (rng.gen_range(BOUNDS.x_min..BOUNDS.x_max), rng.gen_range(BOUNDS.y_min..BOUNDS.y_max));
+                    let tile = ALL_TILES[rng.gen_range(0..ALL_TILES.len())];
+
+                    assert_eq!(
+                        map.can_set_tile_after(&pending, x, y, tile),
+                        changed.can_set_tile(x, y, tile),
+                        "setting ({}, {}) to {:?} after {:?}",
+                        x,
+                        y,
+                        tile,
+                        pending
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn revision_changes_with_tiles_cores_and_spawns() {
+        let mut map = Map::new(BOUNDS);
+        let mut last = map.revision();
+
+        let mut assert_changed = |map: &Map, changed: bool| {
+            assert_eq!(map.revision() != last, changed);
+            last = map.revision();
+        };
+
+        map.set_tile(0, 0, Tile::Open);
+        assert_changed(&map, true);
+        map.set_tile(0, 0, Tile::Open);
+        assert_changed(&map, false);
+        map.set_tile(100, 0, Tile::Open);
+        assert_changed(&map, false);
+
+        map.set_cores(vec![(0, 0)].into_iter().collect());
+        assert_changed(&map, true);
+        map.set_cores(vec![(0, 0)].into_iter().collect());
+        assert_changed(&map, false);
+
+        map.set_spawns(vec![(1, 0)].into_iter().collect());
+        assert_changed(&map, true);
+        map.set_spawns(vec![(1, 0)].into_iter().collect());
+        assert_changed(&map, false);
+
+        // gas comes and goes without touching where it can go
+        map.add_gas_to_tile(0, 0, 10);
+        map.tick_gas_map();
+        assert_changed(&map, false);
+    }
+
+    #[test]
+    fn gas_stays_off_cores_and_spawns() {
+        let mut map = Map::new(BOUNDS);
+
+        // a corridor along y = 0, with a spawn and a core partway along
+        for x in BOUNDS.x_min..BOUNDS.x_max {
+            map.set_tile(x, 0, Tile::Open);
+        }
+        map.set_spawns(vec![(-1, 0)].into_iter().collect());
+        map.set_cores(vec![(4, 0)].into_iter().collect());
+
+        for _ in 0..50 {
+            map.add_gas_to_tile(1, 0, 10);
+            map.tick_gas_map();
+        }
+
+        assert!(map.get_gas_amount(3, 0) > 0);
+        assert!(map.get_gas_amount(0, 0) > 0);
+        for x in [-3, -2, -1, 4, 5, 8].iter().copied() {
+            assert_eq!(map.get_gas_amount(x, 0), 0, "gas got to ({}, 0)", x);
+        }
+
+        let preview = map.preview_gas(&[((1, 0), 10)], &[], 50);
+        assert!(preview.contains_key(&(3, 0)));
+        assert!(!preview.contains_key(&(4, 0)));
+        assert!(!preview.contains_key(&(-1, 0)));
+    }
+
+    /// One gas tick the way it was done before the grid: every tile flows in sorted (x, y) order
+    fn hash_map_gas_tick(amounts: &mut HashMap<(i32, i32), i32>, tiles: &Grid<Tile>, fluidity: i32, dispersal: i32) {
+        amounts.values_mut().for_each(|amt| *amt = (*amt - dispersal).max(0));
+        amounts.retain(|_, amt| *amt != 0);
+
+        let bounds = tiles.bounds();
+        let mut flow_from: Vec<(i32, i32)> = (bounds.y_min..bounds.y_max)
+            .flat_map(|y| (bounds.x_min..bounds.x_max).map(move |x| (x, y)))
+            .collect();
         flow_from.sort();
 
-        // In whatever order we have these tiles in, just
         for (tile_x, tile_y) in flow_from {
             let mut neighbors: Vec<(i32, i32)> = neighbors(tile_x, tile_y)
                 .iter()
                 .copied()
-                .filter(|(x, y)| {
-                    let this_tile = tiles.get(&(*x, *y)).copied().unwrap_or(default_tile);
-                    self.tile_checker.is_passable(this_tile)
-                })
+                .filter(|(x, y)| GasFlow.is_passable(tiles.get(*x, *y)))
                 .collect();
 
-            let mut remaining_fluidity = self.fluidity;
+            let mut remaining_fluidity = fluidity;
             let mut neighbor_index = 0;
-            let mut self_amount = self.amounts.get(&(tile_x, tile_y)).copied().unwrap_or(0);
+            let mut self_amount = amounts.get(&(tile_x, tile_y)).copied().unwrap_or(0);
 
             while remaining_fluidity > 0 && !neighbors.is_empty() && self_amount > 1 {
-                neighbor_index = neighbor_index % neighbors.len();
-
-                let (nx, ny) = neighbors[neighbor_index];
+                neighbor_index %= neighbors.len();
 
-                let neighbor = self.amounts.entry((nx, ny)).or_insert(0);
+                let neighbor = amounts.entry(neighbors[neighbor_index]).or_insert(0);
 
                 if *neighbor + 1 < self_amount {
                     self_amount -= 1;
@@ -298,17 +1345,93 @@ impl<P: PassableChecker + Clone + 'static> FlowMap<P> {
                 }
             }
 
-            self.amounts.insert((tile_x, tile_y), self_amount);
+            amounts.insert((tile_x, tile_y), self_amount);
         }
+
+        amounts.retain(|_, amt| *amt != 0);
     }
 
-    /// Delete unused squares to save space
-    fn cleanup(&mut self) {
-        self.amounts.retain(|_, amt| *amt != 0)
+    #[test]
+    fn gas_flow_matches_sorted_tile_order() {
+        // several chunks each way, either side of zero
+        let bounds = WorldBounds {
+            x_min: -20,
+            y_min: -35,
+            x_max: 30,
+            y_max: 10,
+        };
+
+        for seed in 0..5 {
+            let mut rng = StdRng::seed_from_u64(200 + seed);
+            let mut tiles = Grid::new(bounds, DEFAULT_TILE);
+
+            for x in bounds.x_min..bounds.x_max {
+                for y in bounds.y_min..bounds.y_max {
+                    let tile = if rng.gen_bool(0.7) { Tile::Open } else { Tile::Wall };
+                    tiles.set(x, y, tile);
+                }
+            }
+
+            let sources: Vec<(i32, i32)> = (0..8)
+                .map(|_| (rng.gen_range(bounds.x_min..bounds.x_max), rng.gen_range(bounds.y_min..bounds.y_max)))
+                .filter(|(x, y)| GasFlow.is_passable(tiles.get(*x, *y)))
+                .collect();
+
+            let mut gas = FlowMap::new(bounds, GasFlow, Neighborhood::FourWay, 6, 1);
+            let mut expected: HashMap<(i32, i32), i32> = HashMap::new();
+
+            for tick in 0..120 {
+                // sources dry up halfway, so the gas has to clear away as well
+                if tick < 60 {
+                    for (x, y) in sources.iter().copied() {
+                        gas.add_amount(x, y, 10);
+                        *expected.entry((x, y)).or_insert(0) += 10;
+                    }
+                }
+
+                gas.tick(&tiles, &|_| false);
+                hash_map_gas_tick(&mut expected, &tiles, 6, 1);
+
+                for x in bounds.x_min..bounds.x_max {
+                    for y in bounds.y_min..bounds.y_max {
+                        assert_eq!(
+                            gas.amounts.get(x, y),
+                            expected.get(&(x, y)).copied().unwrap_or(0),
+                            "seed {}, tick {}: gas disagrees at ({}, {})",
+                            seed,
+                            tick,
+                            x,
+                            y
+                        );
+                    }
+                }
+            }
+        }
     }
 
-    pub fn add_amount(&mut self, tile_x: i32, tile_y: i32, to_add: i32) {
-        let amount = self.amounts.entry((tile_x, tile_y)).or_insert(0);
-        *amount = (*amount).saturating_add(to_add);
+    #[test]
+    fn cores_can_only_go_if_every_spawn_reaches_another() {
+        let mut map = Map::new(BOUNDS);
+
+        // a corridor along y = 0 with a spawn in the middle, a core at either end, and a third
+        // core walled off below
+        for x in 0..7 {
+            map.set_tile(x, 0, Tile::Open);
+        }
+        map.set_tile(3, 4, Tile::Open);
+        map.set_spawns(vec![(3, 0)].into_iter().collect());
+        map.set_cores(vec![(0, 0), (6, 0), (3, 4)].into_iter().collect());
+
+        assert!(map.can_remove_core(0, 0));
+        assert!(map.can_remove_core(6, 0));
+        assert!(map.can_remove_core(3, 4));
+
+        map.set_cores(vec![(0, 0), (3, 4)].into_iter().collect());
+        assert!(!map.can_remove_core(0, 0));
+        assert!(map.can_remove_core(3, 4));
+
+        // with no spawns, nothing can be cut off
+        map.set_spawns(BTreeSet::new());
+        assert!(map.can_remove_core(0, 0));
     }
 }
@@ -0,0 +1,62 @@
+use std::collections::{BTreeMap, HashMap};
+
+use super::{MovementKind, Tile};
+
+/// Something worked out from a key, kept until it's asked for with a different key. For previews
+/// which are drawn every frame but take real work to figure out.
+#[derive(Clone, Debug)]
+pub struct Memo<K, V> {
+    entry: Option<(K, V)>,
+}
+
+impl<K, V> Default for Memo<K, V> {
+    fn default() -> Self {
+        Memo { entry: None }
+    }
+}
+
+impl<K: PartialEq, V> Memo<K, V> {
+    /// The value for the key; worked out with `compute` unless it was the last key asked for too
+    pub fn get_or_compute(&mut self, key: K, compute: impl FnOnce(&K) -> V) -> &V {
+        let fresh = matches!(&self.entry, Some((old_key, _)) if *old_key == key);
+
+        if !fresh {
+            let value = compute(&key);
+            self.entry = Some((key, value));
+        }
+
+        &self.entry.as_ref().unwrap().1
+    }
+}
+
+/// Gas sources (position and amount), as `Map::preview_gas` takes them
+pub type GasSources = Vec<((i32, i32), i32)>;
+
+/// Where gas from the previewed sources settles, by the steady sources, the bursts and the map
+/// revision it was worked out for
+pub type GasPreview = Memo<(GasSources, GasSources, u64), HashMap<(i32, i32), i32>>;
+
+/// The route from each spawn, for each movement kind, after a hovered tile change; by the tile,
+/// what it would become and the map revision it was worked out for
+pub type RoutePreview = Memo<((i32, i32), Tile, u64), BTreeMap<((i32, i32), MovementKind), Vec<(i32, i32)>>>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_computes_again_for_a_new_key() {
+        let mut memo: Memo<i32, i32> = Memo::default();
+        let mut computed = 0;
+
+        for key in [1, 1, 1, 2, 2, 1].iter().copied() {
+            let value = *memo.get_or_compute(key, |key| {
+                computed += 1;
+                key * 10
+            });
+            assert_eq!(value, key * 10);
+        }
+
+        assert_eq!(computed, 3);
+    }
+}
@@ -1,20 +1,36 @@
-use std::collections::{BTreeMap, HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 
 use serde::Deserialize;
 
 use legion::Entity;
 
+mod campaign;
+mod floating_labels;
+mod grid;
+mod key_map;
 mod map;
-
+mod memo;
+mod paint;
+mod particles;
+mod spatial_hash;
+mod stats;
+mod undo;
+mod waves;
+
+pub use campaign::{LayoutCell, Level, LevelFailure, ScriptedWave, VictoryCondition, LEVELS, LEVEL_MARGIN};
+pub use floating_labels::{FloatingLabel, FloatingLabels, LabelKind};
+pub use grid::WorldBounds;
+pub use key_map::{HeldActions, InputAction, KeyMap, ALL_INPUT_ACTIONS};
 pub use map::*;
-
-#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
-pub struct KeysPressed {
-    pub down: bool,
-    pub up: bool,
-    pub left: bool,
-    pub right: bool,
-}
+pub use memo::{GasPreview, GasSources, Memo, RoutePreview};
+pub use paint::{PaintPlan, PaintRejection, PaintState, PaintTool, PaintedTile};
+pub use particles::{Particle, Particles};
+pub use spatial_hash::SpatialHash;
+pub use stats::{DamageSource, ResourceCategory, RunStats, WaveStats};
+pub use undo::{SoldStructure, TickCount, UndoAction, UndoGroup, UndoStack, UNDO_GRACE_TICKS};
+pub use waves::{
+    Affix, MobDesc, MobKind, MobTrait, PlannedMob, WavePlan, ARMOR_PERCENT, BOSS_WAVE_INTERVAL, FAST_SPEED_PERCENT, REGENERATION_PER_TICK,
+};
 
 #[derive(Clone, Eq, PartialEq, Debug, Default)]
 pub struct MenuCollapseStates {
@@ -43,13 +59,60 @@ pub enum TdTileSelect {
         y: i32,
         structures: Vec<SelectedStructure>,
     },
+    /// Two or more tiles at once; the structures are those on any of them
+    Many {
+        tiles: BTreeSet<(i32, i32)>,
+        structures: Vec<SelectedStructure>,
+    },
 }
 
 impl TdTileSelect {
+    /// Select exactly these tiles; the structures on them are filled in on the next tick
+    pub fn of_tiles(tiles: BTreeSet<(i32, i32)>) -> TdTileSelect {
+        match tiles.len() {
+            0 => TdTileSelect::None,
+            1 => {
+                let (x, y) = *tiles.iter().next().unwrap();
+                TdTileSelect::Selected {
+                    x,
+                    y,
+                    structures: Vec::new(),
+                }
+            }
+            _ => TdTileSelect::Many {
+                tiles,
+                structures: Vec::new(),
+            },
+        }
+    }
+
     pub fn is_selected(&self) -> bool {
         match self {
             TdTileSelect::None => false,
-            TdTileSelect::Selected { .. } => true,
+            TdTileSelect::Selected { .. } | TdTileSelect::Many { .. } => true,
+        }
+    }
+
+    pub fn contains(&self, tile_x: i32, tile_y: i32) -> bool {
+        match self {
+            TdTileSelect::None => false,
+            TdTileSelect::Selected { x, y, .. } => (*x, *y) == (tile_x, tile_y),
+            TdTileSelect::Many { tiles, .. } => tiles.contains(&(tile_x, tile_y)),
+        }
+    }
+
+    pub fn tiles(&self) -> BTreeSet<(i32, i32)> {
+        match self {
+            TdTileSelect::None => BTreeSet::new(),
+            TdTileSelect::Selected { x, y, .. } => std::iter::once((*x, *y)).collect(),
+            TdTileSelect::Many { tiles, .. } => tiles.clone(),
+        }
+    }
+
+    pub fn structures(&self) -> &[SelectedStructure] {
+        match self {
+            TdTileSelect::None => &[],
+            TdTileSelect::Selected { structures, .. } | TdTileSelect::Many { structures, .. } => structures,
         }
     }
 }
@@ -57,6 +120,8 @@ impl TdTileSelect {
 #[derive(Clone, Eq, PartialEq, Debug)]
 pub struct SelectedStructure {
     pub entity: Entity,
+    /// Which of the selected tiles it's on
+    pub tile: (i32, i32),
     pub kind: StructureKind,
     pub sell_value: Option<OwnedResources>,
 }
@@ -67,9 +132,29 @@ impl Default for TdTileSelect {
     }
 }
 
+/// The build or tile change option the mouse is over, if any, so what it would do can be
+/// previewed on the map
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub enum TdBuildHover {
+    #[default]
+    None,
+    Build {
+        x: i32,
+        y: i32,
+        kind: StructureKind,
+    },
+    ChangeTile {
+        x: i32,
+        y: i32,
+        tile: Tile,
+    },
+}
+
 #[derive(Deserialize, Copy, Clone, Eq, PartialEq, Debug, Hash, Ord, PartialOrd)]
 pub enum StructureKind {
     GasTrap,
+    Core,
+    Spawn,
 }
 
 #[derive(Deserialize, Copy, Clone, Eq, PartialEq, Debug, Hash, Ord, PartialOrd)]
@@ -94,6 +179,25 @@ pub const ALL_RESOURCES: &[OwnedResource] = &[OwnedResource::Wood, OwnedResource
 #[derive(Deserialize, Clone, Eq, PartialEq, Debug, Default)]
 pub struct OwnedResources(pub BTreeMap<OwnedResource, i64>);
 
+/// e.g. "10 Wood, 5 Money"; resources with none are left out, and if that's all of them, "nothing"
+impl std::fmt::Display for OwnedResources {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let parts: Vec<String> = ALL_RESOURCES
+            .iter()
+            .filter_map(|o| match self.0.get(o).copied().unwrap_or(0) {
+                0 => None,
+                amt => Some(format!("{} {}", amt, o)),
+            })
+            .collect();
+
+        if parts.is_empty() {
+            write!(f, "nothing")
+        } else {
+            write!(f, "{}", parts.join(", "))
+        }
+    }
+}
+
 #[derive(Deserialize, Copy, Clone, Eq, PartialEq, Debug)]
 pub struct NextWaveState {
     /// If true, will launch the wave as soon as it's available
@@ -104,6 +208,69 @@ pub struct NextWaveState {
     pub delay_ticks: usize,
 }
 
+/// How fast the game runs; the clock is the same, but each time it fires, this many ticks are run
+pub const GAME_SPEEDS: &[u32] = &[1, 2, 4];
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct GameSpeed {
+    pub paused: bool,
+    pub ticks_per_step: u32,
+}
+
+impl Default for GameSpeed {
+    fn default() -> Self {
+        GameSpeed {
+            paused: false,
+            ticks_per_step: GAME_SPEEDS[0],
+        }
+    }
+}
+
+impl GameSpeed {
+    /// Move on to the next speed up, wrapping back around to the slowest
+    pub fn cycle(&mut self) {
+        let index = GAME_SPEEDS.iter().position(|speed| *speed == self.ticks_per_step).unwrap_or(0);
+        self.ticks_per_step = GAME_SPEEDS[(index + 1) % GAME_SPEEDS.len()];
+    }
+}
+
+/// A rectangle of tiles, both corners included
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct TileRect {
+    pub x_min: i32,
+    pub y_min: i32,
+    pub x_max: i32,
+    pub y_max: i32,
+}
+
+impl TileRect {
+    /// The rectangle with these two tiles at opposite corners, whichever way round they are
+    pub fn from_corners((x1, y1): (i32, i32), (x2, y2): (i32, i32)) -> TileRect {
+        TileRect {
+            x_min: x1.min(x2),
+            y_min: y1.min(y2),
+            x_max: x1.max(x2),
+            y_max: y1.max(y2),
+        }
+    }
+
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        self.x_min <= x && x <= self.x_max && self.y_min <= y && y <= self.y_max
+    }
+
+    pub fn tiles(self) -> impl Iterator<Item = (i32, i32)> {
+        (self.y_min..=self.y_max).flat_map(move |y| (self.x_min..=self.x_max).map(move |x| (x, y)))
+    }
+}
+
+/// The rectangle the player is dragging out to add to the selection, if they are
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub struct TdSelectDrag(pub Option<TileRect>);
+
+/// The kind of structure the player built most recently, if any
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub struct LastBuilt(pub Option<StructureKind>);
+
 impl Default for NextWaveState {
     fn default() -> Self {
         NextWaveState {
@@ -159,24 +326,66 @@ impl OwnedResources {
     }
 }
 
-#[derive(Copy, Clone, Eq, PartialEq)]
-pub struct PlayerHealth {
-    pub health: i32,
-    pub max: i32,
+pub const MIN_ZOOM: f64 = 0.5;
+pub const MAX_ZOOM: f64 = 3.;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct TdCamera {
+    /// Top pixel on camera, in world pixels
+    pub top: i32,
+    /// Leftmost pixel on camera, in world pixels
+    pub left: i32,
+    /// Screen pixels per world pixel
+    pub zoom: f64,
+    /// How big the canvas is on screen, in screen pixels; kept up to date by the view, so nothing
+    /// else has to ask the page
+    pub screen_width: f64,
+    pub screen_height: f64,
 }
 
-impl Default for PlayerHealth {
+impl Default for TdCamera {
     fn default() -> Self {
-        PlayerHealth { health: 20, max: 20 }
+        TdCamera {
+            top: 0,
+            left: 0,
+            zoom: 1.,
+            screen_width: 0.,
+            screen_height: 0.,
+        }
     }
 }
 
-#[derive(Copy, Clone, Eq, PartialEq, Default)]
-pub struct TdCamera {
-    /// Top pixel on camera
-    pub top: i32,
-    /// Leftmost pixel on camera
-    pub left: i32,
+impl TdCamera {
+    /// The world pixel under the given pixel of the canvas
+    pub fn screen_to_world(&self, screen_x: f64, screen_y: f64) -> (i32, i32) {
+        (
+            self.left + (screen_x / self.zoom).floor() as i32,
+            self.top + (screen_y / self.zoom).floor() as i32,
+        )
+    }
+
+    /// How many world pixels fit across the given number of screen pixels
+    pub fn screen_to_world_len(&self, screen_len: f64) -> i32 {
+        (screen_len / self.zoom).ceil() as i32
+    }
+
+    /// Move the camera so the given world pixel is in the middle of the canvas
+    pub fn center_on(&mut self, world_x: i32, world_y: i32) {
+        self.left = world_x - self.screen_to_world_len(self.screen_width) / 2;
+        self.top = world_y - self.screen_to_world_len(self.screen_height) / 2;
+    }
+
+    /// Multiply the zoom by the given factor (within limits), keeping the world pixel under the
+    /// given pixel of the canvas where it is
+    pub fn zoom_around(&mut self, screen_x: f64, screen_y: f64, factor: f64) {
+        let world_x = self.left as f64 + screen_x / self.zoom;
+        let world_y = self.top as f64 + screen_y / self.zoom;
+
+        self.zoom = (self.zoom * factor).clamp(MIN_ZOOM, MAX_ZOOM);
+
+        self.left = (world_x - screen_x / self.zoom).round() as i32;
+        self.top = (world_y - screen_y / self.zoom).round() as i32;
+    }
 }
 
 #[derive(Deserialize, Clone, Eq, PartialEq)]
@@ -243,4 +452,18 @@ pub enum GameState {
     Opening,
     MainGame,
     Died,
+    /// A campaign level was won
+    Won,
+}
+
+/// Which rules the waves follow; picked when the game starts
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub enum GameMode {
+    /// The same waves every game
+    #[default]
+    Classic,
+    /// Waves which keep getting harder, made up from the seed, so the same seed gives the same run
+    Endless { seed: u64 },
+    /// One of the campaign's levels, by its index in `LEVELS`
+    Campaign { level: usize },
 }
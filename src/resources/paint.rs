@@ -0,0 +1,238 @@
+use std::collections::{HashMap, HashSet};
+
+use super::{Map, OwnedResources, StructureBuilds, StructureKind, Tile, TileTransforms};
+
+/// What dragging across the map does, when a tool is picked
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum PaintTool {
+    ChangeTile(Tile),
+    Build(StructureKind),
+}
+
+/// Why a tile in a stroke is left alone
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum PaintRejection {
+    /// The tool does nothing to this kind of tile
+    NotAllowed,
+    /// There's a structure in the way
+    Occupied,
+    /// Along with the tiles before it in the stroke, it would cut a spawn off from the cores
+    BlocksPath,
+    /// Along with the tiles before it in the stroke, it costs more than the player has
+    CannotAfford,
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct PaintedTile {
+    pub x: i32,
+    pub y: i32,
+    pub result: Result<OwnedResources, PaintRejection>,
+}
+
+/// What a stroke would do, tile by tile, if it were let go now
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct PaintPlan {
+    pub tiles: Vec<PaintedTile>,
+    /// What all the accepted tiles cost together
+    pub total: OwnedResources,
+}
+
+impl PaintPlan {
+    /// Work out what doing the tool to each of the tiles would do, in order. Each tile is judged
+    /// as if the ones before it had already been done, so a stroke can't wall off a path one
+    /// harmless-looking tile at a time, or spend the same money twice. With no budget, cost is
+    /// left out of it.
+    pub fn new(
+        tool: PaintTool,
+        tiles: &[(i32, i32)],
+        map: &Map,
+        transforms: &TileTransforms,
+        builds: &StructureBuilds,
+        occupied: &HashSet<(i32, i32)>,
+        budget: Option<&OwnedResources>,
+    ) -> PaintPlan {
+        // tile changes accepted so far, which each later one is judged alongside; builds don't
+        // change the map, so they don't add any
+        let mut pending: HashMap<(i32, i32), Tile> = HashMap::new();
+        let mut remaining = budget.cloned();
+        let mut plan = PaintPlan::default();
+
+        for &(x, y) in tiles {
+            let result = if occupied.contains(&(x, y)) {
+                Err(PaintRejection::Occupied)
+            } else {
+                match tool {
+                    PaintTool::ChangeTile(target) => {
+                        let tile = pending.get(&(x, y)).copied().unwrap_or_else(|| map.get_tile(x, y));
+
+                        match transforms.list_all_for(tile).remove(&target) {
+                            None => Err(PaintRejection::NotAllowed),
+                            Some(_) if !map.can_set_tile_after(&pending, x, y, target) => Err(PaintRejection::BlocksPath),
+                            Some(cost) if !can_pay(&remaining, &cost) => Err(PaintRejection::CannotAfford),
+                            Some(cost) => {
+                                pending.insert((x, y), target);
+                                Ok(cost)
+                            }
+                        }
+                    }
+                    PaintTool::Build(kind) => match builds.list_all_for(map.get_tile(x, y)).remove(&kind) {
+                        None => Err(PaintRejection::NotAllowed),
+                        Some(_) if kind == StructureKind::Spawn && !map.can_add_spawn(x, y) => Err(PaintRejection::BlocksPath),
+                        Some(cost) if !can_pay(&remaining, &cost) => Err(PaintRejection::CannotAfford),
+                        Some(cost) => Ok(cost),
+                    },
+                }
+            };
+
+            if let Ok(cost) = &result {
+                if let Some(remaining) = remaining.as_mut() {
+                    remaining.pay(cost);
+                }
+                plan.total.receive_all(cost);
+            }
+
+            plan.tiles.push(PaintedTile { x, y, result });
+        }
+
+        plan
+    }
+
+    pub fn accepted(&self) -> impl Iterator<Item = (i32, i32, &OwnedResources)> + '_ {
+        self.tiles.iter().filter_map(|t| match &t.result {
+            Ok(cost) => Some((t.x, t.y, cost)),
+            Err(_) => None,
+        })
+    }
+
+    pub fn all_accepted(&self) -> bool {
+        self.tiles.iter().all(|t| t.result.is_ok())
+    }
+
+    pub fn rejected_for(&self, rejection: PaintRejection) -> usize {
+        self.tiles.iter().filter(|t| t.result == Err(rejection)).count()
+    }
+}
+
+fn can_pay(budget: &Option<OwnedResources>, cost: &OwnedResources) -> bool {
+    budget.as_ref().is_none_or(|budget| budget.can_pay(cost))
+}
+
+/// The tool the player has picked, and the tiles they've dragged it over so far
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct PaintState {
+    pub tool: Option<PaintTool>,
+    /// Tiles in the order they were painted, each only once; empty when not dragging
+    pub stroke: Vec<(i32, i32)>,
+    /// The tile under the mouse, so a single tile can be previewed before the drag starts
+    pub hover: Option<(i32, i32)>,
+}
+
+impl PaintState {
+    pub fn add_to_stroke(&mut self, x: i32, y: i32) {
+        if !self.stroke.contains(&(x, y)) {
+            self.stroke.push((x, y));
+        }
+    }
+
+    /// Work out what the stroke would do; or, between strokes, what painting the hovered tile
+    /// would
+    pub fn plan(
+        &self,
+        map: &Map,
+        transforms: &TileTransforms,
+        builds: &StructureBuilds,
+        occupied: &HashSet<(i32, i32)>,
+        owned: &OwnedResources,
+    ) -> PaintPlan {
+        let tool = match self.tool {
+            Some(tool) => tool,
+            None => return PaintPlan::default(),
+        };
+
+        let tiles: &[(i32, i32)] = match (self.stroke.is_empty(), self.hover.as_ref()) {
+            (false, _) => &self.stroke,
+            (true, Some(hover)) => std::slice::from_ref(hover),
+            (true, None) => &[],
+        };
+
+        PaintPlan::new(tool, tiles, map, transforms, builds, occupied, Some(owned))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resources::{OwnedResource, TileTransformDesc, WorldBounds};
+
+    const BOUNDS: WorldBounds = WorldBounds {
+        x_min: 0,
+        y_min: 0,
+        x_max: 8,
+        y_max: 4,
+    };
+
+    /// A loop of open ground with the spawn at the top and the core at the bottom, so there's a
+    /// way round either side
+    fn looped_map() -> Map {
+        let mut map = Map::new(BOUNDS);
+        for x in 0..7 {
+            map.set_tile(x, 0, Tile::Open);
+            map.set_tile(x, 2, Tile::Open);
+        }
+        map.set_tile(0, 1, Tile::Open);
+        map.set_tile(6, 1, Tile::Open);
+        map.set_spawns(vec![(3, 0)].into_iter().collect());
+        map.set_cores(vec![(3, 2)].into_iter().collect());
+        map
+    }
+
+    fn walls_for(cost: i64) -> TileTransforms {
+        let mut transforms = TileTransforms::new();
+        transforms.add(TileTransformDesc {
+            source: Tile::Open,
+            target: Tile::Wall,
+            cost: OwnedResources::new().with(OwnedResource::Money, cost),
+        });
+        transforms
+    }
+
+    fn plan(map: &Map, tiles: &[(i32, i32)], budget: Option<&OwnedResources>) -> PaintPlan {
+        PaintPlan::new(
+            PaintTool::ChangeTile(Tile::Wall),
+            tiles,
+            map,
+            &walls_for(3),
+            &StructureBuilds::new(),
+            &HashSet::new(),
+            budget,
+        )
+    }
+
+    #[test]
+    fn walls_which_only_cut_off_a_spawn_together_are_caught() {
+        let map = looped_map();
+
+        assert!(plan(&map, &[(2, 0)], None).all_accepted());
+        assert!(plan(&map, &[(4, 0)], None).all_accepted());
+
+        let both = plan(&map, &[(2, 0), (4, 0)], None);
+        assert!(both.tiles[0].result.is_ok());
+        assert_eq!(both.tiles[1].result, Err(PaintRejection::BlocksPath));
+        assert_eq!(both.rejected_for(PaintRejection::BlocksPath), 1);
+        assert_eq!(both.total, OwnedResources::new().with(OwnedResource::Money, 3));
+    }
+
+    #[test]
+    fn the_budget_runs_down_along_the_stroke() {
+        let map = looped_map();
+        let budget = OwnedResources::new().with(OwnedResource::Money, 7);
+
+        // all down the left side, which the right side can do without
+
+        let stroke = plan(&map, &[(1, 0), (0, 1), (1, 2)], Some(&budget));
+        assert!(stroke.tiles[0].result.is_ok());
+        assert!(stroke.tiles[1].result.is_ok());
+        assert_eq!(stroke.tiles[2].result, Err(PaintRejection::CannotAfford));
+        assert_eq!(stroke.total, OwnedResources::new().with(OwnedResource::Money, 6));
+    }
+}
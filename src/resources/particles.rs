@@ -0,0 +1,106 @@
+/// Most particles alive at once; past this, new ones replace the oldest, so a big fight costs no
+/// more to tick and draw than a small one
+const MAX_PARTICLES: usize = 1000;
+
+/// Each tick, particles keep this much of their speed
+const PARTICLE_DRAG: f64 = 0.85;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Particle {
+    /// Position in world pixels
+    pub x: f64,
+    pub y: f64,
+    /// Pixels per tick
+    pub vx: f64,
+    pub vy: f64,
+    pub ticks_left: u32,
+    pub lifetime: u32,
+    pub color: &'static str,
+}
+
+impl Particle {
+    /// How far through its life the particle still has to go, from 1 (just made) to 0 (gone)
+    pub fn remaining(&self) -> f64 {
+        self.ticks_left as f64 / self.lifetime.max(1) as f64
+    }
+}
+
+/// Short-lived specks of color, purely for show; they never affect the game. Kept in one flat
+/// buffer rather than as entities, since there are a lot of them and they only ever move.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Particles {
+    particles: Vec<Particle>,
+    // where the next particle goes once the buffer is full
+    next_replaced: usize,
+    // particles only need to look scattered, not be unpredictable, so a fixed seed does
+    rng_state: u64,
+}
+
+impl Default for Particles {
+    fn default() -> Self {
+        Particles {
+            particles: Vec::with_capacity(MAX_PARTICLES),
+            next_replaced: 0,
+            rng_state: 0x2545_f491_4f6c_dd1d,
+        }
+    }
+}
+
+impl Particles {
+    /// Throw `count` particles out from the given point, in random directions at up to `speed`
+    /// pixels per tick, each lasting `lifetime` ticks
+    pub fn burst(&mut self, x: f64, y: f64, count: usize, speed: f64, lifetime: u32, color: &'static str) {
+        for _ in 0..count {
+            let angle = self.next_random() * std::f64::consts::TAU;
+            let speed = speed * (0.5 + 0.5 * self.next_random());
+
+            self.add(Particle {
+                x,
+                y,
+                vx: speed * angle.cos(),
+                vy: speed * angle.sin(),
+                ticks_left: lifetime,
+                lifetime,
+                color,
+            });
+        }
+    }
+
+    fn add(&mut self, particle: Particle) {
+        if self.particles.len() < MAX_PARTICLES {
+            self.particles.push(particle);
+        } else {
+            self.particles[self.next_replaced] = particle;
+            self.next_replaced = (self.next_replaced + 1) % MAX_PARTICLES;
+        }
+    }
+
+    /// Move every particle along, and drop the ones which have run out
+    pub fn tick(&mut self) {
+        for particle in self.particles.iter_mut() {
+            particle.x += particle.vx;
+            particle.y += particle.vy;
+            particle.vx *= PARTICLE_DRAG;
+            particle.vy *= PARTICLE_DRAG;
+            particle.ticks_left = particle.ticks_left.saturating_sub(1);
+        }
+
+        self.particles.retain(|particle| particle.ticks_left > 0);
+        self.next_replaced %= self.particles.len().max(1);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Particle> + '_ {
+        self.particles.iter()
+    }
+
+    /// A number in [0, 1), from a xorshift generator
+    fn next_random(&mut self) -> f64 {
+        let mut x = self.rng_state;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.rng_state = x;
+
+        (x >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
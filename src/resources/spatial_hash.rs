@@ -0,0 +1,46 @@
+use std::collections::HashMap;
+
+/// Buckets values by which cell of a coarse grid their (pixel) position falls in, so everything
+/// near a point can be found without looking at everything else.
+///
+/// Lookups go cell by cell in a fixed order, and values within a cell come back in the order they
+/// were inserted, so results are the same on every run.
+#[derive(Clone, Debug)]
+pub struct SpatialHash<T: Copy> {
+    cell_size: i32,
+    cells: HashMap<(i32, i32), Cell<T>>,
+}
+
+/// Everything in one cell, with its position, in the order it was inserted
+type Cell<T> = Vec<((i32, i32), T)>;
+
+impl<T: Copy> SpatialHash<T> {
+    /// Lookups are cheapest when the cell size is about the distance usually searched
+    pub fn new(cell_size: i32) -> Self {
+        SpatialHash {
+            cell_size: cell_size.max(1),
+            cells: HashMap::new(),
+        }
+    }
+
+    pub fn insert(&mut self, x: i32, y: i32, value: T) {
+        self.cells.entry(self.cell_of(x, y)).or_default().push(((x, y), value));
+    }
+
+    /// Everything in the square reaching `distance` out from the given point on each axis (so
+    /// possibly a little further than `distance` away, at the corners), with its position
+    pub fn near(&self, x: i32, y: i32, distance: i32) -> impl Iterator<Item = ((i32, i32), T)> + '_ {
+        let (x_min, y_min) = self.cell_of(x - distance, y - distance);
+        let (x_max, y_max) = self.cell_of(x + distance, y + distance);
+
+        (y_min..=y_max)
+            .flat_map(move |cell_y| (x_min..=x_max).map(move |cell_x| (cell_x, cell_y)))
+            .filter_map(move |cell| self.cells.get(&cell))
+            .flat_map(|values| values.iter().copied())
+            .filter(move |((vx, vy), _)| (vx - x).abs() <= distance && (vy - y).abs() <= distance)
+    }
+
+    fn cell_of(&self, x: i32, y: i32) -> (i32, i32) {
+        (x.div_euclid(self.cell_size), y.div_euclid(self.cell_size))
+    }
+}
@@ -0,0 +1,153 @@
+use std::collections::BTreeMap;
+
+use super::{OwnedResources, StructureKind};
+
+/// What dealt some damage, as far as can be told
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Hash, Ord, PartialOrd)]
+pub enum DamageSource {
+    Structure(StructureKind),
+    /// Damage which can't be pinned on anything in particular
+    Unknown,
+}
+
+impl std::fmt::Display for DamageSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DamageSource::Structure(kind) => write!(f, "{:?}", kind),
+            DamageSource::Unknown => write!(f, "Unknown"),
+        }
+    }
+}
+
+/// Where resources came from, or went
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Hash, Ord, PartialOrd)]
+pub enum ResourceCategory {
+    MobRewards,
+    Sales,
+    Building(StructureKind),
+    TileChanges,
+}
+
+impl std::fmt::Display for ResourceCategory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ResourceCategory::MobRewards => write!(f, "Mob rewards"),
+            ResourceCategory::Sales => write!(f, "Sales"),
+            ResourceCategory::Building(kind) => write!(f, "Building ({:?})", kind),
+            ResourceCategory::TileChanges => write!(f, "Tile changes"),
+        }
+    }
+}
+
+/// What one structure did over the game. A structure sold and put back on the same tile counts as
+/// the same one.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub struct StructureStats {
+    pub damage: i64,
+    pub kills: usize,
+}
+
+/// How one wave went
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub struct WaveStats {
+    pub spawned: usize,
+    pub killed: usize,
+    /// Mobs which reached a core
+    pub leaked: usize,
+    pub damage: i64,
+}
+
+impl WaveStats {
+    /// Whether every mob in it has been killed or has leaked
+    pub fn is_over(&self) -> bool {
+        self.killed + self.leaked >= self.spawned
+    }
+}
+
+/// Running totals for the whole game, for the summary at the end of it
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct RunStats {
+    /// By wave number
+    pub waves: BTreeMap<usize, WaveStats>,
+    pub damage_by_source: BTreeMap<DamageSource, i64>,
+    pub kills_by_source: BTreeMap<DamageSource, usize>,
+    /// By the kind of structure and the tile it stands on
+    pub by_structure: BTreeMap<(StructureKind, (i32, i32)), StructureStats>,
+    pub earned: BTreeMap<ResourceCategory, OwnedResources>,
+    pub spent: BTreeMap<ResourceCategory, OwnedResources>,
+}
+
+impl RunStats {
+    pub fn wave_mut(&mut self, wave_num: usize) -> &mut WaveStats {
+        self.waves.entry(wave_num).or_default()
+    }
+
+    /// Damage from the given source; if it was a structure, the tile it's on as well, so it can be
+    /// credited to that one structure
+    pub fn add_damage(&mut self, wave_num: usize, source: DamageSource, tile: Option<(i32, i32)>, amount: i64) {
+        self.wave_mut(wave_num).damage += amount;
+        *self.damage_by_source.entry(source).or_insert(0) += amount;
+        if let Some(structure) = self.structure_mut(source, tile) {
+            structure.damage += amount;
+        }
+    }
+
+    pub fn add_kill(&mut self, wave_num: usize, source: DamageSource, tile: Option<(i32, i32)>) {
+        self.wave_mut(wave_num).killed += 1;
+        *self.kills_by_source.entry(source).or_insert(0) += 1;
+        if let Some(structure) = self.structure_mut(source, tile) {
+            structure.kills += 1;
+        }
+    }
+
+    fn structure_mut(&mut self, source: DamageSource, tile: Option<(i32, i32)>) -> Option<&mut StructureStats> {
+        match (source, tile) {
+            (DamageSource::Structure(kind), Some(tile)) => Some(self.by_structure.entry((kind, tile)).or_default()),
+            _ => None,
+        }
+    }
+
+    pub fn add_earned(&mut self, category: ResourceCategory, amount: &OwnedResources) {
+        self.earned.entry(category).or_default().receive_all(amount);
+    }
+
+    pub fn add_spent(&mut self, category: ResourceCategory, amount: &OwnedResources) {
+        self.spent.entry(category).or_default().receive_all(amount);
+    }
+
+    /// Take back earnings which were undone
+    pub fn remove_earned(&mut self, category: ResourceCategory, amount: &OwnedResources) {
+        self.earned.entry(category).or_default().pay(amount);
+    }
+
+    /// Take back spending which was undone
+    pub fn remove_spent(&mut self, category: ResourceCategory, amount: &OwnedResources) {
+        self.spent.entry(category).or_default().pay(amount);
+    }
+
+    /// Waves whose every mob was dealt with, one way or another
+    pub fn waves_survived(&self) -> usize {
+        self.waves.values().filter(|wave| wave.is_over()).count()
+    }
+
+    pub fn total_killed(&self) -> usize {
+        self.waves.values().map(|wave| wave.killed).sum()
+    }
+
+    pub fn total_leaked(&self) -> usize {
+        self.waves.values().map(|wave| wave.leaked).sum()
+    }
+
+    /// Everything spent, whatever on
+    pub fn total_spent(&self) -> OwnedResources {
+        let mut total = OwnedResources::new();
+        for spent in self.spent.values() {
+            total.receive_all(spent);
+        }
+        total
+    }
+
+    pub fn total_damage(&self) -> i64 {
+        self.waves.values().map(|wave| wave.damage).sum()
+    }
+}
@@ -0,0 +1,217 @@
+use legion::Entity;
+
+use super::{OwnedResources, StructureKind, Tile};
+use crate::components::{Core, DamageDealt, OnDeath, PoisonGasTrap, Position, Renderable, SellValue, Spawn, Structure};
+
+/// While a wave is on, how many ticks after doing something it can still be undone
+pub const UNDO_GRACE_TICKS: u64 = 60;
+/// Most groups of actions remembered; past this, the oldest are forgotten
+const MAX_UNDO_GROUPS: usize = 100;
+
+/// How many ticks the game has run; doesn't move while paused
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub struct TickCount(pub u64);
+
+/// Everything a sold structure was, so it can be put back just as it was
+#[derive(Clone, PartialEq, Debug)]
+pub struct SoldStructure {
+    pub position: Position,
+    pub structure: Structure,
+    pub core: Option<Core>,
+    pub spawn: Option<Spawn>,
+    pub trap: Option<PoisonGasTrap>,
+    pub renderable: Option<Renderable>,
+    pub sell_value: Option<SellValue>,
+    pub on_death: Option<OnDeath>,
+    pub damage_dealt: Option<DamageDealt>,
+}
+
+/// Something the player did, with exactly what it cost or paid out
+#[derive(Clone, PartialEq, Debug)]
+pub enum UndoAction {
+    ChangeTile {
+        x: i32,
+        y: i32,
+        before: Tile,
+        after: Tile,
+        paid: OwnedResources,
+    },
+    Build {
+        entity: Entity,
+        kind: StructureKind,
+        tile: (i32, i32),
+        paid: OwnedResources,
+    },
+    Sell {
+        /// What it was before it was sold
+        entity: Entity,
+        sold: SoldStructure,
+        received: OwnedResources,
+    },
+}
+
+/// Actions which happened in the same step, like all the tiles in one stroke; they're undone
+/// together
+#[derive(Clone, PartialEq, Debug)]
+pub struct UndoGroup {
+    /// When it happened
+    pub tick: u64,
+    /// In the order they happened
+    pub actions: Vec<UndoAction>,
+}
+
+/// What the player has done recently, newest last
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct UndoStack {
+    groups: Vec<UndoGroup>,
+    // whether actions recorded now go in with the newest group
+    open: bool,
+}
+
+impl UndoStack {
+    pub fn record(&mut self, tick: u64, action: UndoAction) {
+        match self.groups.last_mut() {
+            Some(group) if self.open => group.actions.push(action),
+            _ => {
+                if self.groups.len() >= MAX_UNDO_GROUPS {
+                    self.groups.remove(0);
+                }
+
+                self.groups.push(UndoGroup {
+                    tick,
+                    actions: vec![action],
+                });
+                self.open = true;
+            }
+        }
+    }
+
+    /// Anything recorded after this starts a new group
+    pub fn close_group(&mut self) {
+        self.open = false;
+    }
+
+    pub fn len(&self) -> usize {
+        self.groups.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.groups.is_empty()
+    }
+
+    /// Whether the newest group can be undone now; always between waves, and only just after it
+    /// happened during one
+    pub fn can_undo(&self, now: u64, wave_active: bool) -> bool {
+        match self.groups.last() {
+            Some(group) => !wave_active || now.saturating_sub(group.tick) <= UNDO_GRACE_TICKS,
+            None => false,
+        }
+    }
+
+    pub fn last(&self) -> Option<&UndoGroup> {
+        self.groups.last()
+    }
+
+    pub fn pop(&mut self) -> Option<UndoGroup> {
+        self.open = false;
+        self.groups.pop()
+    }
+
+    /// A sold structure which was put back is a new entity; anything older which refers to the
+    /// old one should refer to the new one instead
+    pub fn replace_entity(&mut self, old: Entity, new: Entity) {
+        for group in self.groups.iter_mut() {
+            for action in group.actions.iter_mut() {
+                if let UndoAction::Build { entity, .. } = action {
+                    if *entity == old {
+                        *entity = new;
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use legion::World;
+
+    use super::*;
+
+    fn change(x: i32) -> UndoAction {
+        UndoAction::ChangeTile {
+            x,
+            y: 0,
+            before: Tile::Wall,
+            after: Tile::Open,
+            paid: OwnedResources::new(),
+        }
+    }
+
+    #[test]
+    fn actions_group_until_closed() {
+        let mut stack = UndoStack::default();
+
+        stack.record(1, change(0));
+        stack.record(2, change(1));
+        assert_eq!(stack.len(), 1);
+        assert_eq!(stack.last().unwrap().tick, 1);
+        assert_eq!(stack.last().unwrap().actions, vec![change(0), change(1)]);
+
+        stack.close_group();
+        stack.record(3, change(2));
+        assert_eq!(stack.len(), 2);
+        assert_eq!(stack.last().unwrap().actions, vec![change(2)]);
+
+        // once a group is taken back, nothing more can be added to it
+        assert_eq!(stack.pop().unwrap().actions, vec![change(2)]);
+        stack.record(4, change(3));
+        assert_eq!(stack.len(), 2);
+        assert_eq!(stack.last().unwrap().tick, 4);
+    }
+
+    #[test]
+    fn only_so_many_groups_are_kept() {
+        let mut stack = UndoStack::default();
+
+        for x in 0..MAX_UNDO_GROUPS as i32 + 5 {
+            stack.record(x as u64, change(x));
+            stack.close_group();
+        }
+
+        assert_eq!(stack.len(), MAX_UNDO_GROUPS);
+        assert_eq!(stack.groups[0].actions, vec![change(5)]);
+    }
+
+    #[test]
+    fn undo_during_a_wave_only_within_the_grace_period() {
+        let mut stack = UndoStack::default();
+        assert!(!stack.can_undo(0, false));
+
+        stack.record(100, change(0));
+
+        assert!(stack.can_undo(100, true));
+        assert!(stack.can_undo(100 + UNDO_GRACE_TICKS, true));
+        assert!(!stack.can_undo(100 + UNDO_GRACE_TICKS + 1, true));
+        assert!(stack.can_undo(100 + UNDO_GRACE_TICKS * 10, false));
+    }
+
+    #[test]
+    fn put_back_structures_are_followed() {
+        let mut world = World::default();
+        let (old, new, other) = (world.push(()), world.push(()), world.push(()));
+        let build = |entity| UndoAction::Build {
+            entity,
+            kind: StructureKind::GasTrap,
+            tile: (0, 0),
+            paid: OwnedResources::new(),
+        };
+
+        let mut stack = UndoStack::default();
+        stack.record(0, build(old));
+        stack.record(0, build(other));
+        stack.replace_entity(old, new);
+
+        assert_eq!(stack.last().unwrap().actions, vec![build(new), build(other)]);
+    }
+}
@@ -0,0 +1,483 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use super::{GameMode, MovementKind, OwnedResource, OwnedResources, ScriptedWave, LEVELS};
+use crate::components::{Spawn, SpriteId};
+
+/// Ticks after the last mob of a wave sets off before the next wave can be launched
+const WAVE_COOLDOWN_TICKS: usize = 20;
+
+/// In endless mode, every this many waves is a boss wave
+pub const BOSS_WAVE_INTERVAL: usize = 10;
+/// In endless mode, mobs never come out closer together than this
+const MIN_TICKS_PER_MOB: usize = 10;
+/// Most affixes one wave can have
+const MAX_AFFIXES: usize = 3;
+
+/// The kinds of mob a wave can send
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Hash, Ord, PartialOrd)]
+pub enum MobKind {
+    Walker,
+    Swimmer,
+    /// Never in classic games; in endless mode, one from each spawn every boss wave
+    Boss,
+}
+
+/// Everything about a kind of mob which doesn't change from one to the next
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct MobDesc {
+    pub name: &'static str,
+    pub plural: &'static str,
+    pub movement: MovementKind,
+    /// Also how wide it's drawn
+    pub radius: i32,
+    /// Pixels per tick, at full speed
+    pub speed: f64,
+    pub sprite: SpriteId,
+    pub health: i32,
+    pub breathes: bool,
+    pub reward: (OwnedResource, i64),
+    /// How much health a core loses when it gets there
+    pub core_damage: i32,
+}
+
+impl MobKind {
+    pub fn desc(self) -> MobDesc {
+        match self {
+            MobKind::Walker => MobDesc {
+                name: "Walker",
+                plural: "Walkers",
+                movement: MovementKind::Walker,
+                radius: 10,
+                speed: 2.,
+                sprite: SpriteId::WALKER,
+                health: 100,
+                breathes: true,
+                reward: (OwnedResource::Money, 5),
+                core_damage: 1,
+            },
+            // drawn a little smaller (and moves a little slower) so you can tell them apart
+            MobKind::Swimmer => MobDesc {
+                name: "Swimmer",
+                plural: "Swimmers",
+                movement: MovementKind::Swimmer,
+                radius: 8,
+                speed: 1.6,
+                sprite: SpriteId::SWIMMER,
+                health: 100,
+                breathes: true,
+                reward: (OwnedResource::Money, 5),
+                core_damage: 1,
+            },
+            MobKind::Boss => MobDesc {
+                name: "Boss",
+                plural: "Bosses",
+                movement: MovementKind::Walker,
+                radius: 10,
+                speed: 1.2,
+                sprite: SpriteId::WALKER,
+                health: 1000,
+                breathes: true,
+                reward: (OwnedResource::Money, 50),
+                core_damage: 5,
+            },
+        }
+    }
+}
+
+/// Something worth knowing about a kind of mob before it arrives
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Hash, Ord, PartialOrd)]
+pub enum MobTrait {
+    /// Can cross water
+    Swims,
+    /// Doesn't need air, so gas doesn't hurt it
+    NonBreathing,
+}
+
+impl MobTrait {
+    pub fn description(self) -> &'static str {
+        match self {
+            MobTrait::Swims => "swims",
+            MobTrait::NonBreathing => "doesn't breathe",
+        }
+    }
+}
+
+impl MobDesc {
+    pub fn traits(&self) -> Vec<MobTrait> {
+        let mut traits = Vec::new();
+
+        if self.movement == MovementKind::Swimmer {
+            traits.push(MobTrait::Swims);
+        }
+        if !self.breathes {
+            traits.push(MobTrait::NonBreathing);
+        }
+
+        traits
+    }
+}
+
+/// Something which makes every mob in an endless wave tougher
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Hash, Ord, PartialOrd)]
+pub enum Affix {
+    /// Moves half again as fast
+    Fast,
+    /// Shrugs off some of each hit
+    Armored,
+    /// Slowly heals
+    Regenerating,
+}
+
+const ALL_AFFIXES: &[Affix] = &[Affix::Fast, Affix::Armored, Affix::Regenerating];
+
+/// How much faster fast mobs are, as a percentage of their usual speed
+pub const FAST_SPEED_PERCENT: i32 = 150;
+/// How much of each hit armored mobs shrug off, as a percentage (rounded down)
+pub const ARMOR_PERCENT: i32 = 40;
+/// How much health regenerating mobs get back each tick
+pub const REGENERATION_PER_TICK: i32 = 1;
+
+impl Affix {
+    pub fn description(self) -> &'static str {
+        match self {
+            Affix::Fast => "fast",
+            Affix::Armored => "armored",
+            Affix::Regenerating => "regenerating",
+        }
+    }
+}
+
+/// One mob a wave will send
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct PlannedMob {
+    pub kind: MobKind,
+    pub spawn_tile: (i32, i32),
+    /// Ticks after the launch before it sets off
+    pub delay_ticks: usize,
+    /// Whether it goes straight for the main core, even if another is closer
+    pub targets_main_core: bool,
+}
+
+/// Every mob a wave will send, worked out from the spawns which serve it. The same plan is used to
+/// launch the wave and to preview it, so the preview is never wrong.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct WavePlan {
+    pub wave_num: usize,
+    pub mobs: Vec<PlannedMob>,
+    /// Every mob's health, as a percentage of what its kind usually has
+    pub health_percent: i32,
+    /// Every mob's reward, as a percentage of what its kind usually pays
+    pub reward_percent: i32,
+    /// Applied to every mob in the wave
+    pub affixes: Vec<Affix>,
+}
+
+impl WavePlan {
+    /// The spawns don't have to serve the wave; the ones which don't are left out
+    pub fn new(mode: GameMode, wave_num: usize, spawns: &[(Spawn, (i32, i32))]) -> WavePlan {
+        match mode {
+            GameMode::Classic => WavePlan::classic(wave_num, spawns),
+            GameMode::Endless { seed } => WavePlan::endless(seed, wave_num, spawns),
+            // past the level's last wave, nothing more comes
+            GameMode::Campaign { level } => match LEVELS.get(level).and_then(|level| level.wave(wave_num)) {
+                Some(scripted) => WavePlan::scripted(scripted, wave_num, spawns),
+                None => WavePlan {
+                    wave_num,
+                    mobs: Vec::new(),
+                    health_percent: 100,
+                    reward_percent: 100,
+                    affixes: Vec::new(),
+                },
+            },
+        }
+    }
+
+    /// Every spawn which serves the wave sends the whole of it
+    fn scripted(scripted: &ScriptedWave, wave_num: usize, spawns: &[(Spawn, (i32, i32))]) -> WavePlan {
+        let mut mobs = Vec::new();
+
+        for (spawn, tile) in spawns.iter().copied() {
+            if !spawn.serves_wave(wave_num) {
+                continue;
+            }
+
+            let kinds = scripted.mobs.iter().flat_map(|&(kind, count)| std::iter::repeat_n(kind, count));
+
+            for (mob_idx, kind) in kinds.enumerate() {
+                mobs.push(PlannedMob {
+                    kind,
+                    spawn_tile: tile,
+                    delay_ticks: mob_idx * spawn.ticks_per_mob,
+                    targets_main_core: mob_idx % 3 == 2,
+                });
+            }
+        }
+
+        WavePlan {
+            wave_num,
+            mobs,
+            health_percent: scripted.health_percent,
+            reward_percent: 100,
+            affixes: scripted.affixes.to_vec(),
+        }
+    }
+
+    fn classic(wave_num: usize, spawns: &[(Spawn, (i32, i32))]) -> WavePlan {
+        let mut mobs = Vec::new();
+
+        for (spawn, tile) in spawns.iter().copied() {
+            if !spawn.serves_wave(wave_num) {
+                continue;
+            }
+
+            for mob_idx in 0..spawn.mobs_per_wave {
+                // every fifth mob from a spawn can swim, and every third goes for the main core
+                let kind = if mob_idx % 5 == 4 { MobKind::Swimmer } else { MobKind::Walker };
+
+                mobs.push(PlannedMob {
+                    kind,
+                    spawn_tile: tile,
+                    delay_ticks: mob_idx * spawn.ticks_per_mob,
+                    targets_main_core: mob_idx % 3 == 2,
+                });
+            }
+        }
+
+        WavePlan {
+            wave_num,
+            mobs,
+            health_percent: 100,
+            reward_percent: 100,
+            affixes: Vec::new(),
+        }
+    }
+
+    /// Each wave is made up from the seed and its number alone, so it comes out the same however
+    /// many times it's looked at, and whatever came before it
+    fn endless(seed: u64, wave_num: usize, spawns: &[(Spawn, (i32, i32))]) -> WavePlan {
+        let mut rng = WaveRng::new(seed, wave_num);
+        let growth = wave_num.saturating_sub(1);
+        let is_boss_wave = wave_num.is_multiple_of(BOSS_WAVE_INTERVAL);
+
+        let extra_mobs = growth / 2 + rng.below(2);
+        // swimmers get more common: every fifth mob at first, down to every other one
+        let swimmer_every = 5 - (growth / 8).min(3);
+
+        let mut mobs = Vec::new();
+
+        for (spawn, tile) in spawns.iter().copied() {
+            if !spawn.serves_wave(wave_num) {
+                continue;
+            }
+
+            let count = spawn.mobs_per_wave + extra_mobs;
+            let ticks_per_mob = spawn.ticks_per_mob.saturating_sub(growth).max(MIN_TICKS_PER_MOB);
+
+            for mob_idx in 0..count {
+                let kind = if mob_idx % swimmer_every == swimmer_every - 1 {
+                    MobKind::Swimmer
+                } else {
+                    MobKind::Walker
+                };
+
+                mobs.push(PlannedMob {
+                    kind,
+                    spawn_tile: tile,
+                    delay_ticks: mob_idx * ticks_per_mob,
+                    targets_main_core: mob_idx % 3 == 2,
+                });
+            }
+
+            // the boss brings up the rear
+            if is_boss_wave {
+                mobs.push(PlannedMob {
+                    kind: MobKind::Boss,
+                    spawn_tile: tile,
+                    delay_ticks: count * ticks_per_mob,
+                    targets_main_core: true,
+                });
+            }
+        }
+
+        // from wave 5, a third of waves get an affix; boss waves always do; and past every 15th
+        // wave, every wave gets another
+        let mut affix_count = wave_num / 15;
+        if wave_num >= 5 && rng.below(3) == 0 {
+            affix_count += 1;
+        }
+        if is_boss_wave {
+            affix_count += 1;
+        }
+
+        let mut remaining: Vec<Affix> = ALL_AFFIXES.to_vec();
+        let mut affixes = Vec::new();
+        while affixes.len() < affix_count.min(MAX_AFFIXES) {
+            affixes.push(remaining.remove(rng.below(remaining.len())));
+        }
+        affixes.sort();
+
+        WavePlan {
+            wave_num,
+            mobs,
+            health_percent: 100 + 15 * growth as i32 + rng.below(10) as i32,
+            reward_percent: 100 + 8 * growth as i32,
+            affixes,
+        }
+    }
+
+    pub fn is_boss_wave(&self) -> bool {
+        self.mobs.iter().any(|mob| mob.kind == MobKind::Boss)
+    }
+
+    pub fn has_affix(&self, affix: Affix) -> bool {
+        self.affixes.contains(&affix)
+    }
+
+    /// Health of one of the wave's mobs of the given kind
+    pub fn health_of(&self, kind: MobKind) -> i32 {
+        kind.desc().health * self.health_percent / 100
+    }
+
+    /// What killing one of the wave's mobs of the given kind pays
+    pub fn reward_of(&self, kind: MobKind) -> (OwnedResource, i64) {
+        let (resource, amount) = kind.desc().reward;
+        (resource, amount * self.reward_percent as i64 / 100)
+    }
+
+    /// Ticks after the launch before the next wave can be launched
+    pub fn cooldown_ticks(&self) -> usize {
+        self.mobs.iter().map(|mob| mob.delay_ticks).max().unwrap_or(0) + WAVE_COOLDOWN_TICKS
+    }
+
+    pub fn counts(&self) -> BTreeMap<MobKind, usize> {
+        let mut counts = BTreeMap::new();
+        for mob in self.mobs.iter() {
+            *counts.entry(mob.kind).or_insert(0) += 1;
+        }
+        counts
+    }
+
+    pub fn spawn_tiles(&self) -> BTreeSet<(i32, i32)> {
+        self.mobs.iter().map(|mob| mob.spawn_tile).collect()
+    }
+
+    /// The traits of every kind of mob in the wave, each once
+    pub fn traits(&self) -> BTreeSet<MobTrait> {
+        self.counts().keys().flat_map(|kind| kind.desc().traits()).collect()
+    }
+
+    /// What killing every mob in the wave would pay
+    pub fn reward(&self) -> OwnedResources {
+        let mut reward = OwnedResources::new();
+        for mob in self.mobs.iter() {
+            let (resource, amount) = self.reward_of(mob.kind);
+            reward.receive(resource, amount);
+        }
+        reward
+    }
+}
+
+/// A splitmix64 generator, started from the seed and the wave number together
+struct WaveRng(u64);
+
+impl WaveRng {
+    fn new(seed: u64, wave_num: usize) -> WaveRng {
+        WaveRng(seed ^ (wave_num as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15))
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// A number in [0, n)
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n.max(1) as u64) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spawns() -> Vec<(Spawn, (i32, i32))> {
+        let spawn = Spawn {
+            first_wave: 0,
+            wave_interval: 1,
+            mobs_per_wave: 5,
+            ticks_per_mob: 40,
+        };
+
+        vec![(spawn, (0, 0)), (spawn, (6, 2)), (spawn, (-3, 4))]
+    }
+
+    #[test]
+    fn endless_waves_come_from_the_seed_and_number_alone() {
+        let spawns = spawns();
+
+        for seed in [0, 1, 12345, u64::MAX] {
+            for wave_num in 1..=60 {
+                let plan = WavePlan::endless(seed, wave_num, &spawns);
+
+                assert_eq!(plan, WavePlan::endless(seed, wave_num, &spawns), "seed {} wave {}", seed, wave_num);
+                assert_eq!(
+                    plan,
+                    WavePlan::new(GameMode::Endless { seed }, wave_num, &spawns),
+                    "seed {} wave {}",
+                    seed,
+                    wave_num
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn endless_seeds_make_different_waves() {
+        let spawns = spawns();
+        let plans = |seed| {
+            (1..=30)
+                .map(|wave_num| WavePlan::endless(seed, wave_num, &spawns))
+                .collect::<Vec<_>>()
+        };
+
+        assert_ne!(plans(1), plans(2));
+    }
+
+    #[test]
+    fn endless_boss_waves_have_one_boss_per_spawn() {
+        let spawns = spawns();
+
+        for seed in [0, 7, 99] {
+            for wave_num in 1..=100 {
+                let plan = WavePlan::endless(seed, wave_num, &spawns);
+                let bosses: Vec<&PlannedMob> = plan.mobs.iter().filter(|mob| mob.kind == MobKind::Boss).collect();
+
+                if wave_num % BOSS_WAVE_INTERVAL == 0 {
+                    assert_eq!(bosses.len(), spawns.len(), "seed {} wave {}", seed, wave_num);
+                    let tiles: BTreeSet<(i32, i32)> = bosses.iter().map(|mob| mob.spawn_tile).collect();
+                    assert_eq!(tiles, plan.spawn_tiles(), "seed {} wave {}", seed, wave_num);
+                } else {
+                    assert!(bosses.is_empty(), "seed {} wave {}", seed, wave_num);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn endless_affixes_are_few_and_different() {
+        let spawns = spawns();
+
+        for seed in 0..20 {
+            for wave_num in 1..=200 {
+                let plan = WavePlan::endless(seed, wave_num, &spawns);
+                let distinct: BTreeSet<Affix> = plan.affixes.iter().copied().collect();
+
+                assert!(plan.affixes.len() <= MAX_AFFIXES, "seed {} wave {}", seed, wave_num);
+                assert_eq!(distinct.len(), plan.affixes.len(), "seed {} wave {}", seed, wave_num);
+            }
+        }
+    }
+}
@@ -5,11 +5,10 @@ use legion::{world::SubWorld, *};
 use wasm_bindgen::JsValue;
 
 use crate::{
-    assets::{Assets, ImageBitmapExt},
+    assets::{Assets, SpriteAtlas},
     canvas_util::CanvasState,
     components::*,
     resources::*,
-    tile_helpers::{TILE_HEIGHT_PIXELS, TILE_WIDTH_PIXELS},
 };
 
 #[system]
@@ -20,6 +19,7 @@ use crate::{
 pub(super) fn draw_renderables(
     #[state] canvas_state: &mut CanvasState,
     #[state] assets: &mut Arc<Assets>,
+    #[state] time_ms: &mut f64,
     #[resource] camera: &TdCamera,
     world: &SubWorld,
 ) {
@@ -28,9 +28,9 @@ pub(super) fn draw_renderables(
 
     let camera_bounds = BoundingBox {
         xmin: camera.left,
-        xmax: camera.left + canvas_state.bounding_rect.width() as i32,
+        xmax: camera.left + camera.screen_to_world_len(canvas_state.bounding_rect.width()),
         ymin: camera.top,
-        ymax: camera.top + canvas_state.bounding_rect.height() as i32,
+        ymax: camera.top + camera.screen_to_world_len(canvas_state.bounding_rect.height()),
     };
 
     for (pos, rend, hidden, maybe_health) in query.iter(world) {
@@ -38,7 +38,13 @@ pub(super) fn draw_renderables(
             continue;
         }
 
-        let render_bounds = get_render_bounds(*pos, *rend);
+        let (pos_x, pos_y) = pos.to_pixel_coords();
+
+        let render_bounds = match get_render_bounds(*pos, *rend, &assets.atlas, *time_ms) {
+            Some(bounds) => bounds,
+            // nothing to draw if the atlas doesn't have the sprite
+            None => continue,
+        };
 
         if !intersects(render_bounds, camera_bounds) {
             continue;
@@ -51,8 +57,8 @@ pub(super) fn draw_renderables(
 
                     ctx.begin_path();
                     ctx.arc(
-                        (pos.x - camera.left).into(),
-                        (pos.y - camera.top).into(),
+                        pos.x - camera.left as f64,
+                        pos.y - camera.top as f64,
                         radius.into(),
                         0.,
                         std::f64::consts::TAU,
@@ -61,30 +67,29 @@ pub(super) fn draw_renderables(
                     ctx.stroke();
 
                     if let Some(health) = maybe_health.copied() {
-                        draw_health_bar(canvas_state, health, pos.x - camera.left - radius, pos.y - camera.top - radius);
+                        draw_health_bar(canvas_state, health, pos_x - camera.left - radius, pos_y - camera.top - radius);
                     }
                 }
+                RenderGeometry::Square { half_width, color } => {
+                    ctx.set_fill_style(&JsValue::from(color));
+                    ctx.fill_rect(
+                        (pos_x - camera.left - half_width).into(),
+                        (pos_y - camera.top - half_width).into(),
+                        (half_width * 2).into(),
+                        (half_width * 2).into(),
+                    );
+                }
             },
-            Renderable::Bitmap { dx, dy, bitmap } => {
-                let (bitmap, sx, sy, sw, sh) = match bitmap {
-                    // TODO: these constants should be in the Assets struct itself somehow
-                    RenderBitmap::GasTrap => (&assets.gas_trap, 0, 0, TILE_WIDTH_PIXELS, TILE_HEIGHT_PIXELS),
-                };
-
-                bitmap
-                    .render_to_canvas(
-                        &canvas_state.context,
-                        sx,
-                        sy,
-                        dx + pos.x - camera.left,
-                        dy + pos.y - camera.top,
-                        sw,
-                        sh,
-                    )
-                    .expect("Image should render");
+            Renderable::Bitmap { dx, dy, sprite } => {
+                if let Some(frame) = assets.atlas.sprite_frame(sprite, *time_ms) {
+                    assets
+                        .atlas
+                        .draw(ctx, frame, dx + pos_x - camera.left, dy + pos_y - camera.top)
+                        .expect("Image should render");
+                }
 
                 if let Some(health) = maybe_health.copied() {
-                    draw_health_bar(canvas_state, health, dx + pos.x - camera.left, dy + pos.y - camera.top);
+                    draw_health_bar(canvas_state, health, dx + pos_x - camera.left, dy + pos_y - camera.top);
                 }
             }
         }
@@ -135,23 +140,34 @@ fn intersects(a: BoundingBox, b: BoundingBox) -> bool {
     intersects_1d(a.xmin, a.xmax, b.xmin, b.xmax) && intersects_1d(a.ymin, a.ymax, b.ymin, b.ymax)
 }
 
-fn get_render_bounds(pos: Position, rend: Renderable) -> BoundingBox {
-    match rend {
-        Renderable::Bitmap { dx, dy, bitmap } => match bitmap {
-            RenderBitmap::GasTrap => BoundingBox {
-                xmin: pos.x + dx,
-                ymin: pos.y + dy,
-                xmax: pos.x + dx + TILE_WIDTH_PIXELS,
-                ymax: pos.y + dy + TILE_HEIGHT_PIXELS,
-            },
-        },
+fn get_render_bounds(pos: Position, rend: Renderable, atlas: &SpriteAtlas, time_ms: f64) -> Option<BoundingBox> {
+    let (x, y) = pos.to_pixel_coords();
+
+    let bounds = match rend {
+        Renderable::Bitmap { dx, dy, sprite } => {
+            let frame = atlas.sprite_frame(sprite, time_ms)?;
+            BoundingBox {
+                xmin: x + dx,
+                ymin: y + dy,
+                xmax: x + dx + frame.w,
+                ymax: y + dy + frame.h,
+            }
+        }
         Renderable::Geometry(geometry) => match geometry {
             RenderGeometry::Circle { radius } => BoundingBox {
-                xmin: pos.x - radius,
-                ymin: pos.y - radius,
-                xmax: pos.x + radius + 1,
-                ymax: pos.y + radius + 1,
+                xmin: x - radius,
+                ymin: y - radius,
+                xmax: x + radius + 1,
+                ymax: y + radius + 1,
+            },
+            RenderGeometry::Square { half_width, .. } => BoundingBox {
+                xmin: x - half_width,
+                ymin: y - half_width,
+                xmax: x + half_width,
+                ymax: y + half_width,
             },
         },
-    }
+    };
+
+    Some(bounds)
 }
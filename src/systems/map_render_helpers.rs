@@ -17,8 +17,9 @@ pub struct MapRenderData {
 }
 
 pub fn get_map_render_data(canvas_state: &CanvasState, camera: &TdCamera) -> MapRenderData {
-    let canvas_width: i32 = canvas_state.bounding_rect.width() as i32;
-    let canvas_height: i32 = canvas_state.bounding_rect.height() as i32;
+    // in world pixels, which is what everything is drawn in; the canvas is scaled to the zoom
+    let canvas_width: i32 = camera.screen_to_world_len(canvas_state.bounding_rect.width());
+    let canvas_height: i32 = camera.screen_to_world_len(canvas_state.bounding_rect.height());
 
     // start rendering here for the left column of tiles; this may be negative
     let x_pixel_offset = -(camera.left.rem_euclid(TILE_WIDTH_PIXELS));
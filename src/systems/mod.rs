@@ -11,7 +11,7 @@ use crate::{assets::Assets, canvas_util::CanvasState};
 
 mod tick_systems;
 
-pub use tick_systems::make_tick_schedule;
+pub use tick_systems::{make_input_schedule, make_sim_schedule};
 
 mod map_render_helpers;
 
@@ -19,14 +19,38 @@ mod map_render_helpers;
 
 mod draw_renderables;
 mod render_gas_system;
+mod render_labels_system;
 mod render_map_system;
+mod render_paint_system;
+mod render_particles_system;
+mod render_path_system;
+mod render_range_system;
 
 // TODO: don't recreate this every time, somehow
 pub fn canvas_render_schedule(canvas_state: &CanvasState, assets: &Arc<Assets>) -> Schedule {
+    // animations are timed off the page clock, so they play at the same speed however fast it renders
+    let time_ms = web_sys::window()
+        .and_then(|window| window.performance())
+        .map(|performance| performance.now())
+        .unwrap_or(0.);
+
     Schedule::builder()
-        .add_thread_local(render_map_system::draw_map_tiles_system(canvas_state.clone()))
-        .add_thread_local(draw_renderables::draw_renderables_system(canvas_state.clone(), assets.clone()))
+        .add_thread_local(render_map_system::draw_map_tiles_system(
+            canvas_state.clone(),
+            assets.clone(),
+            time_ms,
+        ))
+        .add_thread_local(draw_renderables::draw_renderables_system(
+            canvas_state.clone(),
+            assets.clone(),
+            time_ms,
+        ))
         .add_thread_local(render_gas_system::draw_gas_system(canvas_state.clone(), assets.clone()))
+        .add_thread_local(render_particles_system::draw_particles_system(canvas_state.clone()))
+        .add_thread_local(render_labels_system::draw_labels_system(canvas_state.clone()))
+        .add_thread_local(render_range_system::draw_ranges_system(canvas_state.clone()))
+        .add_thread_local(render_path_system::draw_paths_system(canvas_state.clone()))
+        .add_thread_local(render_paint_system::draw_paint_system(canvas_state.clone()))
         .build()
 }
 
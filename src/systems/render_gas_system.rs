@@ -2,11 +2,10 @@ use std::sync::Arc;
 
 use legion::*;
 
-use web_sys::ImageBitmap;
-
 use crate::{
-    assets::{Assets, ImageBitmapExt},
+    assets::Assets,
     canvas_util::CanvasState,
+    components::SpriteId,
     resources::*,
     tile_helpers::{TILE_HEIGHT_PIXELS, TILE_WIDTH_PIXELS},
 };
@@ -31,7 +30,10 @@ pub(super) fn draw_gas(
         y_pixel_offset,
     } = get_map_render_data(canvas_state, camera);
 
-    let bitmap: &ImageBitmap = &assets.gas_image;
+    let frame = match assets.atlas.sprite_frame(SpriteId::GAS, 0.) {
+        Some(frame) => frame,
+        None => return,
+    };
 
     let old_alpha: f64 = canvas_state.context.global_alpha();
 
@@ -47,9 +49,7 @@ pub(super) fn draw_gas(
             if gas_amount > 0 {
                 let opacity = gas_opacity(gas_amount);
                 canvas_state.context.set_global_alpha(opacity);
-                bitmap
-                    .render_to_canvas_tile(&canvas_state.context, 0, 0, x_left_pixel, y_top_pixel)
-                    .unwrap();
+                assets.atlas.draw(&canvas_state.context, frame, x_left_pixel, y_top_pixel).unwrap();
             }
         }
     }
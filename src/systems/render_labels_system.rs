@@ -0,0 +1,38 @@
+use legion::*;
+
+use wasm_bindgen::JsValue;
+
+use crate::{canvas_util::CanvasState, resources::*};
+
+const DAMAGE_COLOR: &str = "#ff4040";
+const REWARD_COLOR: &str = "#ffd700";
+
+#[system]
+pub(super) fn draw_labels(#[state] canvas_state: &mut CanvasState, #[resource] camera: &TdCamera, #[resource] labels: &FloatingLabels) {
+    let ctx = &canvas_state.context;
+    let old_alpha: f64 = ctx.global_alpha();
+
+    ctx.set_font("bold 12px sans-serif");
+    ctx.set_text_align("center");
+    ctx.set_stroke_style(&JsValue::from("black"));
+
+    for label in labels.iter() {
+        let color = match label.kind {
+            LabelKind::Damage => DAMAGE_COLOR,
+            LabelKind::Reward(_) => REWARD_COLOR,
+        };
+
+        let text = label.text();
+        let x = label.x - camera.left as f64;
+        let y = label.y - camera.top as f64;
+
+        ctx.set_global_alpha(label.opacity());
+        // outlined, so it reads over any tile
+        ctx.stroke_text(&text, x, y).expect("Text should be drawable");
+        ctx.set_fill_style(&JsValue::from(color));
+        ctx.fill_text(&text, x, y).expect("Text should be drawable");
+    }
+
+    ctx.set_text_align("start");
+    ctx.set_global_alpha(old_alpha);
+}
@@ -1,8 +1,11 @@
+use std::sync::Arc;
+
 use legion::*;
 
 use wasm_bindgen::JsValue;
 
 use crate::{
+    assets::Assets,
     canvas_util::CanvasState,
     resources::*,
     tile_helpers::{TILE_HEIGHT_PIXELS, TILE_WIDTH_PIXELS},
@@ -13,9 +16,12 @@ use super::map_render_helpers::{get_map_render_data, MapRenderData};
 #[system]
 pub(super) fn draw_map_tiles(
     #[state] canvas_state: &mut CanvasState,
+    #[state] assets: &mut Arc<Assets>,
+    #[state] time_ms: &mut f64,
     #[resource] camera: &TdCamera,
     #[resource] map: &Map,
     #[resource] hover_state: &TdTileSelect,
+    #[resource] select_drag: &TdSelectDrag,
 ) {
     let MapRenderData {
         x_min_tile,
@@ -39,11 +45,24 @@ pub(super) fn draw_map_tiles(
 
             let tile: Tile = map.get_tile(tile_x, tile_y);
 
+            // the world past the edge is never drawn with sprites, so it stays easy to tell apart
+            if map.in_bounds(tile_x, tile_y) {
+                if let Some(frame) = assets.atlas.tile_frame(tile, *time_ms) {
+                    assets
+                        .atlas
+                        .draw(&canvas_state.context, frame, x_left_pixel, y_top_pixel)
+                        .expect("Image should render");
+                    continue;
+                }
+            }
+
             let color = match tile {
+                _ if !map.in_bounds(tile_x, tile_y) => JsValue::from("#003020"),
                 Tile::Open => JsValue::from("#70e0e0"),
                 Tile::Wall => JsValue::from("#008050"),
-                Tile::Spawn => JsValue::from("#ff1587"),
-                Tile::Core => JsValue::from("#1584ff"),
+                Tile::Mud => JsValue::from("#8a6a3a"),
+                Tile::Road => JsValue::from("#c8c0a8"),
+                Tile::Water => JsValue::from("#3060c8"),
             };
 
             canvas_state.context.set_fill_style(&color);
@@ -66,11 +85,12 @@ pub(super) fn draw_map_tiles(
             let x_left_pixel = x_pixel_offset + (x_ind * TILE_WIDTH_PIXELS);
             let y_top_pixel = y_pixel_offset + (y_ind * TILE_HEIGHT_PIXELS);
 
-            match hover_state {
-                TdTileSelect::Selected { x, y, structures: _ } if *x == tile_x && *y == tile_y => {
-                    canvas_state.context.set_stroke_style(&highlighted);
-                }
-                _ => canvas_state.context.set_stroke_style(&black),
+            let in_drag = select_drag.0.is_some_and(|rect| rect.contains(tile_x, tile_y));
+
+            if hover_state.contains(tile_x, tile_y) || in_drag {
+                canvas_state.context.set_stroke_style(&highlighted);
+            } else {
+                canvas_state.context.set_stroke_style(&black);
             }
 
             canvas_state.context.stroke_rect(
@@ -0,0 +1,112 @@
+//! Previews what the picked tool would do: each tile in the stroke (or the one under the mouse)
+//! marked as going ahead or not, and what the lot would cost.
+
+use std::collections::HashSet;
+
+use legion::{world::SubWorld, *};
+
+use wasm_bindgen::JsValue;
+
+use crate::{
+    canvas_util::CanvasState,
+    components::*,
+    resources::*,
+    tile_helpers::{TILE_HEIGHT_PIXELS, TILE_WIDTH_PIXELS},
+};
+
+const ACCEPTED_COLOR: &str = "#40e040";
+const REJECTED_COLOR: &str = "#ff4040";
+const FILL_ALPHA: f64 = 0.35;
+
+#[system]
+#[read_component(Structure)]
+#[read_component(Position)]
+#[allow(clippy::too_many_arguments)]
+pub(super) fn draw_paint(
+    #[state] canvas_state: &mut CanvasState,
+    #[resource] camera: &TdCamera,
+    #[resource] paint: &PaintState,
+    #[resource] map: &Map,
+    #[resource] transforms: &TileTransforms,
+    #[resource] builds: &StructureBuilds,
+    #[resource] owned: &OwnedResources,
+    world: &SubWorld,
+) {
+    if paint.tool.is_none() {
+        return;
+    }
+
+    let occupied: HashSet<(i32, i32)> = <(Read<Structure>, Read<Position>)>::query()
+        .iter(world)
+        .map(|(_, pos)| pos.to_tile_coords())
+        .collect();
+
+    let plan = paint.plan(map, transforms, builds, &occupied, owned);
+
+    let last = match plan.tiles.last() {
+        Some(last) => last,
+        None => return,
+    };
+
+    let ctx = &canvas_state.context;
+    let old_alpha: f64 = ctx.global_alpha();
+
+    for painted in plan.tiles.iter() {
+        let color = if painted.result.is_ok() { ACCEPTED_COLOR } else { REJECTED_COLOR };
+        let left = (painted.x * TILE_WIDTH_PIXELS - camera.left) as f64;
+        let top = (painted.y * TILE_HEIGHT_PIXELS - camera.top) as f64;
+
+        ctx.set_global_alpha(FILL_ALPHA);
+        ctx.set_fill_style(&JsValue::from(color));
+        ctx.fill_rect(left, top, TILE_WIDTH_PIXELS as f64, TILE_HEIGHT_PIXELS as f64);
+
+        ctx.set_global_alpha(1.);
+        ctx.set_stroke_style(&JsValue::from(color));
+        ctx.stroke_rect(left + 1.5, top + 1.5, TILE_WIDTH_PIXELS as f64 - 3., TILE_HEIGHT_PIXELS as f64 - 3.);
+    }
+
+    // the cost, and why anything was left out, just above wherever the stroke has got to
+    let mut lines = vec![cost_text(&plan.total)];
+    for (rejection, why) in REJECTION_TEXT.iter() {
+        let count = plan.rejected_for(*rejection);
+        if count > 0 {
+            lines.push(format!("{} skipped: {}", count, why));
+        }
+    }
+
+    ctx.set_font("bold 14px sans-serif");
+    ctx.set_fill_style(&JsValue::from("white"));
+
+    let text_x = (last.x * TILE_WIDTH_PIXELS - camera.left) as f64;
+    let text_top = (last.y * TILE_HEIGHT_PIXELS - camera.top) as f64 - 16. * lines.len() as f64;
+
+    for (row, line) in lines.iter().enumerate() {
+        let text_y = text_top + 16. * row as f64 + 12.;
+        ctx.fill_text(line, text_x, text_y).expect("Text should be drawable");
+    }
+
+    ctx.set_global_alpha(old_alpha);
+}
+
+const REJECTION_TEXT: &[(PaintRejection, &str)] = &[
+    (PaintRejection::NotAllowed, "can't go there"),
+    (PaintRejection::Occupied, "something's in the way"),
+    (PaintRejection::BlocksPath, "would block the path"),
+    (PaintRejection::CannotAfford, "can't afford"),
+];
+
+fn cost_text(total: &OwnedResources) -> String {
+    let parts: Vec<String> = ALL_RESOURCES
+        .iter()
+        .filter_map(|o| match total.0.get(o).copied().unwrap_or(0) {
+            0 => None,
+            amt => Some(format!("{} {}", amt, o)),
+        })
+        .collect();
+
+    if parts.is_empty() {
+        "Cost: nothing".to_string()
+    } else {
+        format!("Cost: {}", parts.join(", "))
+    }
+}
@@ -0,0 +1,32 @@
+use legion::*;
+
+use wasm_bindgen::JsValue;
+
+use crate::{canvas_util::CanvasState, resources::*};
+
+const PARTICLE_SIZE: f64 = 3.;
+
+#[system]
+pub(super) fn draw_particles(#[state] canvas_state: &mut CanvasState, #[resource] camera: &TdCamera, #[resource] particles: &Particles) {
+    let ctx = &canvas_state.context;
+    let old_alpha: f64 = ctx.global_alpha();
+
+    let width = camera.screen_to_world_len(canvas_state.bounding_rect.width()) as f64;
+    let height = camera.screen_to_world_len(canvas_state.bounding_rect.height()) as f64;
+
+    for particle in particles.iter() {
+        let x = particle.x - camera.left as f64;
+        let y = particle.y - camera.top as f64;
+
+        if x < -PARTICLE_SIZE || y < -PARTICLE_SIZE || x > width || y > height {
+            continue;
+        }
+
+        // fade out over the particle's life
+        ctx.set_global_alpha(particle.remaining());
+        ctx.set_fill_style(&JsValue::from(particle.color));
+        ctx.fill_rect(x - PARTICLE_SIZE / 2., y - PARTICLE_SIZE / 2., PARTICLE_SIZE, PARTICLE_SIZE);
+    }
+
+    ctx.set_global_alpha(old_alpha);
+}
@@ -0,0 +1,118 @@
+//! The routes mobs will take from each spawn to the nearest core, one per movement kind. While
+//! a tile change option is hovered, the routes they would take after it are drawn as well, along
+//! with how much longer or shorter each one gets.
+
+use legion::*;
+
+use wasm_bindgen::JsValue;
+
+use crate::{
+    canvas_util::CanvasState,
+    resources::*,
+    tile_helpers::{tile_to_pixel_coords, TILE_HEIGHT_PIXELS, TILE_WIDTH_PIXELS},
+};
+
+const ROUTE_ALPHA: f64 = 0.7;
+const WHAT_IF_COLOR: &str = "#ff4040";
+
+/// Each kind gets its own color, and is drawn a little off center so routes they share stay visible
+fn kind_style(kind: MovementKind) -> (&'static str, f64) {
+    match kind {
+        MovementKind::Walker => ("#ffffff", -2.),
+        MovementKind::Swimmer => ("#40d0ff", 2.),
+    }
+}
+
+#[system]
+pub(super) fn draw_paths(
+    #[state] canvas_state: &mut CanvasState,
+    #[resource] camera: &TdCamera,
+    #[resource] map: &Map,
+    #[resource] build_hover: &TdBuildHover,
+    #[resource] route_preview: &mut RoutePreview,
+) {
+    // a change which would cut a spawn off is refused anyway, so there's nothing to show for it.
+    // Trying the change out means copying the map, so that's only done when the hover or the map
+    // changes, not every frame.
+    let what_if = match *build_hover {
+        TdBuildHover::ChangeTile { x, y, tile } if map.can_set_tile(x, y, tile) => {
+            Some(route_preview.get_or_compute(((x, y), tile, map.revision()), |_| {
+                let mut changed = map.clone();
+                changed.set_tile(x, y, tile);
+
+                map.spawns()
+                    .flat_map(|spawn| ALL_MOVEMENT_KINDS.iter().map(move |kind| (spawn, *kind)))
+                    .map(|(spawn, kind)| ((spawn, kind), changed.route(kind, PathTarget::NearestCore, spawn.0, spawn.1)))
+                    .collect()
+            }))
+        }
+        _ => None,
+    };
+
+    let ctx = &canvas_state.context;
+    let old_alpha: f64 = ctx.global_alpha();
+    let old_line_width: f64 = ctx.line_width();
+
+    ctx.set_global_alpha(ROUTE_ALPHA);
+    ctx.set_line_width(2.);
+    ctx.set_font("bold 14px sans-serif");
+
+    for (spawn_x, spawn_y) in map.spawns() {
+        for (row, kind) in ALL_MOVEMENT_KINDS.iter().copied().enumerate() {
+            let (color, offset) = kind_style(kind);
+            let route = map.route(kind, PathTarget::NearestCore, spawn_x, spawn_y);
+
+            draw_route(canvas_state, camera, &route, color, offset);
+
+            let new_route = match what_if.and_then(|routes| routes.get(&((spawn_x, spawn_y), kind))) {
+                Some(new_route) => new_route,
+                None => continue,
+            };
+
+            if *new_route != route {
+                draw_route(canvas_state, camera, new_route, WHAT_IF_COLOR, offset);
+            }
+
+            // one line of text per kind, in its color, just above the spawn
+            let delta = new_route.len() as i32 - route.len() as i32;
+            let text = match delta {
+                0 => format!("{:?}: no change", kind),
+                _ => format!("{:?}: {:+} tiles", kind, delta),
+            };
+
+            let (x, y) = tile_to_pixel_coords(spawn_x, spawn_y);
+            let text_x = (x - TILE_WIDTH_PIXELS / 2 - camera.left) as f64;
+            let text_y = (y - TILE_HEIGHT_PIXELS / 2 - camera.top) as f64 - 16. * (ALL_MOVEMENT_KINDS.len() - row - 1) as f64 - 4.;
+
+            ctx.set_fill_style(&JsValue::from(color));
+            ctx.fill_text(&text, text_x, text_y).expect("Text should be drawable");
+        }
+    }
+
+    ctx.set_global_alpha(old_alpha);
+    ctx.set_line_width(old_line_width);
+}
+
+fn draw_route(canvas_state: &CanvasState, camera: &TdCamera, route: &[(i32, i32)], color: &str, offset: f64) {
+    if route.len() < 2 {
+        return;
+    }
+
+    let ctx = &canvas_state.context;
+
+    ctx.set_stroke_style(&JsValue::from(color));
+    ctx.begin_path();
+
+    for (i, (tile_x, tile_y)) in route.iter().copied().enumerate() {
+        let (x, y) = tile_to_pixel_coords(tile_x, tile_y);
+        let (x, y) = ((x - camera.left) as f64 + offset, (y - camera.top) as f64 + offset);
+
+        if i == 0 {
+            ctx.move_to(x, y);
+        } else {
+            ctx.line_to(x, y);
+        }
+    }
+
+    ctx.stroke();
+}
@@ -0,0 +1,111 @@
+//! Previews of where structures reach: the build option the mouse is over, or failing that the
+//! structures on the selected tile. Gas is shown as the footprint it would settle into.
+
+use legion::{world::SubWorld, *};
+
+use wasm_bindgen::JsValue;
+
+use crate::{
+    canvas_util::CanvasState,
+    components::*,
+    resources::*,
+    tile_helpers::{TILE_HEIGHT_PIXELS, TILE_WIDTH_PIXELS},
+};
+
+/// Long enough for a trap's gas to settle, or a burst of gas to clear, on any reasonable map
+const MAX_PREVIEW_TICKS: u32 = 200;
+/// Gas amount at which a tile is drawn fully covered
+const FULL_COVERAGE_AMOUNT: i32 = 20;
+const MAX_COVERAGE_ALPHA: f64 = 0.45;
+
+#[system]
+#[read_component(PoisonGasTrap)]
+#[read_component(OnDeath)]
+#[read_component(Position)]
+pub(super) fn draw_ranges(
+    #[state] canvas_state: &mut CanvasState,
+    #[resource] camera: &TdCamera,
+    #[resource] map: &Map,
+    #[resource] build_hover: &TdBuildHover,
+    #[resource] selected_tile: &TdTileSelect,
+    #[resource] gas_preview: &mut GasPreview,
+    world: &SubWorld,
+) {
+    // gas produced every tick, and gas released all at once
+    let mut steady: GasSources = Vec::new();
+    let mut bursts: GasSources = Vec::new();
+
+    match (build_hover, selected_tile) {
+        (TdBuildHover::Build { x, y, kind }, _) => match kind {
+            StructureKind::GasTrap => steady.push(((*x, *y), BUILT_GAS_TRAP_AMOUNT)),
+            StructureKind::Core => bursts.push(((*x, *y), BUILT_CORE_GAS_RELEASE)),
+            StructureKind::Spawn => {}
+        },
+        (TdBuildHover::None, selected_tile) => {
+            for structure in selected_tile.structures() {
+                // the selection may still name a structure which was sold or destroyed this frame
+                let entry = match world.entry_ref(structure.entity) {
+                    Ok(entry) => entry,
+                    Err(_) => continue,
+                };
+
+                let tile = match entry.get_component::<Position>() {
+                    Ok(pos) => pos.to_tile_coords(),
+                    Err(_) => continue,
+                };
+
+                if let Ok(trap) = entry.get_component::<PoisonGasTrap>() {
+                    steady.push((tile, trap.amount));
+                }
+
+                if let Ok(on_death) = entry.get_component::<OnDeath>() {
+                    for event in on_death.events.iter() {
+                        if let DeathEvent::ReleaseGas(amount) = event {
+                            bursts.push((tile, *amount));
+                        }
+                    }
+                }
+            }
+        }
+        _ => {}
+    }
+
+    if steady.is_empty() && bursts.is_empty() {
+        return;
+    }
+
+    // only worked out again when the sources or the map change, not every frame
+    let coverage = gas_preview.get_or_compute((steady.clone(), bursts.clone(), map.revision()), |(steady, bursts, _)| {
+        map.preview_gas(steady, bursts, MAX_PREVIEW_TICKS)
+    });
+
+    let ctx = &canvas_state.context;
+    let old_alpha: f64 = ctx.global_alpha();
+
+    ctx.set_fill_style(&JsValue::from("#f0e020"));
+
+    for (&(tile_x, tile_y), &amount) in coverage {
+        let strength = amount.min(FULL_COVERAGE_AMOUNT) as f64 / FULL_COVERAGE_AMOUNT as f64;
+        ctx.set_global_alpha(MAX_COVERAGE_ALPHA * strength);
+        ctx.fill_rect(
+            (tile_x * TILE_WIDTH_PIXELS - camera.left) as f64,
+            (tile_y * TILE_HEIGHT_PIXELS - camera.top) as f64,
+            TILE_WIDTH_PIXELS as f64,
+            TILE_HEIGHT_PIXELS as f64,
+        );
+    }
+
+    ctx.set_global_alpha(old_alpha);
+
+    // outline the sources, so it's clear where the footprint comes from
+    ctx.set_stroke_style(&JsValue::from("#f0e020"));
+
+    for ((tile_x, tile_y), _) in steady.iter().chain(bursts.iter()) {
+        ctx.stroke_rect(
+            (tile_x * TILE_WIDTH_PIXELS - camera.left) as f64 + 1.5,
+            (tile_y * TILE_HEIGHT_PIXELS - camera.top) as f64 + 1.5,
+            TILE_WIDTH_PIXELS as f64 - 3.,
+            TILE_HEIGHT_PIXELS as f64 - 3.,
+        );
+    }
+}
@@ -1,27 +1,94 @@
-//! Entities that breathe and share a tile with gas should take appropriate damage
+//! Entities that breathe and share a tile with gas should take appropriate damage. The damage is
+//! put down to the gas traps in the same patch of gas, shared out by how much gas each one makes.
+
+use std::collections::HashMap;
 
 use legion::{systems::CommandBuffer, world::SubWorld, *};
 
-use crate::{components::*, resources::*, tile_helpers::coords_to_tile};
+use crate::{components::*, resources::*};
+
+// since we can't communicate how much gas there is to the player, enemies just take a flat amount
+// of damage from "any gas", which is a perfectly fine gameplay mechanic and more transparent for
+// the user
+const GAS_DAMAGE: i32 = 5;
 
 #[system]
 #[read_component(Position)]
 #[read_component(Breathes)]
+#[read_component(PoisonGasTrap)]
 pub(super) fn breathe_gas(#[resource] map: &Map, cmd: &mut CommandBuffer, world: &SubWorld) {
+    let traps: Vec<(Entity, (i32, i32), i32)> = <(Entity, Read<PoisonGasTrap>, Read<Position>)>::query()
+        .iter(world)
+        .map(|(entity, trap, pos)| (*entity, pos.to_tile_coords(), trap.amount))
+        .collect();
+
+    // each patch of gas is only looked over once, however many mobs are in it
+    let mut emitters_by_tile: HashMap<(i32, i32), Vec<(Entity, i32)>> = HashMap::new();
+
     let mut query = <(Entity, Read<Position>, Read<Breathes>)>::query();
 
     for (entity, pos, _) in query.iter(world) {
-        let (tile_x, tile_y) = coords_to_tile(pos.x, pos.y);
-        let gas_amount = map.get_gas_amount(tile_x, tile_y);
+        let (tile_x, tile_y) = pos.to_tile_coords();
+
+        if map.get_gas_amount(tile_x, tile_y) <= 0 {
+            continue;
+        }
+
+        if !emitters_by_tile.contains_key(&(tile_x, tile_y)) {
+            let cloud = map.gas_cloud(tile_x, tile_y);
+            let emitters: Vec<(Entity, i32)> = traps
+                .iter()
+                .filter(|(_, tile, _)| cloud.contains(tile))
+                .map(|(trap, _, amount)| (*trap, *amount))
+                .collect();
 
-        if gas_amount > 0 {
+            for tile in cloud {
+                emitters_by_tile.insert(tile, emitters.clone());
+            }
+        }
+
+        for (source, amount) in split_damage(GAS_DAMAGE, &emitters_by_tile[&(tile_x, tile_y)]) {
             cmd.push((TakeDamage {
                 target: *entity,
-                // since we can't communicate how much gas there is to the player,
-                // enemies just take a flat amount of damage from "any gas", which is a perfectly
-                // fine gameplay mechanic and more transparent for the user
-                amount: 5,
+                amount,
+                source,
             },));
         }
     }
 }
+
+/// Share the damage out between the emitters by how much each one emits, whole points only; the
+/// points left over after rounding down go to whoever was rounded down the most. With no emitters,
+/// it's down to nothing in particular.
+fn split_damage(total: i32, emitters: &[(Entity, i32)]) -> Vec<(Option<Entity>, i32)> {
+    let emitted: i32 = emitters.iter().map(|(_, amount)| *amount).sum();
+
+    if emitted <= 0 {
+        return vec![(None, total)];
+    }
+
+    let mut shares: Vec<(Entity, i32, i32)> = emitters
+        .iter()
+        .map(|&(entity, amount)| (entity, total * amount / emitted, total * amount % emitted))
+        .collect();
+
+    let mut left_over = total - shares.iter().map(|(_, share, _)| share).sum::<i32>();
+
+    let mut by_remainder: Vec<usize> = (0..shares.len()).collect();
+    // stable, so ties go to whichever came first
+    by_remainder.sort_by_key(|&idx| std::cmp::Reverse(shares[idx].2));
+
+    for idx in by_remainder {
+        if left_over == 0 {
+            break;
+        }
+        shares[idx].1 += 1;
+        left_over -= 1;
+    }
+
+    shares
+        .into_iter()
+        .filter(|(_, share, _)| *share > 0)
+        .map(|(entity, share, _)| (Some(entity), share))
+        .collect()
+}
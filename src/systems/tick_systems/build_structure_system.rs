@@ -5,7 +5,18 @@ use crate::{components::*, resources::*};
 
 #[system]
 #[read_component(TryBuildStructure)]
-pub(super) fn build_structures(#[resource] owned_resources: &mut OwnedResources, cmd: &mut CommandBuffer, world: &SubWorld) {
+#[allow(clippy::too_many_arguments)]
+pub(super) fn build_structures(
+    #[resource] owned_resources: &mut OwnedResources,
+    #[resource] map: &Map,
+    #[resource] next_wave_state: &NextWaveState,
+    #[resource] last_built: &mut LastBuilt,
+    #[resource] tick: &TickCount,
+    #[resource] undo_stack: &mut UndoStack,
+    #[resource] stats: &mut RunStats,
+    cmd: &mut CommandBuffer,
+    world: &SubWorld,
+) {
     let mut query = <(Entity, Read<TryBuildStructure>)>::query();
 
     for (entity, try_build) in query.iter(world) {
@@ -18,27 +29,91 @@ pub(super) fn build_structures(#[resource] owned_resources: &mut OwnedResources,
             ref costs,
         } = try_build;
 
-        if owned_resources.can_pay(costs) {
+        // a spawn with nowhere to go would just leave its mobs stuck
+        let can_place = match desired {
+            StructureKind::Spawn => map.can_add_spawn(tile_x, tile_y),
+            StructureKind::GasTrap | StructureKind::Core => true,
+        };
+
+        if can_place && owned_resources.can_pay(costs) {
             owned_resources.pay(costs);
+            stats.add_spent(ResourceCategory::Building(desired), costs);
+            last_built.0 = Some(desired);
+
+            let built = build_structure(cmd, desired, tile_x, tile_y, next_wave_state.next_wave);
 
-            match desired {
-                StructureKind::GasTrap => build_gas_trap(cmd, tile_x, tile_y),
-            }
+            undo_stack.record(
+                tick.0,
+                UndoAction::Build {
+                    entity: built,
+                    kind: desired,
+                    tile: (tile_x, tile_y),
+                    paid: costs.clone(),
+                },
+            );
         }
     }
 }
 
-fn build_gas_trap(cmd: &mut CommandBuffer, tile_x: i32, tile_y: i32) {
+/// Push a freshly built structure of the given kind; paying for it and recording it is up to the
+/// caller
+pub(super) fn build_structure(cmd: &mut CommandBuffer, kind: StructureKind, tile_x: i32, tile_y: i32, next_wave: usize) -> Entity {
+    match kind {
+        StructureKind::GasTrap => build_gas_trap(cmd, tile_x, tile_y),
+        StructureKind::Core => build_core(cmd, tile_x, tile_y),
+        StructureKind::Spawn => build_spawn(cmd, tile_x, tile_y, next_wave),
+    }
+}
+
+fn build_gas_trap(cmd: &mut CommandBuffer, tile_x: i32, tile_y: i32) -> Entity {
     cmd.push((
         Position::at_tile_center(tile_x, tile_y),
         Structure(StructureKind::GasTrap),
-        PoisonGasTrap { amount: 10 },
+        PoisonGasTrap {
+            amount: BUILT_GAS_TRAP_AMOUNT,
+        },
         Renderable::Bitmap {
             dx: -TILE_WIDTH_PIXELS / 2,
             dy: -TILE_HEIGHT_PIXELS / 2,
-            bitmap: RenderBitmap::GasTrap,
+            sprite: SpriteId::GAS_TRAP,
         },
         // TODO: sell value should be tracked in a resource or something somewhere
         SellValue(OwnedResources::new().with(OwnedResource::Money, 10).with(OwnedResource::Wood, 5)),
-    ));
+    ))
+}
+
+fn build_core(cmd: &mut CommandBuffer, tile_x: i32, tile_y: i32) -> Entity {
+    cmd.push((
+        Position::at_tile_center(tile_x, tile_y),
+        Structure(StructureKind::Core),
+        Core {
+            health: BUILT_CORE_HEALTH,
+            max_health: BUILT_CORE_HEALTH,
+            main: false,
+        },
+        Renderable::Geometry(RenderGeometry::Square {
+            half_width: TILE_WIDTH_PIXELS / 2 - 1,
+            color: CORE_COLOR,
+        }),
+        OnDeath {
+            events: vec![DeathEvent::ReleaseGas(BUILT_CORE_GAS_RELEASE)],
+        },
+    ))
+}
+
+fn build_spawn(cmd: &mut CommandBuffer, tile_x: i32, tile_y: i32, next_wave: usize) -> Entity {
+    cmd.push((
+        Position::at_tile_center(tile_x, tile_y),
+        Structure(StructureKind::Spawn),
+        Spawn {
+            first_wave: next_wave,
+            wave_interval: 1,
+            mobs_per_wave: 5,
+            ticks_per_mob: 40,
+        },
+        Renderable::Geometry(RenderGeometry::Square {
+            half_width: TILE_WIDTH_PIXELS / 2 - 1,
+            color: SPAWN_COLOR,
+        }),
+    ))
 }
@@ -0,0 +1,378 @@
+//! Bulk actions are checked as a whole and then carried out in full, all of them or none, with
+//! one payment for the lot; nothing is split back into one-tile requests which could fail part
+//! way through.
+
+use std::collections::HashSet;
+
+use legion::{systems::CommandBuffer, world::SubWorld, *};
+
+use crate::{components::*, resources::*};
+
+use super::{build_structure_system::build_structure, sell_structure_system::sell_structure};
+
+/// Message component; a bulk change or build which has been checked and paid for, and only needs
+/// carrying out. It's handed on rather than done straight away just to keep each system's list of
+/// resources down; nothing runs in between.
+pub(super) struct ApplyBulkPlan {
+    tool: PaintTool,
+    plan: PaintPlan,
+}
+
+#[system]
+#[read_component(TryBulkAction)]
+#[read_component(SellValue)]
+#[read_component(Position)]
+#[read_component(Structure)]
+#[read_component(Core)]
+#[read_component(Spawn)]
+#[read_component(PoisonGasTrap)]
+#[read_component(Renderable)]
+#[read_component(OnDeath)]
+#[read_component(DamageDealt)]
+#[allow(clippy::too_many_arguments)]
+pub(super) fn process_bulk_actions(
+    #[resource] map: &Map,
+    #[resource] transforms: &TileTransforms,
+    #[resource] builds: &StructureBuilds,
+    #[resource] owned_resources: &mut OwnedResources,
+    #[resource] tick: &TickCount,
+    #[resource] undo_stack: &mut UndoStack,
+    #[resource] stats: &mut RunStats,
+    cmd: &mut CommandBuffer,
+    world: &SubWorld,
+) {
+    let mut query = <(Entity, Read<TryBulkAction>)>::query();
+
+    for (entity, bulk) in query.iter(world) {
+        cmd.remove(*entity);
+
+        match bulk {
+            TryBulkAction::Paint { tool, tiles } => {
+                let occupied: HashSet<(i32, i32)> = <(Read<Structure>, Read<Position>)>::query()
+                    .iter(world)
+                    .map(|(_, pos)| pos.to_tile_coords())
+                    .collect();
+
+                // one cost check for the whole lot, rather than tile by tile
+                let plan = PaintPlan::new(*tool, tiles, map, transforms, builds, &occupied, None);
+
+                if !plan.all_accepted() || !owned_resources.can_pay(&plan.total) {
+                    continue;
+                }
+
+                owned_resources.pay(&plan.total);
+                cmd.push((ApplyBulkPlan { tool: *tool, plan },));
+            }
+            TryBulkAction::Sell { to_sell } => {
+                // selling only ever pays out, so the one thing to check is that they're all there
+                let entries: Option<Vec<_>> = to_sell.iter().map(|to_sell| world.entry_ref(*to_sell).ok()).collect();
+
+                let entries = match entries {
+                    Some(entries) => entries,
+                    None => continue,
+                };
+
+                for (existing, to_sell) in entries.iter().zip(to_sell.iter().copied()) {
+                    sell_structure(existing, to_sell, owned_resources, stats, undo_stack, tick, cmd);
+                }
+            }
+        }
+    }
+}
+
+/// Carry out bulk changes and builds which were checked and paid for just before
+#[system]
+#[read_component(ApplyBulkPlan)]
+#[allow(clippy::too_many_arguments)]
+pub(super) fn apply_bulk_plans(
+    #[resource] map: &mut Map,
+    #[resource] next_wave_state: &NextWaveState,
+    #[resource] last_built: &mut LastBuilt,
+    #[resource] tick: &TickCount,
+    #[resource] undo_stack: &mut UndoStack,
+    #[resource] stats: &mut RunStats,
+    cmd: &mut CommandBuffer,
+    world: &SubWorld,
+) {
+    let mut query = <(Entity, Read<ApplyBulkPlan>)>::query();
+
+    for (entity, ApplyBulkPlan { tool, plan }) in query.iter(world) {
+        cmd.remove(*entity);
+
+        // the plan judged each tile as if the ones before it were done, so doing them in the same
+        // order can't go wrong
+        for (x, y, costs) in plan.accepted() {
+            match *tool {
+                PaintTool::ChangeTile(desired) => {
+                    let before = map.get_tile(x, y);
+                    stats.add_spent(ResourceCategory::TileChanges, costs);
+                    map.set_tile(x, y, desired);
+
+                    undo_stack.record(
+                        tick.0,
+                        UndoAction::ChangeTile {
+                            x,
+                            y,
+                            before,
+                            after: desired,
+                            paid: costs.clone(),
+                        },
+                    );
+                }
+                PaintTool::Build(desired) => {
+                    stats.add_spent(ResourceCategory::Building(desired), costs);
+                    last_built.0 = Some(desired);
+
+                    let built = build_structure(cmd, desired, x, y, next_wave_state.next_wave);
+
+                    undo_stack.record(
+                        tick.0,
+                        UndoAction::Build {
+                            entity: built,
+                            kind: desired,
+                            tile: (x, y),
+                            paid: costs.clone(),
+                        },
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use legion::{Resources, Schedule, World};
+
+    use super::*;
+
+    const BOUNDS: WorldBounds = WorldBounds {
+        x_min: 0,
+        y_min: 0,
+        x_max: 8,
+        y_max: 4,
+    };
+    const START_MONEY: i64 = 30;
+    const WALL_COST: i64 = 3;
+    const MUD_TO_WALL_COST: i64 = 5;
+    const TRAP_COST: i64 = 10;
+
+    /// A corridor along y = 0 with a spawn in the middle and a core at the left end, and a row at
+    /// y = 2 which isn't on anyone's way, with one tile of mud
+    fn setup() -> (World, Resources) {
+        let mut map = Map::new(BOUNDS);
+        for x in 0..7 {
+            map.set_tile(x, 0, Tile::Open);
+            map.set_tile(x, 2, Tile::Open);
+        }
+        map.set_tile(1, 2, Tile::Mud);
+        map.set_spawns(vec![(3, 0)].into_iter().collect());
+        map.set_cores(vec![(0, 0)].into_iter().collect());
+
+        let mut transforms = TileTransforms::new();
+        for (source, cost) in [(Tile::Open, WALL_COST), (Tile::Mud, MUD_TO_WALL_COST)].iter().copied() {
+            transforms.add(TileTransformDesc {
+                source,
+                target: Tile::Wall,
+                cost: money(cost),
+            });
+        }
+
+        let mut builds = StructureBuilds::new();
+        builds.add(StructureBuildDesc {
+            tile: Tile::Open,
+            kind: StructureKind::GasTrap,
+            cost: money(TRAP_COST),
+        });
+
+        let mut resources = Resources::default();
+        resources.insert(map);
+        resources.insert(transforms);
+        resources.insert(builds);
+        resources.insert(money(START_MONEY));
+        resources.insert(TickCount(0));
+        resources.insert(UndoStack::default());
+        resources.insert(RunStats::default());
+        resources.insert(NextWaveState::default());
+        resources.insert(LastBuilt::default());
+
+        (World::default(), resources)
+    }
+
+    fn money(amount: i64) -> OwnedResources {
+        OwnedResources::new().with(OwnedResource::Money, amount)
+    }
+
+    fn money_owned(resources: &Resources) -> i64 {
+        resources
+            .get::<OwnedResources>()
+            .unwrap()
+            .0
+            .get(&OwnedResource::Money)
+            .copied()
+            .unwrap_or(0)
+    }
+
+    fn run(world: &mut World, resources: &mut Resources, bulk: TryBulkAction) {
+        world.push((bulk,));
+        Schedule::builder()
+            .add_system(process_bulk_actions_system())
+            .flush()
+            .add_system(apply_bulk_plans_system())
+            .build()
+            .execute(world, resources);
+    }
+
+    fn structures(world: &World) -> usize {
+        <Read<Structure>>::query().iter(world).count()
+    }
+
+    #[test]
+    fn a_change_pays_once_for_every_tile() {
+        let (mut world, mut resources) = setup();
+        let tiles = vec![(1, 2), (2, 2), (3, 2)];
+
+        let plan = PaintPlan::new(
+            PaintTool::ChangeTile(Tile::Wall),
+            &tiles,
+            &resources.get::<Map>().unwrap(),
+            &resources.get::<TileTransforms>().unwrap(),
+            &resources.get::<StructureBuilds>().unwrap(),
+            &HashSet::new(),
+            None,
+        );
+        let per_tile: i64 = plan.accepted().map(|(_, _, cost)| cost.0[&OwnedResource::Money]).sum();
+        assert_eq!(per_tile, MUD_TO_WALL_COST + 2 * WALL_COST);
+
+        run(
+            &mut world,
+            &mut resources,
+            TryBulkAction::Paint {
+                tool: PaintTool::ChangeTile(Tile::Wall),
+                tiles: tiles.clone(),
+            },
+        );
+
+        for &(x, y) in tiles.iter() {
+            assert_eq!(resources.get::<Map>().unwrap().get_tile(x, y), Tile::Wall);
+        }
+        assert_eq!(money_owned(&resources), START_MONEY - per_tile);
+        assert_eq!(
+            resources.get::<RunStats>().unwrap().total_spent().0[&OwnedResource::Money],
+            per_tile
+        );
+
+        let undo_stack = resources.get::<UndoStack>().unwrap();
+        assert_eq!(undo_stack.len(), 1);
+        assert_eq!(undo_stack.last().unwrap().actions.len(), tiles.len());
+    }
+
+    #[test]
+    fn one_blocking_tile_stops_the_whole_change() {
+        let (mut world, mut resources) = setup();
+
+        // the last tile would cut the spawn off from the core
+        run(
+            &mut world,
+            &mut resources,
+            TryBulkAction::Paint {
+                tool: PaintTool::ChangeTile(Tile::Wall),
+                tiles: vec![(2, 2), (3, 2), (2, 0)],
+            },
+        );
+
+        let map = resources.get::<Map>().unwrap();
+        for &(x, y) in [(2, 2), (3, 2), (2, 0)].iter() {
+            assert_eq!(map.get_tile(x, y), Tile::Open);
+        }
+        assert_eq!(money_owned(&resources), START_MONEY);
+        assert!(resources.get::<UndoStack>().unwrap().is_empty());
+    }
+
+    #[test]
+    fn one_unaffordable_tile_stops_the_whole_build() {
+        let (mut world, mut resources) = setup();
+        let tiles: Vec<(i32, i32)> = (2..6).map(|x| (x, 2)).collect();
+        assert!(START_MONEY < TRAP_COST * tiles.len() as i64);
+
+        run(
+            &mut world,
+            &mut resources,
+            TryBulkAction::Paint {
+                tool: PaintTool::Build(StructureKind::GasTrap),
+                tiles,
+            },
+        );
+
+        assert_eq!(structures(&world), 0);
+        assert_eq!(money_owned(&resources), START_MONEY);
+        assert!(resources.get::<UndoStack>().unwrap().is_empty());
+    }
+
+    #[test]
+    fn one_unbuildable_tile_stops_the_whole_build() {
+        let (mut world, mut resources) = setup();
+
+        // traps only go on open ground, and (1, 2) is mud
+        run(
+            &mut world,
+            &mut resources,
+            TryBulkAction::Paint {
+                tool: PaintTool::Build(StructureKind::GasTrap),
+                tiles: vec![(2, 2), (1, 2)],
+            },
+        );
+
+        assert_eq!(structures(&world), 0);
+        assert_eq!(money_owned(&resources), START_MONEY);
+
+        run(
+            &mut world,
+            &mut resources,
+            TryBulkAction::Paint {
+                tool: PaintTool::Build(StructureKind::GasTrap),
+                tiles: vec![(2, 2), (3, 2)],
+            },
+        );
+
+        assert_eq!(structures(&world), 2);
+        assert_eq!(money_owned(&resources), START_MONEY - 2 * TRAP_COST);
+    }
+
+    #[test]
+    fn selling_is_all_or_nothing() {
+        let (mut world, mut resources) = setup();
+        let mut trap_at = |x: i32| {
+            world.push((
+                Structure(StructureKind::GasTrap),
+                Position::at_tile_center(x, 2),
+                SellValue(money(4)),
+            ))
+        };
+        let (first, second, gone) = (trap_at(2), trap_at(3), trap_at(4));
+        world.remove(gone);
+
+        run(
+            &mut world,
+            &mut resources,
+            TryBulkAction::Sell {
+                to_sell: vec![first, second, gone],
+            },
+        );
+
+        assert_eq!(structures(&world), 2);
+        assert_eq!(money_owned(&resources), START_MONEY);
+
+        run(
+            &mut world,
+            &mut resources,
+            TryBulkAction::Sell {
+                to_sell: vec![first, second],
+            },
+        );
+
+        assert_eq!(structures(&world), 0);
+        assert_eq!(money_owned(&resources), START_MONEY + 8);
+        assert_eq!(resources.get::<UndoStack>().unwrap().last().unwrap().actions.len(), 2);
+    }
+}
@@ -6,17 +6,20 @@ use crate::resources::*;
 const KEYBOARD_MOVE_SPEED: i32 = 8;
 
 #[system]
-pub(super) fn camera_move(#[resource] keys: &KeysPressed, #[resource] camera: &mut TdCamera) {
-    if keys.right {
-        camera.left += KEYBOARD_MOVE_SPEED;
+pub(super) fn camera_move(#[resource] held: &HeldActions, #[resource] camera: &mut TdCamera) {
+    // the same speed across the screen, however far it's zoomed
+    let speed = camera.screen_to_world_len(KEYBOARD_MOVE_SPEED as f64);
+
+    if held.is_held(InputAction::PanRight) {
+        camera.left += speed;
     }
-    if keys.left {
-        camera.left -= KEYBOARD_MOVE_SPEED;
+    if held.is_held(InputAction::PanLeft) {
+        camera.left -= speed;
     }
-    if keys.up {
-        camera.top -= KEYBOARD_MOVE_SPEED;
+    if held.is_held(InputAction::PanUp) {
+        camera.top -= speed;
     }
-    if keys.down {
-        camera.top += KEYBOARD_MOVE_SPEED;
+    if held.is_held(InputAction::PanDown) {
+        camera.top += speed;
     }
 }
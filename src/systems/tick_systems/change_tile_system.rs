@@ -7,6 +7,9 @@ use crate::{components::*, resources::*};
 pub(super) fn process_tile_changes(
     #[resource] map: &mut Map,
     #[resource] owned_resources: &mut OwnedResources,
+    #[resource] tick: &TickCount,
+    #[resource] undo_stack: &mut UndoStack,
+    #[resource] stats: &mut RunStats,
     cmd: &mut CommandBuffer,
     world: &SubWorld,
 ) {
@@ -16,8 +19,21 @@ pub(super) fn process_tile_changes(
         let &TryChangeTileType { x, y, desired, ref costs } = try_change;
 
         if owned_resources.can_pay(costs) && map.can_set_tile(x, y, desired) {
+            let before = map.get_tile(x, y);
             owned_resources.pay(costs);
+            stats.add_spent(ResourceCategory::TileChanges, costs);
             map.set_tile(x, y, desired);
+
+            undo_stack.record(
+                tick.0,
+                UndoAction::ChangeTile {
+                    x,
+                    y,
+                    before,
+                    after: desired,
+                    paid: costs.clone(),
+                },
+            );
         }
 
         cmd.remove(*entity);
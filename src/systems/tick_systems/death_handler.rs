@@ -5,20 +5,76 @@ use legion::{world::SubWorld, *};
 
 use crate::{components::*, resources::*};
 
+const DEATH_PARTICLES: usize = 12;
+const DEATH_PARTICLE_SPEED: f64 = 3.;
+const DEATH_PARTICLE_TICKS: u32 = 14;
+const DEATH_PARTICLE_COLOR: &str = "#daa520";
+
 #[system]
 #[read_component(OnDeath)]
 #[read_component(Died)]
-// Note -- map is &mut because we have to verify the dijkstra map is fresh
-pub(super) fn death_handler(#[resource] owned: &mut OwnedResources, world: &mut SubWorld) {
-    let mut query = <(Read<Died>, Read<OnDeath>)>::query();
+#[read_component(Position)]
+#[read_component(WaveState)]
+#[read_component(LastHitBy)]
+#[write_component(DamageDealt)]
+pub(super) fn death_handler(
+    #[resource] owned: &mut OwnedResources,
+    #[resource] map: &mut Map,
+    #[resource] particles: &mut Particles,
+    #[resource] labels: &mut FloatingLabels,
+    #[resource] stats: &mut RunStats,
+    world: &mut SubWorld,
+) {
+    for (_, pos) in <(Read<Died>, Read<Position>)>::query().iter(world) {
+        particles.burst(
+            pos.x,
+            pos.y,
+            DEATH_PARTICLES,
+            DEATH_PARTICLE_SPEED,
+            DEATH_PARTICLE_TICKS,
+            DEATH_PARTICLE_COLOR,
+        );
+    }
+
+    let mut killers: Vec<Entity> = Vec::new();
 
-    for (_, on_death) in query.iter_mut(world) {
+    for (_, wave_state, last_hit) in <(Read<Died>, Read<WaveState>, TryRead<LastHitBy>)>::query().iter(world) {
+        stats.add_kill(
+            wave_state.wave_num,
+            last_hit.map(|hit| hit.kind).unwrap_or(DamageSource::Unknown),
+            last_hit.and_then(|hit| hit.tile),
+        );
+        killers.extend(last_hit.and_then(|hit| hit.source));
+    }
+
+    // anything which has hurt a mob has a damage count by now; if it's gone, it can't be credited
+    for killer in killers {
+        if let Ok(mut entry) = world.entry_mut(killer) {
+            if let Ok(dealt) = entry.get_component_mut::<DamageDealt>() {
+                dealt.kills += 1;
+            }
+        }
+    }
+
+    let mut query = <(Read<Died>, Read<OnDeath>, TryRead<Position>)>::query();
+
+    for (_, on_death, pos) in query.iter_mut(world) {
         // Type ascription because IJ is just lost
         let odr: &OnDeath = on_death;
         for death_event in odr.events.iter() {
             match *death_event {
                 DeathEvent::GetResources(kind, amount) => {
                     owned.receive(kind, amount);
+                    stats.add_earned(ResourceCategory::MobRewards, &OwnedResources::new().with(kind, amount));
+                    if let Some(pos) = pos {
+                        labels.add_reward(pos.x, pos.y, kind, amount);
+                    }
+                }
+                DeathEvent::ReleaseGas(amount) => {
+                    if let Some(pos) = pos {
+                        let (tile_x, tile_y) = pos.to_tile_coords();
+                        map.add_gas_to_tile(tile_x, tile_y, amount);
+                    }
                 }
             }
         }
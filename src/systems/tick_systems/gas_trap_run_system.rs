@@ -2,7 +2,7 @@
 
 use legion::{world::SubWorld, *};
 
-use crate::{components::*, resources::*, tile_helpers::coords_to_tile};
+use crate::{components::*, resources::*};
 
 #[system]
 #[read_component(PoisonGasTrap)]
@@ -11,7 +11,7 @@ pub(super) fn gas_traps_make_gas(#[resource] map: &mut Map, world: &mut SubWorld
     let mut query = <(Read<PoisonGasTrap>, Read<Position>)>::query();
 
     for (gas_trap, pos) in query.iter_mut(world) {
-        let (tile_x, tile_y) = coords_to_tile(pos.x, pos.y);
+        let (tile_x, tile_y) = pos.to_tile_coords();
         let amount: i32 = gas_trap.amount;
 
         map.add_gas_to_tile(tile_x, tile_y, amount);
@@ -1,34 +1,134 @@
 use legion::{systems::CommandBuffer, world::SubWorld, *};
 
-use crate::{components::*, resources::*};
+use crate::{components::*, resources::*, tile_helpers::tile_to_pixel_coords};
+
+/// What the one-off actions need to look at to decide what to do
+#[derive(Copy, Clone)]
+struct ActionContext<'a> {
+    select: &'a TdTileSelect,
+    last_built: LastBuilt,
+    map: &'a Map,
+    builds: &'a StructureBuilds,
+}
 
 #[system]
 #[read_component(UserKeyEvent)]
-pub(super) fn process_key_input(#[resource] keys: &mut KeysPressed, cmd: &mut CommandBuffer, world: &SubWorld) {
+#[read_component(Structure)]
+#[read_component(Position)]
+#[allow(clippy::too_many_arguments)]
+pub(super) fn process_key_input(
+    #[resource] held: &mut HeldActions,
+    #[resource] speed: &mut GameSpeed,
+    #[resource] camera: &mut TdCamera,
+    #[resource] paint: &mut PaintState,
+    #[resource] select: &TdTileSelect,
+    #[resource] last_built: &LastBuilt,
+    #[resource] map: &Map,
+    #[resource] builds: &StructureBuilds,
+    cmd: &mut CommandBuffer,
+    world: &SubWorld,
+) {
     let mut query = <(Entity, Read<UserKeyEvent>)>::query();
 
     for (entity, uke) in query.iter(world) {
-        let uke: &UserKeyEvent = uke;
-        match *uke {
-            UserKeyEvent::KeyDown(key) => *get_flag_mut(key, keys) = true,
-            UserKeyEvent::KeyUp(key) => *get_flag_mut(key, keys) = false,
-            UserKeyEvent::AllKeysUp => {
-                keys.left = false;
-                keys.right = false;
-                keys.down = false;
-                keys.up = false;
+        match uke {
+            UserKeyEvent::Pressed { code, action } if action.is_held() => held.press(code.clone(), *action),
+            UserKeyEvent::Pressed { action, .. } => {
+                let context = ActionContext {
+                    select,
+                    last_built: *last_built,
+                    map,
+                    builds,
+                };
+                run_action(*action, context, speed, camera, paint, cmd, world);
             }
+            UserKeyEvent::Released { code } => held.release(code),
+            UserKeyEvent::AllReleased => held.release_all(),
         }
 
         cmd.remove(*entity);
     }
 }
 
-fn get_flag_mut(key: UserKey, keys: &mut KeysPressed) -> &mut bool {
-    match key {
-        UserKey::Up => &mut keys.up,
-        UserKey::Down => &mut keys.down,
-        UserKey::Left => &mut keys.left,
-        UserKey::Right => &mut keys.right,
+/// Most actions just send the same message as the matching button would
+fn run_action(
+    action: InputAction,
+    context: ActionContext,
+    speed: &mut GameSpeed,
+    camera: &mut TdCamera,
+    paint: &mut PaintState,
+    cmd: &mut CommandBuffer,
+    world: &SubWorld,
+) {
+    match action {
+        // these last as long as the key is down, so they're handled by whatever reads HeldActions
+        InputAction::PanUp | InputAction::PanDown | InputAction::PanLeft | InputAction::PanRight => {}
+        // put the tool down first, if there is one
+        InputAction::Unselect if paint.tool.is_some() => *paint = PaintState::default(),
+        InputAction::Unselect => {
+            cmd.push((UserUnselectTile,));
+        }
+        InputAction::LaunchWave => {
+            cmd.push((TryLaunchWave,));
+        }
+        InputAction::ToggleAutoLaunch => {
+            cmd.push((ToggleAutoLaunchWave,));
+        }
+        InputAction::TogglePause => speed.paused = !speed.paused,
+        InputAction::CycleSpeed => speed.cycle(),
+        InputAction::BuildLast => {
+            if let (TdTileSelect::Selected { x, y, structures }, Some(kind)) = (context.select, context.last_built.0) {
+                if !structures.is_empty() {
+                    return;
+                }
+
+                let tile = context.map.get_tile(*x, *y);
+
+                if let Some(costs) = context.builds.list_all_for(tile).remove(&kind) {
+                    cmd.push((TryBuildStructure {
+                        x: *x,
+                        y: *y,
+                        desired: kind,
+                        costs,
+                    },));
+                }
+            }
+        }
+        InputAction::Sell => {
+            if let TdTileSelect::Selected { structures, .. } = context.select {
+                if let Some(to_sell) = structures.iter().find(|s| s.sell_value.is_some()) {
+                    cmd.push((TrySellStructure { to_sell: to_sell.entity },));
+                }
+            }
+        }
+        InputAction::CycleSelection => {
+            let mut tiles: Vec<(i32, i32)> = <(Read<Structure>, Read<Position>)>::query()
+                .iter(world)
+                .map(|(_, pos)| pos.to_tile_coords())
+                .collect();
+
+            // row by row, so repeated presses sweep across the map
+            tiles.sort_by_key(|&(x, y)| (y, x));
+            tiles.dedup();
+
+            let next = match context.select {
+                TdTileSelect::Selected { x, y, .. } => tiles
+                    .iter()
+                    .copied()
+                    .find(|&(tx, ty)| (ty, tx) > (*y, *x))
+                    .or_else(|| tiles.first().copied()),
+                TdTileSelect::None | TdTileSelect::Many { .. } => tiles.first().copied(),
+            };
+
+            if let Some((tile_x, tile_y)) = next {
+                cmd.push((UserClickTile { tile_x, tile_y },));
+
+                let (px, py) = tile_to_pixel_coords(tile_x, tile_y);
+                camera.center_on(px, py);
+            }
+        }
+        InputAction::Undo => {
+            cmd.push((TryUndo,));
+        }
     }
 }
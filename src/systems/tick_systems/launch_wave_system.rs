@@ -1,26 +1,49 @@
 use legion::{systems::CommandBuffer, world::SubWorld, *};
 
-use crate::{components::*, resources::*, tile_helpers::tile_to_pixel_coords};
+use crate::{components::*, resources::*};
 
 #[system]
 #[read_component(TryLaunchWave)]
 #[read_component(ToggleAutoLaunchWave)]
+#[read_component(Core)]
+#[read_component(Spawn)]
+#[read_component(Position)]
 pub(super) fn process_wave_launch(
     #[resource] next_wave_state: &mut NextWaveState,
-    #[resource] map: &Map,
+    #[resource] stats: &mut RunStats,
+    #[resource] mode: &GameMode,
     cmd: &mut CommandBuffer,
     world: &SubWorld,
 ) {
     let mut query = <(Entity, Read<TryLaunchWave>)>::query();
 
     for (entity, _try_change) in query.iter(world) {
-        if next_wave_state.delay_ticks == 0 {
-            let spawns = map.all_spawns();
+        let wave_num = next_wave_state.next_wave;
+        // a campaign level has only so many waves
+        let past_last_wave = match *mode {
+            GameMode::Campaign { level } => LEVELS[level].wave(wave_num).is_none(),
+            GameMode::Classic | GameMode::Endless { .. } => false,
+        };
 
-            let wave_delay = launch_wave(cmd, &spawns);
+        if next_wave_state.delay_ticks == 0 && !past_last_wave {
+            let spawns: Vec<(Spawn, (i32, i32))> = <(Read<Spawn>, Read<Position>)>::query()
+                .iter(world)
+                .map(|(spawn, pos)| (*spawn, pos.to_tile_coords()))
+                .collect();
+            let main_core = <(Read<Core>, Read<Position>)>::query()
+                .iter(world)
+                .find(|(core, _)| core.main)
+                .map(|(_, pos)| pos.to_tile_coords());
+
+            let plan = WavePlan::new(*mode, wave_num, &spawns);
+
+            for mob in plan.mobs.iter() {
+                launch_mob(cmd, &plan, mob, main_core);
+            }
+            stats.wave_mut(wave_num).spawned += plan.mobs.len();
 
             next_wave_state.next_wave += 1;
-            next_wave_state.delay_ticks = wave_delay;
+            next_wave_state.delay_ticks = plan.cooldown_ticks();
         }
 
         cmd.remove(*entity);
@@ -40,38 +63,71 @@ fn safe_reduce(m: &mut usize) {
     }
 }
 
-fn launch_wave(cmd: &mut CommandBuffer, spawns: &[(i32, i32)]) -> usize {
-    let mut spawn_idx = 0;
-    let mut max_delay = 0;
-    for delay in 0..10 {
-        let delay_ticks = delay * 20;
-        let (tile_x, tile_y) = spawns[spawn_idx];
-        let (x, y) = tile_to_pixel_coords(tile_x, tile_y);
-        spawn_idx = (spawn_idx + 1) % spawns.len();
-
-        cmd.push((
-            Position { x, y },
-            TdMob,
-            WaveState {
-                wave_num: 0,
-                wait_state: WaitState::Waiting {
-                    ticks_remaining: delay_ticks,
-                },
-            },
-            Renderable::Geometry(RenderGeometry::Circle { radius: 10 }),
-            MobHealth {
-                current_health: 100,
-                max_health: 100,
-            },
-            Breathes,
-            OnDeath {
-                events: vec![DeathEvent::GetResources(OwnedResource::Money, 5)],
+fn launch_mob(cmd: &mut CommandBuffer, plan: &WavePlan, mob: &PlannedMob, main_core: Option<(i32, i32)>) {
+    let desc = mob.kind.desc();
+    let health = plan.health_of(mob.kind);
+    let (reward_resource, reward_amount) = plan.reward_of(mob.kind);
+    let (tile_x, tile_y) = mob.spawn_tile;
+
+    let target = match main_core {
+        Some((x, y)) if mob.targets_main_core => PathTarget::Core { x, y },
+        _ => PathTarget::NearestCore,
+    };
+
+    let entity = cmd.push((
+        Position::at_tile_center(tile_x, tile_y),
+        TdMob,
+        WaveState {
+            wave_num: plan.wave_num,
+            wait_state: WaitState::Waiting {
+                ticks_remaining: mob.delay_ticks,
             },
-            Hidden,
-        ));
+        },
+        // the sprites are drawn just as wide as the mob
+        Renderable::Bitmap {
+            dx: -desc.radius,
+            dy: -desc.radius,
+            sprite: desc.sprite,
+        },
+        MobHealth {
+            current_health: health,
+            max_health: health,
+        },
+        OnDeath {
+            events: vec![DeathEvent::GetResources(reward_resource, reward_amount)],
+        },
+        Hidden,
+    ));
 
-        max_delay = max_delay.max(delay_ticks);
+    if desc.breathes {
+        cmd.add_component(entity, Breathes);
     }
 
-    max_delay + 20
+    // legion only takes tuples of up to eight components at once
+    cmd.add_component(
+        entity,
+        Movement {
+            kind: desc.movement,
+            target,
+            speed: if plan.has_affix(Affix::Fast) {
+                desc.speed * FAST_SPEED_PERCENT as f64 / 100.
+            } else {
+                desc.speed
+            },
+        },
+    );
+    cmd.add_component(entity, CoreDamage { amount: desc.core_damage });
+    cmd.add_component(entity, MobRadius(desc.radius));
+
+    if plan.has_affix(Affix::Armored) {
+        cmd.add_component(entity, Armor { percent: ARMOR_PERCENT });
+    }
+    if plan.has_affix(Affix::Regenerating) {
+        cmd.add_component(
+            entity,
+            Regenerates {
+                per_tick: REGENERATION_PER_TICK,
+            },
+        );
+    }
 }
@@ -0,0 +1,25 @@
+//! In a campaign level, the level is won once every one of its waves is over, and lost as soon as
+//! its victory condition can't be met any more
+
+use legion::*;
+
+use crate::resources::*;
+
+#[system]
+pub(super) fn level_outcome(#[resource] mode: &GameMode, #[resource] stats: &RunStats, #[resource] game_state: &mut GameState) {
+    let level = match *mode {
+        GameMode::Campaign { level } => &LEVELS[level],
+        GameMode::Classic | GameMode::Endless { .. } => return,
+    };
+
+    // if the main core fell, that's the end of it, whatever else happened
+    if *game_state != GameState::MainGame {
+        return;
+    }
+
+    if level.failure(stats).is_some() {
+        *game_state = GameState::Died;
+    } else if level.all_waves_over(stats) {
+        *game_state = GameState::Won;
+    }
+}
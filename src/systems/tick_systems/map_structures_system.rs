@@ -0,0 +1,27 @@
+//! Keeps the map's pathing cache in line with the core and spawn entities. The map only redoes
+//! the paths around cores which came or went, so this is cheap on ticks where nothing changed.
+
+use std::collections::BTreeSet;
+
+use legion::{world::SubWorld, *};
+
+use crate::{components::*, resources::*};
+
+#[system]
+#[read_component(Core)]
+#[read_component(Spawn)]
+#[read_component(Position)]
+pub(super) fn sync_map_structures(#[resource] map: &mut Map, world: &SubWorld) {
+    let cores: BTreeSet<(i32, i32)> = <(Read<Core>, Read<Position>)>::query()
+        .iter(world)
+        .map(|(_, pos)| pos.to_tile_coords())
+        .collect();
+
+    let spawns: BTreeSet<(i32, i32)> = <(Read<Spawn>, Read<Position>)>::query()
+        .iter(world)
+        .map(|(_, pos)| pos.to_tile_coords())
+        .collect();
+
+    map.set_cores(cores);
+    map.set_spawns(spawns);
+}
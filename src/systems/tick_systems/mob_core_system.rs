@@ -1,4 +1,4 @@
-//! Any TdMob that touched the core is deleted, wooo
+//! Any TdMob that touched a core damages it and is deleted, wooo
 
 use legion::{systems::CommandBuffer, world::SubWorld, *};
 
@@ -7,11 +7,34 @@ use crate::{components::*, resources::*};
 #[system]
 #[read_component(TdMob)]
 #[read_component(TouchedCore)]
-pub(super) fn mob_core_hits(#[resource] player_health: &mut PlayerHealth, cmd: &mut CommandBuffer, world: &mut SubWorld) {
-    let mut query = <(Entity, Read<TdMob>, Read<TouchedCore>)>::query();
+#[read_component(CoreDamage)]
+#[read_component(Position)]
+#[read_component(WaveState)]
+#[write_component(Core)]
+pub(super) fn mob_core_hits(#[resource] stats: &mut RunStats, cmd: &mut CommandBuffer, world: &mut SubWorld) {
+    let mut query = <(Entity, Read<TdMob>, Read<TouchedCore>, TryRead<CoreDamage>, TryRead<WaveState>)>::query();
 
-    for (entity, _, _) in query.iter(world) {
+    let mut hits: Vec<((i32, i32), i32)> = Vec::new();
+
+    for (entity, _, touched, damage, wave_state) in query.iter(world) {
         cmd.remove(*entity);
-        player_health.health -= 1;
+        if let Some(wave_state) = wave_state {
+            stats.wave_mut(wave_state.wave_num).leaked += 1;
+        }
+        hits.push(((touched.tile_x, touched.tile_y), damage.map(|d| d.amount).unwrap_or(1)));
+    }
+
+    if hits.is_empty() {
+        return;
+    }
+
+    for (core, pos) in <(Write<Core>, Read<Position>)>::query().iter_mut(world) {
+        let core_tile = pos.to_tile_coords();
+
+        for (tile, amount) in hits.iter() {
+            if *tile == core_tile {
+                core.health -= amount;
+            }
+        }
     }
 }
@@ -6,60 +6,254 @@ use legion::{systems::CommandBuffer, world::SubWorld, *};
 use crate::{
     components::*,
     resources::*,
-    tile_helpers::{coords_to_tile, tile_to_pixel_coords},
+    tile_helpers::{tile_to_pixel_coords, TILE_WIDTH_PIXELS},
 };
 
-const MOVE_SPEED: i32 = 2;
-const DIAG_MOVE_SPEED: i32 = 1;
+const WALL_DAMAGE: i32 = 1;
+
+/// The furthest a mob gets shoved by the mobs crowding it, per tick
+const MAX_SEPARATION_SPEED: f64 = 1.;
+/// Neighbors are looked up in cells of this size; about the widest a mob gets
+const CROWD_CELL_SIZE: i32 = TILE_WIDTH_PIXELS;
+
+/// What one mob is up to this tick, worked out before anyone moves
+struct CrowdMob {
+    entity: Entity,
+    pos: Position,
+    radius: i32,
+    kind: MovementKind,
+    /// Where it would go this tick if nobody were in the way
+    step: (f64, f64),
+}
 
 #[system]
 #[write_component(Position)]
 #[read_component(TdMob)]
 #[read_component(WaveState)]
-// Note -- map is &mut because we have to verify the dijkstra map is fresh
-pub(super) fn move_mobs(#[resource] map: &mut Map, cmd: &mut CommandBuffer, world: &mut SubWorld) {
-    let mut query = <(Entity, Write<Position>, Read<TdMob>, Read<WaveState>)>::query();
+#[read_component(Movement)]
+#[read_component(MobRadius)]
+pub(super) fn move_mobs(#[resource] map: &Map, cmd: &mut CommandBuffer, world: &mut SubWorld) {
+    let mut query = <(
+        Entity,
+        Read<Position>,
+        Read<TdMob>,
+        Read<WaveState>,
+        Read<Movement>,
+        TryRead<MobRadius>,
+    )>::query();
+
+    let mut mobs: Vec<CrowdMob> = Vec::new();
 
-    for (entity, mut pos, _, wave_state) in query.iter_mut(world) {
+    for (entity, pos, _, wave_state, movement, radius) in query.iter(world) {
         if !matches!(wave_state.wait_state, WaitState::Active) {
             continue;
         }
 
-        // at the moment, the position is the center of the mob, and is used to compute which tile
-        // they're on, for the purpose of pathing. They figure out their goal tile and move toward
-        // the center of it.
-        {
-            let (tile_x, tile_y) = coords_to_tile(pos.x, pos.y);
-            let (next_x, next_y) = map.move_toward_spawn(tile_x, tile_y);
-            let (next_x, next_y) = tile_to_pixel_coords(next_x, next_y);
+        let radius = radius.map(|r| r.0).unwrap_or(0);
+
+        mobs.push(CrowdMob {
+            entity: *entity,
+            pos: *pos,
+            radius,
+            kind: movement.kind,
+            step: path_step(map, cmd, *pos, radius, movement),
+        });
+    }
+
+    // Everyone moves based on where the others were at the start of the tick, so the order
+    // they're processed in doesn't matter
+    let mut crowd = SpatialHash::new(CROWD_CELL_SIZE);
+    for (index, mob) in mobs.iter().enumerate() {
+        let (x, y) = mob.pos.to_pixel_coords();
+        crowd.insert(x, y, index);
+    }
+
+    let max_radius = mobs.iter().map(|mob| mob.radius).max().unwrap_or(0);
+
+    for (index, mob) in mobs.iter().enumerate() {
+        let (step, push) = avoid_crowd(index, &mobs, &crowd, max_radius);
+
+        let walked = (step.0, step.1);
+        let shoved = (step.0 + push.0, step.1 + push.1);
 
-            let dx = unit_diff(pos.x, next_x);
-            let dy = unit_diff(pos.y, next_y);
+        // the crowd can't push anyone somewhere they couldn't walk
+        let shoved_pos = Position {
+            x: mob.pos.x + shoved.0,
+            y: mob.pos.y + shoved.1,
+        };
+        let (shoved_x, shoved_y) = shoved_pos.to_tile_coords();
+        let (dx, dy) = if mob.kind.speed_percent(map.get_tile(shoved_x, shoved_y)).is_some() {
+            shoved
+        } else {
+            walked
+        };
 
-            let speed = if dx != 0 && dy != 0 { DIAG_MOVE_SPEED } else { MOVE_SPEED };
+        let new_pos = Position {
+            x: mob.pos.x + dx,
+            y: mob.pos.y + dy,
+        };
 
-            pos.x += dx * speed;
-            pos.y += dy * speed;
+        if let Ok(mut entry) = world.entry_mut(mob.entity) {
+            if let Ok(pos) = entry.get_component_mut::<Position>() {
+                *pos = new_pos;
+            }
         }
 
         // If they're now in the core
         // TODO: probably this should be in the core hits system
-        {
-            let (tile_x, tile_y) = coords_to_tile(pos.x, pos.y);
-            match map.get_tile(tile_x, tile_y) {
-                Tile::Core => cmd.add_component(*entity, TouchedCore),
-                _ => {}
-            }
+        let (tile_x, tile_y) = new_pos.to_tile_coords();
+        if map.has_core(tile_x, tile_y) {
+            cmd.add_component(mob.entity, TouchedCore { tile_x, tile_y });
         }
     }
 }
 
-fn unit_diff(start: i32, end: i32) -> i32 {
-    if start < end {
-        1
-    } else if start > end {
-        -1
+/// How the mob would move this tick following its path, ignoring other mobs; or, if the path
+/// goes through a wall, no move at all while it breaks the wall down
+fn path_step(map: &Map, cmd: &mut CommandBuffer, pos: Position, radius: i32, movement: &Movement) -> (f64, f64) {
+    // at the moment, the position is the center of the mob, and is used to compute which tile
+    // they're on, for the purpose of pathing. They figure out their goal tile and move toward
+    // it.
+    let (tile_x, tile_y) = pos.to_tile_coords();
+    let (next_tile_x, next_tile_y) = map.move_toward_spawn(movement.kind, movement.target, tile_x, tile_y);
+
+    // if the best way on is through a wall, stop and break it down first
+    if map.get_tile(next_tile_x, next_tile_y) == Tile::Wall {
+        cmd.push((DamageWall {
+            tile_x: next_tile_x,
+            tile_y: next_tile_y,
+            amount: WALL_DAMAGE,
+        },));
+        return (0., 0.);
+    }
+
+    let (goal_x, goal_y) = match cut_corner(map, movement, (tile_x, tile_y), (next_tile_x, next_tile_y)) {
+        Some((after_x, after_y)) => {
+            let (x, y) = tile_to_pixel_coords(after_x, after_y);
+            (x as f64, y as f64)
+        }
+        None => {
+            let (center_x, center_y) = tile_to_pixel_coords(next_tile_x, next_tile_y);
+            let (center_x, center_y) = (center_x as f64, center_y as f64);
+
+            // Head for the center of the next tile along the direction of travel, but across it,
+            // anywhere the mob still fits inside the tile will do, so a crowd can spread out side
+            // by side
+            let slack = (TILE_WIDTH_PIXELS / 2 - radius).max(0) as f64;
+            let goal_x = if next_tile_x == tile_x {
+                pos.x.clamp(center_x - slack, center_x + slack)
+            } else {
+                center_x
+            };
+            let goal_y = if next_tile_y == tile_y {
+                pos.y.clamp(center_y - slack, center_y + slack)
+            } else {
+                center_y
+            };
+            (goal_x, goal_y)
+        }
+    };
+
+    // terrain underfoot speeds the mob up or slows it down, but never stops it outright
+    let speed_percent = movement.kind.speed_percent(map.get_tile(tile_x, tile_y)).unwrap_or(100);
+    let speed = movement.speed * speed_percent as f64 / 100.;
+
+    // the same speed in every direction, without overshooting a goal closer than a full step
+    let (dx, dy) = (goal_x - pos.x, goal_y - pos.y);
+    let dist = (dx * dx + dy * dy).sqrt();
+
+    if dist <= speed {
+        (dx, dy)
+    } else {
+        (dx / dist * speed, dy / dist * speed)
+    }
+}
+
+/// If the path turns a corner right after the next tile, and the tile on the inside of that
+/// corner can be crossed too, the tile after next: the mob can cut straight across to it, instead
+/// of going to the middle of the next tile and turning there
+fn cut_corner(map: &Map, movement: &Movement, here: (i32, i32), next: (i32, i32)) -> Option<(i32, i32)> {
+    if next == here {
+        return None;
+    }
+
+    let after = map.move_toward_spawn(movement.kind, movement.target, next.0, next.1);
+
+    if (after.0 - here.0).abs() != 1 || (after.1 - here.1).abs() != 1 {
+        return None;
+    }
+
+    let inside = (here.0 + after.0 - next.0, here.1 + after.1 - next.1);
+    let crossable = |(x, y): (i32, i32)| movement.kind.speed_percent(map.get_tile(x, y)).is_some();
+
+    if crossable(inside) && crossable(after) {
+        Some(after)
     } else {
-        0
+        None
     }
 }
+
+/// Adjust the mob's step for the mobs around it, giving (step, push). It waits rather than walk
+/// into a mob ahead of it which isn't getting out of the way, so mobs queue up in corridors;
+/// and any mobs it overlaps push it away, so they spread out rather than stack up.
+fn avoid_crowd(index: usize, mobs: &[CrowdMob], crowd: &SpatialHash<usize>, max_radius: i32) -> ((f64, f64), (f64, f64)) {
+    let me = &mobs[index];
+    let mut step = me.step;
+    let (mut push_x, mut push_y) = (0., 0.);
+
+    let (x, y) = me.pos.to_pixel_coords();
+
+    // one pixel further, since the hash only knows which whole pixel everyone is in
+    for (_, other_index) in crowd.near(x, y, me.radius + max_radius + 1) {
+        if other_index == index {
+            continue;
+        }
+
+        let other = &mobs[other_index];
+
+        let (dx, dy) = (me.pos.x - other.pos.x, me.pos.y - other.pos.y);
+        let dist = (dx * dx + dy * dy).sqrt();
+        let min_dist = (me.radius + other.radius) as f64;
+
+        if dist >= min_dist {
+            continue;
+        }
+
+        let ahead = dx * me.step.0 + dy * me.step.1 < 0.;
+        let coming_back = other.step.0 * me.step.0 + other.step.1 * me.step.1 < 0.;
+        // if each is in the other's way (say, paths crossing at an angle) one has to go first
+        let other_waiting_on_me = dx * other.step.0 + dy * other.step.1 > 0.;
+
+        if ahead && !coming_back && (!other_waiting_on_me || other_index < index) {
+            step = (0., 0.);
+        }
+
+        let overlap = min_dist - dist;
+
+        let (away_x, away_y) = if dist == 0. {
+            // exactly on top of each other; split them sideways, one each way
+            let side = if index < other_index { 1. } else { -1. };
+            let (sx, sy) = me.step;
+            let len = (sx * sx + sy * sy).sqrt();
+            if len == 0. {
+                (side, 0.)
+            } else {
+                (-sy / len * side, sx / len * side)
+            }
+        } else {
+            (dx / dist, dy / dist)
+        };
+
+        push_x += away_x * overlap;
+        push_y += away_y * overlap;
+    }
+
+    let push_len = (push_x * push_x + push_y * push_y).sqrt();
+    let push = if push_len > MAX_SEPARATION_SPEED {
+        (push_x / push_len * MAX_SEPARATION_SPEED, push_y / push_len * MAX_SEPARATION_SPEED)
+    } else {
+        (push_x, push_y)
+    };
+
+    (step, push)
+}
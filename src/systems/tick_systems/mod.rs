@@ -4,10 +4,12 @@ use super::ScheduleBuilderExt;
 
 // user input systems
 mod build_structure_system;
+mod bulk_action_system;
 mod change_tile_system;
 mod keyboard_system;
 mod launch_wave_system;
 mod sell_structure_system;
+mod undo_system;
 mod user_click_system;
 
 // "every tick" systems
@@ -17,42 +19,70 @@ mod death_cleanup; // delete all mobs which have an associated death component
 mod death_handler; // process on-death events for all dead things
 mod gas_dispersal; // gas should spread out
 mod gas_trap_run_system; // gas traps generate poison gas
-mod mob_core_system; // if a mob touches the core, deduct player health and destroy (not kill) the mob
+mod level_outcome_system; // in a campaign level, win or lose by the level's victory condition
+mod map_structures_system; // point the map's path goals and spawns at the core and spawn entities
+mod mob_core_system; // if a mob touches a core, deduct core health and destroy (not kill) the mob
 mod mob_death_tracker; // if mob health <= 0, give them death component
 mod mob_movement_system; // mobs follow their movement AI
-mod player_death_system; // if player dies, end the game
+mod particle_system; // particles and floating labels move and expire
+mod player_death_system; // destroyed cores fall; if the player has lost, end the game
+mod regeneration_system; // regenerating mobs heal a little
 mod take_damage_system; // handle "take damage events"
+mod tick_count_system; // count the ticks, so recent actions can be told from old ones
+mod wall_damage_system; // handle "damage wall" events; broken walls become open ground
 mod wave_update_system; // tick the wave counter and spawn enemies if appropriate
 
 fn add_input_systems(builder: &mut Builder) -> &mut Builder {
     builder
+        // first, since most actions just send the same messages as the buttons do
+        .add_system_and_flush(keyboard_system::process_key_input_system())
         .add_system_and_flush(user_click_system::process_tile_clicks_system())
+        // before anything new is done, so it takes back what was done on an earlier step
+        .add_system_and_flush(undo_system::process_undo_system())
+        .add_system_and_flush(bulk_action_system::process_bulk_actions_system())
+        .add_system_and_flush(bulk_action_system::apply_bulk_plans_system())
         .add_system_and_flush(change_tile_system::process_tile_changes_system())
         .add_system_and_flush(sell_structure_system::sell_structures_system())
         .add_system_and_flush(build_structure_system::build_structures_system())
         .add_system_and_flush(launch_wave_system::process_wave_launch_system())
-        .add_system_and_flush(keyboard_system::process_key_input_system())
+        .add_system_and_flush(map_structures_system::sync_map_structures_system())
+        .add_system_and_flush(camera_move_system::camera_move_system())
 }
 
 fn add_auto_systems(builder: &mut Builder) -> &mut Builder {
     builder
-        .add_system_and_flush(camera_move_system::camera_move_system())
+        // again, in case a core fell on an earlier tick of the same step
+        .add_system_and_flush(map_structures_system::sync_map_structures_system())
+        .add_system_and_flush(tick_count_system::count_ticks_system())
         .add_system_and_flush(wave_update_system::update_wave_state_system())
         .add_system_and_flush(gas_trap_run_system::gas_traps_make_gas_system())
         .add_system_and_flush(gas_dispersal::disperse_gas_system())
         .add_system_and_flush(mob_movement_system::move_mobs_system())
         .add_system_and_flush(breathe_gas_system::breathe_gas_system())
+        // before the damage is taken, so a mob can't heal its way back from dead
+        .add_system_and_flush(regeneration_system::regenerate_system())
         .add_system_and_flush(mob_core_system::mob_core_hits_system())
         .add_system_and_flush(player_death_system::player_death_system())
         .add_system_and_flush(take_damage_system::take_damage_system())
+        .add_system_and_flush(wall_damage_system::damage_walls_system())
         .add_system_and_flush(mob_death_tracker::mobs_die_at_no_health_system())
         .add_system_and_flush(death_handler::death_handler_system())
         .add_system_and_flush(death_cleanup::death_cleanup_system())
+        .add_system_and_flush(level_outcome_system::level_outcome_system())
+        .add_system_and_flush(particle_system::move_particles_system())
 }
 
-pub fn make_tick_schedule() -> Schedule {
+/// Handles what the player asked for; runs once per step, even while the game is paused
+pub fn make_input_schedule() -> Schedule {
     let mut builder = Schedule::builder();
     add_input_systems(&mut builder);
+    builder.build()
+}
+
+/// Moves the game along by one tick; runs after the input schedule, as many times per step as
+/// the game speed says
+pub fn make_sim_schedule() -> Schedule {
+    let mut builder = Schedule::builder();
     add_auto_systems(&mut builder);
     builder.build()
 }
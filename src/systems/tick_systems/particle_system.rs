@@ -0,0 +1,11 @@
+//! Particles drift, slow down and fade out, and so do floating labels
+
+use legion::*;
+
+use crate::resources::*;
+
+#[system]
+pub(super) fn move_particles(#[resource] particles: &mut Particles, #[resource] labels: &mut FloatingLabels) {
+    particles.tick();
+    labels.tick();
+}
@@ -1,12 +1,29 @@
-//! System to detect and enforce player death
+//! System to knock down destroyed cores, and to detect and enforce player death
 
-use legion::*;
+use legion::{systems::CommandBuffer, world::SubWorld, *};
 
-use crate::resources::*;
+use crate::{components::*, resources::*};
 
 #[system]
-pub(super) fn player_death(#[resource] player_health: &mut PlayerHealth, #[resource] game_state: &mut GameState) {
-    if player_health.health <= 0 {
+#[read_component(Core)]
+pub(super) fn player_death(#[resource] game_state: &mut GameState, cmd: &mut CommandBuffer, world: &SubWorld) {
+    let mut cores_standing = 0;
+
+    for (entity, core) in <(Entity, Read<Core>)>::query().iter(world) {
+        if core.health > 0 {
+            cores_standing += 1;
+            continue;
+        }
+
+        // The core falls; once it's cleaned up, mobs stop heading for it
+        cmd.add_component(*entity, Died);
+
+        if core.main {
+            *game_state = GameState::Died;
+        }
+    }
+
+    if cores_standing == 0 {
         *game_state = GameState::Died;
     }
 }
@@ -0,0 +1,22 @@
+//! Mobs which regenerate get a little health back every tick, up to their max
+
+use legion::{world::SubWorld, *};
+
+use crate::components::*;
+
+#[system]
+#[read_component(Regenerates)]
+#[read_component(WaveState)]
+#[write_component(MobHealth)]
+pub(super) fn regenerate(world: &mut SubWorld) {
+    let mut query = <(Read<Regenerates>, Read<WaveState>, Write<MobHealth>)>::query();
+
+    for (regenerates, wave_state, health) in query.iter_mut(world) {
+        // the dead stay dead, and the ones still waiting to set off are already at full health
+        if !matches!(wave_state.wait_state, WaitState::Active) || health.current_health <= 0 {
+            continue;
+        }
+
+        health.current_health = (health.current_health + regenerates.per_tick).min(health.max_health);
+    }
+}
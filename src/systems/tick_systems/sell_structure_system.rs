@@ -1,11 +1,30 @@
-use legion::{systems::CommandBuffer, world::SubWorld, *};
+use legion::{
+    systems::CommandBuffer,
+    world::{EntryRef, SubWorld},
+    *,
+};
 
 use crate::{components::*, resources::*};
 
 #[system]
 #[read_component(TrySellStructure)]
 #[read_component(SellValue)]
-pub(super) fn sell_structures(#[resource] owned_resources: &mut OwnedResources, cmd: &mut CommandBuffer, world: &SubWorld) {
+#[read_component(Position)]
+#[read_component(Structure)]
+#[read_component(Core)]
+#[read_component(Spawn)]
+#[read_component(PoisonGasTrap)]
+#[read_component(Renderable)]
+#[read_component(OnDeath)]
+#[read_component(DamageDealt)]
+pub(super) fn sell_structures(
+    #[resource] owned_resources: &mut OwnedResources,
+    #[resource] tick: &TickCount,
+    #[resource] undo_stack: &mut UndoStack,
+    #[resource] stats: &mut RunStats,
+    cmd: &mut CommandBuffer,
+    world: &SubWorld,
+) {
     let mut query = <(Entity, Read<TrySellStructure>)>::query();
 
     for (entity, try_sell) in query.iter(world) {
@@ -14,11 +33,54 @@ pub(super) fn sell_structures(#[resource] owned_resources: &mut OwnedResources,
         let to_sell: Entity = try_sell.to_sell;
 
         if let Ok(existing) = world.entry_ref(to_sell) {
-            if let Ok(sell_value) = existing.get_component::<SellValue>() {
-                owned_resources.receive_all(&sell_value.0);
-            }
-
-            cmd.remove(to_sell);
+            sell_structure(&existing, to_sell, owned_resources, stats, undo_stack, tick, cmd);
         }
     }
 }
+
+/// Sell the structure: pay out its sell value and take it away, keeping what's needed to put it
+/// back on undo. The entry must be for `to_sell`, from a world where every component a structure
+/// can have is readable.
+pub(super) fn sell_structure(
+    existing: &EntryRef,
+    to_sell: Entity,
+    owned_resources: &mut OwnedResources,
+    stats: &mut RunStats,
+    undo_stack: &mut UndoStack,
+    tick: &TickCount,
+    cmd: &mut CommandBuffer,
+) {
+    let received = match existing.get_component::<SellValue>() {
+        Ok(sell_value) => sell_value.0.clone(),
+        Err(_) => OwnedResources::new(),
+    };
+
+    owned_resources.receive_all(&received);
+    stats.add_earned(ResourceCategory::Sales, &received);
+
+    // kept whole, so undoing the sale puts back exactly what was there
+    if let (Ok(position), Ok(structure)) = (existing.get_component::<Position>(), existing.get_component::<Structure>()) {
+        let sold = SoldStructure {
+            position: *position,
+            structure: *structure,
+            core: existing.get_component::<Core>().ok().copied(),
+            spawn: existing.get_component::<Spawn>().ok().copied(),
+            trap: existing.get_component::<PoisonGasTrap>().ok().copied(),
+            renderable: existing.get_component::<Renderable>().ok().copied(),
+            sell_value: existing.get_component::<SellValue>().ok().cloned(),
+            on_death: existing.get_component::<OnDeath>().ok().cloned(),
+            damage_dealt: existing.get_component::<DamageDealt>().ok().copied(),
+        };
+
+        undo_stack.record(
+            tick.0,
+            UndoAction::Sell {
+                entity: to_sell,
+                sold,
+                received,
+            },
+        );
+    }
+
+    cmd.remove(to_sell);
+}
@@ -2,13 +2,31 @@
 
 use legion::{systems::CommandBuffer, world::SubWorld, *};
 
-use crate::components::*;
+use crate::{components::*, resources::*};
 use wasm_bindgen::__rt::std::collections::HashMap;
 
+/// A hit throws out a few specks per point of damage, up to a limit, so even a crowd choking on
+/// gas every tick stays cheap
+const MAX_DAMAGE_PARTICLES: i32 = 3;
+const DAMAGE_PARTICLE_SPEED: f64 = 2.;
+const DAMAGE_PARTICLE_TICKS: u32 = 8;
+const DAMAGE_PARTICLE_COLOR: &str = "#d02020";
+
 #[system]
 #[write_component(MobHealth)]
 #[read_component(TakeDamage)]
-pub(super) fn take_damage(cmd: &mut CommandBuffer, world: &mut SubWorld) {
+#[read_component(Position)]
+#[read_component(WaveState)]
+#[read_component(Structure)]
+#[read_component(Armor)]
+#[write_component(DamageDealt)]
+pub(super) fn take_damage(
+    #[resource] particles: &mut Particles,
+    #[resource] labels: &mut FloatingLabels,
+    #[resource] stats: &mut RunStats,
+    cmd: &mut CommandBuffer,
+    world: &mut SubWorld,
+) {
     let mut query_get_takes = <(Entity, Read<TakeDamage>)>::query();
     let mut damages = HashMap::new();
 
@@ -20,15 +38,107 @@ pub(super) fn take_damage(cmd: &mut CommandBuffer, world: &mut SubWorld) {
             .push(*take_damage);
     }
 
+    // looked up before any damage is done, since the sources can't be looked at while the
+    // targets are being changed
+    let source_kinds: HashMap<Entity, (DamageSource, Option<(i32, i32)>)> = damages
+        .values()
+        .flatten()
+        .filter_map(|event| event.source)
+        .map(|source| {
+            let entry = world.entry_ref(source).ok();
+            let kind = match entry.as_ref().and_then(|e| e.get_component::<Structure>().ok().copied()) {
+                Some(Structure(kind)) => DamageSource::Structure(kind),
+                None => DamageSource::Unknown,
+            };
+            let tile = entry
+                .as_ref()
+                .and_then(|e| e.get_component::<Position>().ok().map(|pos| pos.to_tile_coords()));
+            (source, (kind, tile))
+        })
+        .collect();
+
+    let kind_of = |source: Option<Entity>| {
+        source
+            .and_then(|s| source_kinds.get(&s).copied())
+            .unwrap_or((DamageSource::Unknown, None))
+    };
+
+    let mut dealt: HashMap<Entity, i64> = HashMap::new();
+
     for (damaged_entity, events) in damages {
         // err in this part probably means the mob is already dead, which is fine
         if let Ok(mut entity_mut) = world.entry_mut(damaged_entity) {
+            let wave_num = entity_mut.get_component::<WaveState>().ok().map(|wave_state| wave_state.wave_num);
+            let armor = entity_mut.get_component::<Armor>().map(|armor| armor.percent).unwrap_or(0);
             let mob_health = entity_mut
                 .get_component_mut::<MobHealth>()
                 .expect("System should ensure targeted mobs have health");
-            for event in events {
-                mob_health.current_health -= event.amount;
+
+            // armour comes off the tick's damage as a whole, so many small hits aren't rounded down
+            // one at a time
+            let raw: i32 = events.iter().map(|event| event.amount).sum();
+            let total = raw - raw * armor / 100;
+            mob_health.current_health -= total;
+
+            // whatever hit hardest this tick (the first, on a tie) gets the credit, if this is what kills it
+            let hardest = events
+                .iter()
+                .enumerate()
+                .rev()
+                .max_by_key(|(_, event)| event.amount)
+                .map(|(idx, _)| idx);
+            let shares = share_out(&events, raw, total, hardest);
+
+            for (event, share) in events.iter().zip(shares) {
+                if let Some(source) = event.source {
+                    *dealt.entry(source).or_insert(0) += share;
+                }
+                if let Some(wave_num) = wave_num {
+                    let (kind, tile) = kind_of(event.source);
+                    stats.add_damage(wave_num, kind, tile, share);
+                }
+            }
+
+            let source = hardest.and_then(|idx| events[idx].source);
+            let (kind, tile) = kind_of(source);
+            cmd.add_component(damaged_entity, LastHitBy { source, kind, tile });
+
+            if let Ok(pos) = entity_mut.get_component::<Position>() {
+                labels.add_damage(damaged_entity, pos.x, pos.y, total as i64);
+                particles.burst(
+                    pos.x,
+                    pos.y,
+                    total.clamp(0, MAX_DAMAGE_PARTICLES) as usize,
+                    DAMAGE_PARTICLE_SPEED,
+                    DAMAGE_PARTICLE_TICKS,
+                    DAMAGE_PARTICLE_COLOR,
+                );
             }
         }
     }
+
+    for (source, damage) in dealt {
+        if let Ok(mut entry) = world.entry_mut(source) {
+            match entry.get_component_mut::<DamageDealt>() {
+                Ok(so_far) => so_far.damage += damage,
+                Err(_) => cmd.add_component(source, DamageDealt { damage, kills: 0 }),
+            }
+        }
+    }
+}
+
+/// Split what a target actually lost among the hits it took, in proportion to how hard each was;
+/// whatever rounding leaves over goes to the hardest hit, so the shares add up to the total
+fn share_out(events: &[TakeDamage], raw: i32, total: i32, hardest: Option<usize>) -> Vec<i64> {
+    if raw == 0 {
+        return vec![0; events.len()];
+    }
+
+    let mut shares: Vec<i64> = events.iter().map(|event| event.amount as i64 * total as i64 / raw as i64).collect();
+
+    if let Some(hardest) = hardest {
+        shares[hardest] += total as i64 - shares.iter().sum::<i64>();
+    }
+
+    shares
 }
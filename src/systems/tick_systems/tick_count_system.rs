@@ -0,0 +1,8 @@
+use legion::*;
+
+use crate::resources::*;
+
+#[system]
+pub(super) fn count_ticks(#[resource] tick: &mut TickCount) {
+    tick.0 += 1;
+}
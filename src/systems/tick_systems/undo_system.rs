@@ -0,0 +1,457 @@
+//! Takes back the last thing the player did: tiles go back to what they were, structures they
+//! built are taken away and ones they sold are put back, and resources move back by exactly what
+//! they moved the first time. Everything done in the same step goes back together, or none of it.
+
+use std::collections::HashSet;
+
+use legion::{systems::CommandBuffer, world::SubWorld, *};
+
+use crate::{components::*, resources::*};
+
+/// Why the last group of actions can't be undone
+enum UndoFailure {
+    /// Something it would put back has changed since (a wall was broken, a trap destroyed), so it
+    /// never can be
+    Gone,
+    /// It would block the path or leave a spawn without a core, or the player can't pay back what
+    /// they were given; that might change
+    NotNow,
+}
+
+#[system]
+#[read_component(TryUndo)]
+#[read_component(WaveState)]
+#[read_component(Structure)]
+#[read_component(Position)]
+pub(super) fn process_undo(
+    #[resource] undo_stack: &mut UndoStack,
+    #[resource] tick: &TickCount,
+    #[resource] map: &mut Map,
+    #[resource] owned_resources: &mut OwnedResources,
+    #[resource] stats: &mut RunStats,
+    cmd: &mut CommandBuffer,
+    world: &SubWorld,
+) {
+    // whatever the player does from here on is a new thing to undo
+    undo_stack.close_group();
+
+    let mut asked = false;
+    for (entity, _) in <(Entity, Read<TryUndo>)>::query().iter(world) {
+        cmd.remove(*entity);
+        asked = true;
+    }
+
+    if !asked {
+        return;
+    }
+
+    let wave_active = <Read<WaveState>>::query().iter(world).next().is_some();
+    if !undo_stack.can_undo(tick.0, wave_active) {
+        return;
+    }
+
+    let outcome = match undo_stack.last() {
+        Some(group) => try_undo(group, map, owned_resources, world),
+        None => return,
+    };
+
+    match outcome {
+        Ok((undone_map, undone_resources)) => {
+            let group = undo_stack.pop().expect("The group was just looked at");
+
+            *map = undone_map;
+            *owned_resources = undone_resources;
+
+            for action in group.actions.iter().rev() {
+                match action {
+                    UndoAction::ChangeTile { paid, .. } => {
+                        stats.remove_spent(ResourceCategory::TileChanges, paid);
+                    }
+                    UndoAction::Build { entity, kind, paid, .. } => {
+                        stats.remove_spent(ResourceCategory::Building(*kind), paid);
+                        cmd.remove(*entity);
+                    }
+                    UndoAction::Sell { entity, sold, received } => {
+                        stats.remove_earned(ResourceCategory::Sales, received);
+                        let put_back = put_back(cmd, sold);
+                        undo_stack.replace_entity(*entity, put_back);
+                    }
+                }
+            }
+        }
+        Err(UndoFailure::Gone) => {
+            undo_stack.pop();
+        }
+        Err(UndoFailure::NotNow) => {}
+    }
+}
+
+/// What the map and resources would be with the group undone, newest action first, so each one
+/// sees the ones after it already undone
+fn try_undo(
+    group: &UndoGroup,
+    map: &Map,
+    owned_resources: &OwnedResources,
+    world: &SubWorld,
+) -> Result<(Map, OwnedResources), UndoFailure> {
+    let mut map = map.clone();
+    let mut owned_resources = owned_resources.clone();
+
+    let mut occupied: HashSet<(i32, i32)> = <(Read<Structure>, Read<Position>)>::query()
+        .iter(world)
+        .map(|(_, pos)| pos.to_tile_coords())
+        .collect();
+
+    for action in group.actions.iter().rev() {
+        match action {
+            UndoAction::ChangeTile { x, y, before, after, paid } => {
+                if map.get_tile(*x, *y) != *after {
+                    return Err(UndoFailure::Gone);
+                }
+
+                if !map.can_set_tile(*x, *y, *before) {
+                    return Err(UndoFailure::NotNow);
+                }
+
+                map.set_tile(*x, *y, *before);
+                owned_resources.receive_all(paid);
+            }
+            UndoAction::Build { entity, kind, tile, paid } => {
+                if world.entry_ref(*entity).is_err() {
+                    return Err(UndoFailure::Gone);
+                }
+
+                // taking a core away mustn't leave a spawn with nowhere to go
+                if *kind == StructureKind::Core {
+                    if !map.can_remove_core(tile.0, tile.1) {
+                        return Err(UndoFailure::NotNow);
+                    }
+
+                    let cores = map.cores().filter(|core| core != tile).collect();
+                    map.set_cores(cores);
+                }
+
+                occupied.remove(tile);
+                owned_resources.receive_all(paid);
+            }
+            UndoAction::Sell { sold, received, .. } => {
+                let (tile_x, tile_y) = sold.position.to_tile_coords();
+
+                let can_place = !occupied.contains(&(tile_x, tile_y)) && (sold.spawn.is_none() || map.can_add_spawn(tile_x, tile_y));
+
+                if !can_place || !owned_resources.can_pay(received) {
+                    return Err(UndoFailure::NotNow);
+                }
+
+                occupied.insert((tile_x, tile_y));
+                owned_resources.pay(received);
+            }
+        }
+    }
+
+    Ok((map, owned_resources))
+}
+
+fn put_back(cmd: &mut CommandBuffer, sold: &SoldStructure) -> Entity {
+    let entity = cmd.push((sold.position, sold.structure));
+
+    if let Some(core) = sold.core {
+        cmd.add_component(entity, core);
+    }
+    if let Some(spawn) = sold.spawn {
+        cmd.add_component(entity, spawn);
+    }
+    if let Some(trap) = sold.trap {
+        cmd.add_component(entity, trap);
+    }
+    if let Some(renderable) = sold.renderable {
+        cmd.add_component(entity, renderable);
+    }
+    if let Some(sell_value) = sold.sell_value.clone() {
+        cmd.add_component(entity, sell_value);
+    }
+    if let Some(on_death) = sold.on_death.clone() {
+        cmd.add_component(entity, on_death);
+    }
+    if let Some(damage_dealt) = sold.damage_dealt {
+        cmd.add_component(entity, damage_dealt);
+    }
+
+    entity
+}
+
+#[cfg(test)]
+mod tests {
+    use legion::{Resources, Schedule, World};
+
+    use super::*;
+
+    const BOUNDS: WorldBounds = WorldBounds {
+        x_min: 0,
+        y_min: 0,
+        x_max: 8,
+        y_max: 4,
+    };
+    const START_MONEY: i64 = 20;
+
+    /// A corridor along y = 0, with a spawn in the middle and the one core at the left end
+    fn setup() -> (World, Resources) {
+        let mut map = Map::new(BOUNDS);
+        for x in 0..7 {
+            map.set_tile(x, 0, Tile::Open);
+        }
+        map.set_spawns(vec![(3, 0)].into_iter().collect());
+        map.set_cores(vec![(0, 0)].into_iter().collect());
+
+        let mut resources = Resources::default();
+        resources.insert(map);
+        resources.insert(UndoStack::default());
+        resources.insert(TickCount(1000));
+        resources.insert(OwnedResources::new().with(OwnedResource::Money, START_MONEY));
+        resources.insert(RunStats::default());
+
+        (World::default(), resources)
+    }
+
+    fn money(amount: i64) -> OwnedResources {
+        OwnedResources::new().with(OwnedResource::Money, amount)
+    }
+
+    fn money_owned(resources: &Resources) -> i64 {
+        resources
+            .get::<OwnedResources>()
+            .unwrap()
+            .0
+            .get(&OwnedResource::Money)
+            .copied()
+            .unwrap_or(0)
+    }
+
+    fn groups(resources: &Resources) -> usize {
+        resources.get::<UndoStack>().unwrap().len()
+    }
+
+    fn record(resources: &Resources, tick: u64, action: UndoAction) {
+        let mut undo_stack = resources.get_mut::<UndoStack>().unwrap();
+        undo_stack.record(tick, action);
+        undo_stack.close_group();
+    }
+
+    fn undo(world: &mut World, resources: &mut Resources) {
+        world.push((TryUndo,));
+        Schedule::builder()
+            .add_system(process_undo_system())
+            .build()
+            .execute(world, resources);
+    }
+
+    fn opened_wall(x: i32, y: i32, paid: i64) -> UndoAction {
+        UndoAction::ChangeTile {
+            x,
+            y,
+            before: Tile::Wall,
+            after: Tile::Open,
+            paid: money(paid),
+        }
+    }
+
+    fn structure_at(world: &mut World, kind: StructureKind, tile: (i32, i32)) -> Entity {
+        world.push((Structure(kind), Position::at_tile_center(tile.0, tile.1)))
+    }
+
+    fn built(entity: Entity, kind: StructureKind, tile: (i32, i32), paid: i64) -> UndoAction {
+        UndoAction::Build {
+            entity,
+            kind,
+            tile,
+            paid: money(paid),
+        }
+    }
+
+    #[test]
+    fn tile_changes_go_back_with_exactly_what_was_paid() {
+        let (mut world, mut resources) = setup();
+        resources.get_mut::<Map>().unwrap().set_tile(3, 2, Tile::Open);
+        resources
+            .get_mut::<RunStats>()
+            .unwrap()
+            .add_spent(ResourceCategory::TileChanges, &money(7));
+        record(&resources, 1000, opened_wall(3, 2, 7));
+
+        undo(&mut world, &mut resources);
+
+        assert_eq!(resources.get::<Map>().unwrap().get_tile(3, 2), Tile::Wall);
+        assert_eq!(money_owned(&resources), START_MONEY + 7);
+        let spent = resources.get::<RunStats>().unwrap().total_spent();
+        assert_eq!(spent.0.get(&OwnedResource::Money).copied(), Some(0));
+        assert_eq!(groups(&resources), 0);
+    }
+
+    #[test]
+    fn changed_since_is_gone_for_good() {
+        let (mut world, mut resources) = setup();
+        // recorded as opened, but it's a wall again by now
+        record(&resources, 1000, opened_wall(3, 2, 7));
+
+        undo(&mut world, &mut resources);
+
+        assert_eq!(money_owned(&resources), START_MONEY);
+        assert_eq!(groups(&resources), 0);
+    }
+
+    #[test]
+    fn blocking_the_path_is_not_now() {
+        let (mut world, mut resources) = setup();
+        record(&resources, 1000, opened_wall(1, 0, 7));
+
+        undo(&mut world, &mut resources);
+
+        assert_eq!(resources.get::<Map>().unwrap().get_tile(1, 0), Tile::Open);
+        assert_eq!(money_owned(&resources), START_MONEY);
+        assert_eq!(groups(&resources), 1);
+    }
+
+    #[test]
+    fn builds_are_taken_away_and_paid_back() {
+        let (mut world, mut resources) = setup();
+        let trap = structure_at(&mut world, StructureKind::GasTrap, (5, 0));
+        record(&resources, 1000, built(trap, StructureKind::GasTrap, (5, 0), 12));
+
+        undo(&mut world, &mut resources);
+
+        assert!(world.entry_ref(trap).is_err());
+        assert_eq!(money_owned(&resources), START_MONEY + 12);
+        assert_eq!(groups(&resources), 0);
+    }
+
+    #[test]
+    fn a_built_core_stays_while_a_spawn_needs_it() {
+        let (mut world, mut resources) = setup();
+        let core = structure_at(&mut world, StructureKind::Core, (6, 0));
+        resources.get_mut::<Map>().unwrap().set_cores(vec![(6, 0)].into_iter().collect());
+        record(&resources, 1000, built(core, StructureKind::Core, (6, 0), 30));
+
+        undo(&mut world, &mut resources);
+
+        assert!(world.entry_ref(core).is_ok());
+        assert_eq!(money_owned(&resources), START_MONEY);
+        assert_eq!(groups(&resources), 1);
+
+        // with another core for the spawn to go to, it can go
+        resources
+            .get_mut::<Map>()
+            .unwrap()
+            .set_cores(vec![(0, 0), (6, 0)].into_iter().collect());
+        undo(&mut world, &mut resources);
+
+        assert!(world.entry_ref(core).is_err());
+        assert_eq!(money_owned(&resources), START_MONEY + 30);
+        assert_eq!(groups(&resources), 0);
+    }
+
+    #[test]
+    fn sold_structures_come_back_and_can_then_be_unbuilt() {
+        let (mut world, mut resources) = setup();
+        let trap = structure_at(&mut world, StructureKind::GasTrap, (5, 0));
+        record(&resources, 1000, built(trap, StructureKind::GasTrap, (5, 0), 12));
+
+        // sold for 8
+        let sold = SoldStructure {
+            position: Position::at_tile_center(5, 0),
+            structure: Structure(StructureKind::GasTrap),
+            core: None,
+            spawn: None,
+            trap: Some(PoisonGasTrap { amount: 3 }),
+            renderable: None,
+            sell_value: Some(SellValue(money(8))),
+            on_death: None,
+            damage_dealt: Some(DamageDealt { damage: 40, kills: 2 }),
+        };
+        world.remove(trap);
+        resources.get_mut::<OwnedResources>().unwrap().receive(OwnedResource::Money, 8);
+        record(
+            &resources,
+            1000,
+            UndoAction::Sell {
+                entity: trap,
+                sold: sold.clone(),
+                received: money(8),
+            },
+        );
+
+        undo(&mut world, &mut resources);
+
+        assert_eq!(money_owned(&resources), START_MONEY);
+        let put_back: Vec<Entity> = <(Entity, Read<Structure>)>::query().iter(&world).map(|(e, _)| *e).collect();
+        assert_eq!(put_back.len(), 1);
+        let entry = world.entry_ref(put_back[0]).unwrap();
+        assert_eq!(entry.get_component::<Position>().ok(), Some(&sold.position));
+        assert_eq!(entry.get_component::<SellValue>().ok(), sold.sell_value.as_ref());
+        assert_eq!(entry.get_component::<DamageDealt>().ok(), sold.damage_dealt.as_ref());
+
+        // the build it came from now refers to what was put back
+        undo(&mut world, &mut resources);
+
+        assert!(world.entry_ref(put_back[0]).is_err());
+        assert_eq!(money_owned(&resources), START_MONEY + 12);
+        assert_eq!(groups(&resources), 0);
+    }
+
+    #[test]
+    fn unselling_needs_the_sale_paid_back() {
+        let (mut world, mut resources) = setup();
+        let sold = SoldStructure {
+            position: Position::at_tile_center(5, 0),
+            structure: Structure(StructureKind::GasTrap),
+            core: None,
+            spawn: None,
+            trap: None,
+            renderable: None,
+            sell_value: None,
+            on_death: None,
+            damage_dealt: None,
+        };
+        let entity = world.push(());
+        record(
+            &resources,
+            1000,
+            UndoAction::Sell {
+                entity,
+                sold,
+                received: money(START_MONEY + 1),
+            },
+        );
+
+        undo(&mut world, &mut resources);
+
+        assert_eq!(money_owned(&resources), START_MONEY);
+        assert_eq!(<Read<Structure>>::query().iter(&world).count(), 0);
+        assert_eq!(groups(&resources), 1);
+    }
+
+    #[test]
+    fn during_a_wave_only_just_after() {
+        let (mut world, mut resources) = setup();
+        world.push((WaveState {
+            wave_num: 1,
+            wait_state: WaitState::Active,
+        },));
+        let trap = structure_at(&mut world, StructureKind::GasTrap, (5, 0));
+        record(
+            &resources,
+            1000 - UNDO_GRACE_TICKS - 1,
+            built(trap, StructureKind::GasTrap, (5, 0), 12),
+        );
+
+        undo(&mut world, &mut resources);
+
+        assert!(world.entry_ref(trap).is_ok());
+        assert_eq!(groups(&resources), 1);
+
+        resources.get_mut::<TickCount>().unwrap().0 -= 1;
+        undo(&mut world, &mut resources);
+
+        assert!(world.entry_ref(trap).is_err());
+        assert_eq!(groups(&resources), 0);
+    }
+}
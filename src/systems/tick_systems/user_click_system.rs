@@ -4,53 +4,88 @@ use crate::{components::*, resources::*};
 
 #[system]
 #[read_component(UserClickTile)]
+#[read_component(UserToggleTile)]
+#[read_component(UserSelectRect)]
 #[read_component(UserUnselectTile)]
 #[read_component(Structure)]
 #[read_component(SellValue)]
 #[read_component(Position)]
-pub(super) fn process_tile_clicks(#[resource] selected_tile: &mut TdTileSelect, cmd: &mut CommandBuffer, world: &SubWorld) {
+pub(super) fn process_tile_clicks(
+    #[resource] selected_tile: &mut TdTileSelect,
+    #[resource] build_hover: &mut TdBuildHover,
+    cmd: &mut CommandBuffer,
+    world: &SubWorld,
+) {
     let mut query = <(Entity, Read<UserClickTile>)>::query();
 
     for (entity, click_tile) in query.iter(world) {
         let &UserClickTile { tile_x, tile_y } = click_tile;
 
-        // TODO: get all the structures here, too
         *selected_tile = TdTileSelect::Selected {
             x: tile_x,
             y: tile_y,
             structures: vec![],
         };
 
+        // the build options the mouse was over go away with the old selection
+        *build_hover = TdBuildHover::None;
+        cmd.remove(*entity);
+    }
+
+    let mut query = <(Entity, Read<UserToggleTile>)>::query();
+
+    for (entity, toggle) in query.iter(world) {
+        let &UserToggleTile { tile_x, tile_y } = toggle;
+
+        let mut tiles = selected_tile.tiles();
+        if !tiles.remove(&(tile_x, tile_y)) {
+            tiles.insert((tile_x, tile_y));
+        }
+
+        *selected_tile = TdTileSelect::of_tiles(tiles);
+        *build_hover = TdBuildHover::None;
+        cmd.remove(*entity);
+    }
+
+    let mut query = <(Entity, Read<UserSelectRect>)>::query();
+
+    for (entity, UserSelectRect(rect)) in query.iter(world) {
+        let mut tiles = selected_tile.tiles();
+        tiles.extend(rect.tiles());
+
+        *selected_tile = TdTileSelect::of_tiles(tiles);
+        *build_hover = TdBuildHover::None;
         cmd.remove(*entity);
     }
 
     let mut query = <(Entity, Read<UserUnselectTile>)>::query();
     for (entity, _) in query.iter(world) {
         *selected_tile = TdTileSelect::None;
+        *build_hover = TdBuildHover::None;
         cmd.remove(*entity);
     }
 
-    if let TdTileSelect::Selected {
-        x: tile_x,
-        y: tile_y,
-        structures,
-    } = selected_tile
-    {
-        structures.clear();
-
-        for (entity, structure, pos, maybe_sell_value) in
-            <(Entity, Read<Structure>, Read<Position>, TryRead<SellValue>)>::query().iter(world)
-        {
-            let (pos_tile_x, pos_tile_y) = pos.to_tile_coords();
-            if (pos_tile_x, pos_tile_y) != (*tile_x, *tile_y) {
-                continue;
-            }
-
-            structures.push(SelectedStructure {
-                entity: *entity,
-                kind: structure.0,
-                sell_value: maybe_sell_value.map(|sv| sv.0.clone()),
-            });
+    // the structures are looked up fresh every tick, since they come and go
+    let tiles = selected_tile.tiles();
+
+    let structures = match selected_tile {
+        TdTileSelect::None => return,
+        TdTileSelect::Selected { structures, .. } | TdTileSelect::Many { structures, .. } => structures,
+    };
+
+    structures.clear();
+
+    for (entity, structure, pos, maybe_sell_value) in <(Entity, Read<Structure>, Read<Position>, TryRead<SellValue>)>::query().iter(world) {
+        let tile = pos.to_tile_coords();
+        if !tiles.contains(&tile) {
+            continue;
         }
+
+        structures.push(SelectedStructure {
+            entity: *entity,
+            tile,
+            kind: structure.0,
+            sell_value: maybe_sell_value.map(|sv| sv.0.clone()),
+        });
     }
 }
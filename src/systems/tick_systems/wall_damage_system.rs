@@ -0,0 +1,19 @@
+//! Handler for mobs attacking walls which stand in their way
+
+use legion::{systems::CommandBuffer, world::SubWorld, *};
+
+use crate::{components::*, resources::*};
+
+#[system]
+#[read_component(DamageWall)]
+pub(super) fn damage_walls(#[resource] map: &mut Map, cmd: &mut CommandBuffer, world: &SubWorld) {
+    let mut query = <(Entity, Read<DamageWall>)>::query();
+
+    for (entity, damage) in query.iter(world) {
+        let &DamageWall { tile_x, tile_y, amount } = damage;
+
+        map.damage_wall(tile_x, tile_y, amount);
+
+        cmd.remove(*entity);
+    }
+}